@@ -21,6 +21,8 @@ pub struct ButtonData<Color: Copy> {
     pub visibility: Visibility,
     state: ButtonState,
     pub style: ButtonStyle<Color>,
+    /// If set, [`Self::style`] follows the theme's style for this class.
+    pub theme_class: Option<&'static str>,
 }
 
 impl<Color: Copy> ButtonData<Color> {
@@ -29,9 +31,17 @@ impl<Color: Copy> ButtonData<Color> {
             visibility: Visibility::Occlude,
             state: ButtonState::Normal,
             style,
+            theme_class: None,
         }
     }
 
+    /// Take the style from the theme's `class` from now on.
+    #[inline]
+    pub const fn themed(mut self, class: &'static str) -> Self {
+        self.theme_class = Some(class);
+        self
+    }
+
     #[inline]
     pub const fn state(&self) -> ButtonState {
         self.state
@@ -71,9 +81,10 @@ impl<Color: Copy, T: Node, F> Node for Button<Color, T, F> {
     }
 }
 
-impl<Color: Copy, TB, T: TickNode<TB>, F: FnMut(&mut ButtonData<Color>)> TickNode<TB> for Button<Color, T, F> {
+impl<Color: Copy, TB: TickBackend<Color = Color>, T: TickNode<TB>, F: FnMut(&mut ButtonData<Color>)> TickNode<TB> for Button<Color, T, F> {
     #[inline]
     fn dibs_tick(&mut self, tb: &mut TB, slot: Rect, events: &mut Events) {
+        tb.theme().restyle(&mut self.data.style, self.data.theme_class);
         let (item, slot) = self.child_mut(slot);
         item.dibs_tick(tb, slot, events);
     }
@@ -89,7 +100,7 @@ impl<Color: Copy, TB, T: TickNode<TB>, F: FnMut(&mut ButtonData<Color>)> TickNod
         }
 
         if !matches!(self.data.state, ButtonState::Disabled) {
-            if events.left_mouse_release {
+            if events.is_mouse_released(MouseButton::Left) {
                 self.data.state = ButtonState::Hover;
             }
 
@@ -98,7 +109,7 @@ impl<Color: Copy, TB, T: TickNode<TB>, F: FnMut(&mut ButtonData<Color>)> TickNod
                     self.data.state = ButtonState::Hover;
                 }
 
-                if hover.left_mouse_press.take().is_some() {
+                if hover.take_press(MouseButton::Left).is_some() {
                     self.data.state = ButtonState::Press;
                     (self.on_press)(&mut self.data);
                 }
@@ -143,3 +154,62 @@ impl<Color: Copy, T: Node, F> ParentNode for Button<Color, T, F> {
         &mut self.content
     }
 }
+
+#[cfg(test)]
+mod button_tests {
+    use std::cell::Cell;
+    use crate::headless::*;
+    use super::*;
+
+    const SLOT: Rect = Rect { x_min: 10.0, y_min: 10.0, x_max: 60.0, y_max: 30.0 };
+    const INSIDE: Point = Point { x: 20.0, y: 20.0 };
+    const OUTSIDE: Point = Point { x: 100.0, y: 100.0 };
+
+    #[test]
+    fn test_state_transitions() {
+        let presses = Cell::new(0);
+        let mut button = Button::new(ButtonData::new(BUTTON_STYLE), |_: &mut ButtonData<&str>| presses.set(presses.get() + 1), Empty);
+        let mut hb = HeadlessBackend::default();
+
+        hb.run(&mut button, SLOT, [[InputAction::MoveMouse(OUTSIDE)]]);
+        assert!(button.data.state() == ButtonState::Normal);
+        assert_eq!(hb.rects().collect::<Vec<_>>(), [(&SLOT, &"normal")]);
+
+        hb.run(&mut button, SLOT, [[InputAction::MoveMouse(INSIDE)]]);
+        assert!(button.data.state() == ButtonState::Hover);
+        assert_eq!(hb.rects().collect::<Vec<_>>(), [(&SLOT, &"hover")]);
+
+        hb.run(&mut button, SLOT, [[InputAction::Press(MouseButton::Left)]]);
+        assert!(button.data.state() == ButtonState::Press);
+        assert_eq!(presses.get(), 1);
+
+        // holding doesn't press again
+        hb.run(&mut button, SLOT, [[]]);
+        assert!(button.data.state() == ButtonState::Press);
+        assert_eq!(presses.get(), 1);
+
+        hb.run(&mut button, SLOT, [[InputAction::Release(MouseButton::Left)]]);
+        assert!(button.data.state() == ButtonState::Hover);
+
+        hb.run(&mut button, SLOT, [[InputAction::MoveMouse(OUTSIDE)]]);
+        assert!(button.data.state() == ButtonState::Normal);
+        assert_eq!(presses.get(), 1);
+    }
+
+    #[test]
+    fn test_press_outside() {
+        let presses = Cell::new(0);
+        let mut button = Button::new(ButtonData::new(BUTTON_STYLE), |_: &mut ButtonData<&str>| presses.set(presses.get() + 1), Empty);
+        let mut hb = HeadlessBackend::default();
+
+        hb.click(&mut button, SLOT, OUTSIDE, MouseButton::Left);
+        assert_eq!(presses.get(), 0);
+        assert!(button.data.state() == ButtonState::Normal);
+
+        // only the left button presses
+        hb.click(&mut button, SLOT, INSIDE, MouseButton::Right);
+        assert_eq!(presses.get(), 0);
+        hb.click(&mut button, SLOT, INSIDE, MouseButton::Left);
+        assert_eq!(presses.get(), 1);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::*;
+
+/// Clip and transform stacks for implementing [`DrawBackend`].
+///
+/// Keeps everything in screen space, so the backend only has to apply [`Self::clip`] and [`Self::transform`].
+pub struct DrawState {
+    /// Already transformed and intersected with the clips outside them.
+    clips: Vec<Rect>,
+    /// Already composed with the transforms outside them.
+    transforms: Vec<Transform>,
+}
+
+impl DrawState {
+    pub const fn new() -> Self {
+        Self {
+            clips: Vec::new(),
+            transforms: Vec::new(),
+        }
+    }
+
+    /// The current clip in screen space, or [`None`] if drawing isn't clipped.
+    #[inline]
+    pub fn clip(&self) -> Option<&Rect> {
+        self.clips.last()
+    }
+
+    /// The current transform from drawing space to screen space.
+    #[inline]
+    pub fn transform(&self) -> Transform {
+        self.transforms.last().copied().unwrap_or(Transform::IDENTITY)
+    }
+
+    /// Returns the new clip in screen space.
+    pub fn push_clip(&mut self, rect: &Rect) -> Rect {
+        let rect = self.transform().apply_rect(rect);
+        let rect = self.clip().map_or(rect, |outer| rect.intersection(outer));
+        self.clips.push(rect);
+        rect
+    }
+
+    /// Returns the clip that is back in effect, if any.
+    pub fn pop_clip(&mut self) -> Option<&Rect> {
+        self.clips.pop().expect("pop_clip should match a push_clip");
+        self.clip()
+    }
+
+    /// Returns the new transform to screen space.
+    pub fn push_transform(&mut self, transform: &Transform) -> Transform {
+        let transform = self.transform().compose(transform);
+        self.transforms.push(transform);
+        transform
+    }
+
+    /// Returns the transform that is back in effect.
+    pub fn pop_transform(&mut self) -> Transform {
+        self.transforms.pop().expect("pop_transform should match a push_transform");
+        self.transform()
+    }
+}
+
+impl Default for DrawState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -54,13 +54,75 @@ impl<T> Event<T> {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    /// The "back" side button.
+    Back,
+    /// The "forward" side button.
+    Forward,
+}
+
+impl MouseButton {
+    pub const ALL: [Self; 5] = [Self::Left, Self::Right, Self::Middle, Self::Back, Self::Forward];
+}
+
+/// A mouse button press.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Click {
+    pub button: MouseButton,
+    /// 1 for a single click, 2 for a double click, 3 for a triple click, and so on.
+    pub count: u32,
+}
+
 #[derive(Clone, Copy)]
 pub struct MouseEvent {
     pub position: Point,
-    pub left_mouse_press: Event<()>,
+    /// Presses this frame, indexed by [`MouseButton`].
+    pub presses: [Event<Click>; 5],
     pub scroll: Event<Point>,
 }
 
+impl MouseEvent {
+    #[inline]
+    pub fn is_pressed(&self, button: MouseButton) -> bool {
+        self.presses[button as usize].is_some()
+    }
+
+    #[inline]
+    pub fn take_press(&mut self, button: MouseButton) -> Option<Click> {
+        self.presses[button as usize].take()
+    }
+
+    /// Take a press of `button` that is at least the `count`th click in a row.
+    #[inline]
+    pub fn take_multi_press(&mut self, button: MouseButton, count: u32) -> Option<Click> {
+        self.presses[button as usize].take_if(|click| click.count >= count)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DragKind {
+    /// The mouse moved past [`InputState::DRAG_THRESHOLD`] while the button was held.
+    Start,
+    Move,
+    /// The button was released. Sent even if the mouse didn't move on the final frame.
+    End,
+}
+
+#[derive(Clone, Copy)]
+pub struct DragEvent {
+    pub button: MouseButton,
+    pub kind: DragKind,
+    /// Where the button was pressed.
+    pub start: Point,
+    pub position: Point,
+    /// Movement since the previous drag event.
+    pub delta: Point,
+}
+
 impl Event<MouseEvent> {
     #[inline]
     pub fn is_some_and_overlapping(&self, region: Rect) -> bool {
@@ -73,23 +135,447 @@ impl Event<MouseEvent> {
     }
 }
 
-#[derive(Clone, Copy)]
+/// Keyboard keys, independent of the backend.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Key {
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Space, Enter, Escape, Tab, Backspace, Delete, Insert,
+    Left, Right, Up, Down, Home, End, PageUp, PageDown,
+    Minus, Equal, LeftBracket, RightBracket, Backslash, Semicolon, Apostrophe, Comma, Period, Slash, Grave,
+    LeftShift, RightShift, LeftControl, RightControl, LeftAlt, RightAlt, LeftSuper, RightSuper,
+}
+
+impl Key {
+    pub const ALL: [Self; 82] = {
+        use Key::*;
+        [
+            A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+            Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+            F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+            Space, Enter, Escape, Tab, Backspace, Delete, Insert,
+            Left, Right, Up, Down, Home, End, PageUp, PageDown,
+            Minus, Equal, LeftBracket, RightBracket, Backslash, Semicolon, Apostrophe, Comma, Period, Slash, Grave,
+            LeftShift, RightShift, LeftControl, RightControl, LeftAlt, RightAlt, LeftSuper, RightSuper,
+        ]
+    };
+
+    pub const DIGITS: [Self; 10] = {
+        use Key::*;
+        [Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9]
+    };
+}
+
+/// Modifier keys held down, either side.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub super_key: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Self = Self { shift: false, ctrl: false, alt: false, super_key: false };
+    pub const SHIFT: Self = Self { shift: true, ..Self::NONE };
+    pub const CTRL: Self = Self { ctrl: true, ..Self::NONE };
+    pub const ALT: Self = Self { alt: true, ..Self::NONE };
+    pub const CTRL_SHIFT: Self = Self { ctrl: true, shift: true, ..Self::NONE };
+    pub const CTRL_ALT: Self = Self { ctrl: true, alt: true, ..Self::NONE };
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeyEventKind {
+    Press,
+    /// The key was held long enough for the OS to repeat it.
+    Repeat,
+    Release,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub kind: KeyEventKind,
+    /// Modifiers held at the time of the event.
+    pub modifiers: Modifiers,
+}
+
+impl KeyEvent {
+    /// Whether this is a press or repeat of `key` with exactly `modifiers` held.
+    #[inline]
+    pub fn is_shortcut(&self, key: Key, modifiers: Modifiers) -> bool {
+        self.key == key && self.kind != KeyEventKind::Release && self.modifiers == modifiers
+    }
+}
+
+/// Identifies a widget that can hold keyboard focus.
+/// Any value works as long as no two focusable widgets share it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FocusId(pub u32);
+
+struct DragTracker {
+    button: MouseButton,
+    start: Point,
+    last: Point,
+    is_dragging: bool,
+}
+
+/// Input tracking that carries over from one frame to the next, for focus, multi-clicks and drags.
+pub struct InputState {
+    focus: Option<FocusId>,
+    last_click: Option<(Click, f64, Point)>,
+    drag: Option<DragTracker>,
+}
+
+impl InputState {
+    /// How far the mouse has to move while a button is held before it counts as a drag.
+    pub const DRAG_THRESHOLD: f32 = 4.0;
+    /// Longest time between presses, in seconds, for them to count as a multi-click.
+    pub const MULTI_CLICK_TIME: f64 = 0.4;
+
+    pub const fn new() -> Self {
+        Self {
+            focus: None,
+            last_click: None,
+            drag: None,
+        }
+    }
+
+    /// Keep what needs to carry over from this frame's events, such as focus changes.
+    /// Call after ticking.
+    #[inline]
+    pub fn end_frame(&mut self, events: &Events) {
+        self.focus = events.focus;
+    }
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline]
+fn distance(a: Point, b: Point) -> f32 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+/// Input for one frame.
+///
+/// Key events go to the focused widget first: it should take them in [`TickNode::dibs_tick`] using
+/// [`Events::take_focused_key_if`], since that runs before any active/inactive tick.
+/// Whatever it leaves behind is available to everything else (such as global shortcuts) in later ticks.
+#[derive(Clone)]
 pub struct Events {
     pub mouse_event: Event<MouseEvent>,
-    /// left mouse release is not consumable, becasuse everything
+    /// mouse release is not consumable, becasuse everything
     /// should be allowed to reset even if something else "consumed" it
-    pub left_mouse_release: bool,
+    mouse_released: [bool; 5],
+    mouse_down: [bool; 5],
+    /// Dragging with any button. Should be taken by whatever took the press that started it,
+    /// in [`TickNode::dibs_tick`] so that it keeps the drag even after the mouse leaves it.
+    pub drag: Event<DragEvent>,
+    /// Key presses, repeats and releases, in [`Key::ALL`] order.
+    pub key_events: Vec<Event<KeyEvent>>,
+    /// Modifier keys are not consumable, since they only change the meaning of other input.
+    pub modifiers: Modifiers,
+    /// Keys held down. Not consumable, for the same reason as mouse release.
+    keys_down: Vec<Key>,
+    /// Characters typed this frame, with keyboard layout and shift already applied.
+    pub text: Event<String>,
+    focus: Option<FocusId>,
 }
 
 impl Events {
-    pub fn check<IB: InputBackend>(tb: &mut IB) -> Self {
+    /// Poll the backend. `state` should be the same every frame, and [`InputState::end_frame`] should be called after ticking.
+    pub fn check<IB: InputBackend>(tb: &mut IB, state: &mut InputState) -> Self {
+        let keys_down = Key::ALL.into_iter().filter(|&key| tb.is_key_down(key)).collect::<Vec<_>>();
+        let is_down = |a, b| keys_down.contains(&a) || keys_down.contains(&b);
+        let modifiers = Modifiers {
+            shift: is_down(Key::LeftShift, Key::RightShift),
+            ctrl: is_down(Key::LeftControl, Key::RightControl),
+            alt: is_down(Key::LeftAlt, Key::RightAlt),
+            super_key: is_down(Key::LeftSuper, Key::RightSuper),
+        };
+
+        let mut key_events = Vec::new();
+        for key in Key::ALL {
+            let kind = if tb.is_key_pressed(key) {
+                KeyEventKind::Press
+            } else if tb.is_key_pressed_repeat(key) {
+                KeyEventKind::Repeat
+            } else if tb.is_key_released(key) {
+                KeyEventKind::Release
+            } else {
+                continue;
+            };
+            key_events.push(Event::new(Some(KeyEvent { key, kind, modifiers })));
+        }
+
+        let mut text = String::new();
+        while let Some(ch) = tb.char_pressed() {
+            text.push(ch);
+        }
+
+        let position = tb.mouse_position();
+        let time = tb.time();
+        let mouse_released = MouseButton::ALL.map(|button| tb.is_mouse_button_released(button));
+        let mouse_down = MouseButton::ALL.map(|button| tb.is_mouse_button_down(button));
+        let presses = MouseButton::ALL.map(|button| {
+            if !tb.is_mouse_button_pressed(button) {
+                return Event::new(None);
+            }
+            let count = match state.last_click {
+                Some((last, last_time, last_position)) if last.button == button
+                    && time - last_time <= InputState::MULTI_CLICK_TIME
+                    && distance(position, last_position) < InputState::DRAG_THRESHOLD => last.count + 1,
+                _ => 1,
+            };
+            let click = Click { button, count };
+            state.last_click = Some((click, time, position));
+            if state.drag.is_none() {
+                state.drag = Some(DragTracker { button, start: position, last: position, is_dragging: false });
+            }
+            Event::new(Some(click))
+        });
+
+        let mut drag = None;
+        if let Some(tracker) = &mut state.drag {
+            let delta = Point { x: position.x - tracker.last.x, y: position.y - tracker.last.y };
+            let event = |kind| DragEvent { button: tracker.button, kind, start: tracker.start, position, delta };
+            if mouse_released[tracker.button as usize] || !mouse_down[tracker.button as usize] {
+                drag = tracker.is_dragging.then(|| event(DragKind::End));
+                state.drag = None;
+            } else if !tracker.is_dragging {
+                if distance(position, tracker.start) >= InputState::DRAG_THRESHOLD {
+                    drag = Some(event(DragKind::Start));
+                    tracker.is_dragging = true;
+                    tracker.last = position;
+                }
+            } else if delta.x != 0.0 || delta.y != 0.0 {
+                drag = Some(event(DragKind::Move));
+                tracker.last = position;
+            }
+        }
+
         Self {
             mouse_event: Event::new(Some(MouseEvent {
-                position: tb.mouse_position(),
-                left_mouse_press: Event::new(tb.is_m1_pressed().then_some(())),
+                position,
+                presses,
                 scroll: Event::new(Some(tb.mouse_wheel_move())),
             })),
-            left_mouse_release: tb.is_m1_released(),
+            mouse_released,
+            mouse_down,
+            drag: Event::new(drag),
+            key_events,
+            modifiers,
+            keys_down,
+            text: Event::new((!text.is_empty()).then_some(text)),
+            focus: state.focus,
+        }
+    }
+
+    #[inline]
+    pub fn is_mouse_released(&self, button: MouseButton) -> bool {
+        self.mouse_released[button as usize]
+    }
+
+    #[inline]
+    pub fn is_mouse_down(&self, button: MouseButton) -> bool {
+        self.mouse_down[button as usize]
+    }
+
+    #[inline]
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    /// Take the first key event matching the predicate.
+    pub fn take_key_if<P: FnMut(&KeyEvent) -> bool>(&mut self, mut predicate: P) -> Option<KeyEvent> {
+        self.key_events.iter_mut().find_map(|event| event.take_if(&mut predicate))
+    }
+
+    /// Take a press or repeat of `key` with exactly `modifiers` held.
+    #[inline]
+    pub fn take_shortcut(&mut self, key: Key, modifiers: Modifiers) -> Option<KeyEvent> {
+        self.take_key_if(|event| event.is_shortcut(key, modifiers))
+    }
+
+    #[inline]
+    pub const fn focus(&self) -> Option<FocusId> {
+        self.focus
+    }
+
+    #[inline]
+    pub fn is_focused(&self, id: FocusId) -> bool {
+        self.focus == Some(id)
+    }
+
+    /// Give keyboard focus to a widget, or take it away from everything with [`None`].
+    /// Takes effect immediately and carries over to the next frame through [`InputState::end_frame`].
+    #[inline]
+    pub fn set_focus(&mut self, focus: Option<FocusId>) {
+        self.focus = focus;
+    }
+
+    /// Take the typed text, but only if `id` has focus.
+    #[inline]
+    pub fn take_focused_text(&mut self, id: FocusId) -> Option<String> {
+        if self.is_focused(id) {
+            self.text.take()
+        } else { None }
+    }
+
+    /// Same as [`Self::take_key_if`], but only if `id` has focus.
+    #[inline]
+    pub fn take_focused_key_if<P: FnMut(&KeyEvent) -> bool>(&mut self, id: FocusId, predicate: P) -> Option<KeyEvent> {
+        if self.is_focused(id) {
+            self.take_key_if(predicate)
+        } else { None }
+    }
+}
+
+/// Moves mouse and drag positions into the inner space of `transform` for as long as it lives,
+/// so that nodes drawn through [`DrawBackend::push_transform`] see the mouse where they drew.
+pub(crate) struct TransformedEvents<'a> {
+    original_mouse_pos: Option<Point>,
+    original_drag: Option<(Point, Point, Point)>,
+    pub events: &'a mut Events,
+}
+
+impl Drop for TransformedEvents<'_> {
+    fn drop(&mut self) {
+        if let Some(event) = self.events.mouse_event.event.as_mut() {
+            event.position = self.original_mouse_pos.expect("original position should be Some if mouse event exists");
+        }
+        if let Some(drag) = self.events.drag.event.as_mut() {
+            (drag.start, drag.position, drag.delta) = self.original_drag.expect("original drag should be Some if drag event exists");
         }
     }
 }
+
+impl<'a> TransformedEvents<'a> {
+    pub fn new(events: &'a mut Events, transform: Transform) -> Self {
+        let original_mouse_pos = events.mouse_event.event.as_mut().map(|event| {
+            let original = event.position;
+            event.position = transform.invert(original);
+            original
+        });
+        let original_drag = events.drag.event.as_mut().map(|drag| {
+            let original = (drag.start, drag.position, drag.delta);
+            drag.start = transform.invert(drag.start);
+            drag.position = transform.invert(drag.position);
+            drag.delta = Point { x: drag.delta.x / transform.scale, y: drag.delta.y / transform.scale };
+            original
+        });
+        Self {
+            original_mouse_pos,
+            original_drag,
+            events,
+        }
+    }
+}
+
+#[cfg(test)]
+mod events_tests {
+    use crate::headless::*;
+    use super::*;
+
+    const SLOT: Rect = rect(0.0, 0.0, 100.0, 100.0);
+    const HOME: Point = Point { x: 10.0, y: 10.0 };
+
+    /// Apply `actions` and poll a frame with nothing to take the events.
+    fn step(hb: &mut HeadlessBackend<&str>, actions: impl IntoIterator<Item = InputAction>) -> Events {
+        hb.run(&mut Empty, SLOT, [actions]).unwrap()
+    }
+
+    /// Press and release the left button at `position`, returning the click count of the press.
+    fn click_count(hb: &mut HeadlessBackend<&str>, position: Point) -> Option<u32> {
+        let events = step(hb, [InputAction::MoveMouse(position), InputAction::Press(MouseButton::Left)]);
+        step(hb, [InputAction::Release(MouseButton::Left)]);
+        events.mouse_event.event.and_then(|mouse| mouse.presses[MouseButton::Left as usize].event).map(|click| click.count)
+    }
+
+    /// Kind, position and delta of the drag event, if any.
+    fn drag(events: &Events) -> Option<(DragKind, Point, Point)> {
+        events.drag.event.map(|drag| (drag.kind, drag.position, drag.delta))
+    }
+
+    #[test]
+    fn test_multi_click() {
+        let mut hb = HeadlessBackend::<&str>::default();
+
+        assert_eq!(click_count(&mut hb, HOME), Some(1));
+        // a little movement between clicks doesn't matter
+        assert_eq!(click_count(&mut hb, Point { x: 13.0, y: 10.0 }), Some(2));
+        assert_eq!(click_count(&mut hb, HOME), Some(3));
+
+        // as long as the clicks are close enough in time
+        hb.run(&mut Empty, SLOT, vec![[]; 20]);
+        assert_eq!(click_count(&mut hb, HOME), Some(4));
+        let waited = (InputState::MULTI_CLICK_TIME / HeadlessBackend::<&str>::FRAME_TIME) as usize + 1;
+        hb.run(&mut Empty, SLOT, vec![[]; waited]);
+        assert_eq!(click_count(&mut hb, HOME), Some(1));
+
+        // and in space
+        assert_eq!(click_count(&mut hb, Point { x: 10.0, y: 10.0 + InputState::DRAG_THRESHOLD }), Some(1));
+
+        // and with the same button
+        let events = step(&mut hb, [InputAction::Press(MouseButton::Right)]);
+        assert_eq!(events.mouse_event.event.unwrap().presses[MouseButton::Right as usize].event, Some(Click { button: MouseButton::Right, count: 1 }));
+    }
+
+    #[test]
+    fn test_click_becomes_drag() {
+        let mut hb = HeadlessBackend::<&str>::default();
+
+        let events = step(&mut hb, [InputAction::MoveMouse(HOME), InputAction::Press(MouseButton::Left)]);
+        assert!(events.mouse_event.event.unwrap().is_pressed(MouseButton::Left));
+        assert_eq!(drag(&events), None);
+
+        // not far enough yet
+        let events = step(&mut hb, [InputAction::MoveMouse(Point { x: 13.0, y: 10.0 })]);
+        assert_eq!(drag(&events), None);
+
+        let events = step(&mut hb, [InputAction::MoveMouse(Point { x: 14.0, y: 10.0 })]);
+        assert_eq!(drag(&events), Some((DragKind::Start, Point { x: 14.0, y: 10.0 }, Point { x: 4.0, y: 0.0 })));
+        let start = events.drag.event.unwrap();
+        assert_eq!((start.button, start.start), (MouseButton::Left, HOME));
+    }
+
+    #[test]
+    fn test_drag_sequence() {
+        let mut hb = HeadlessBackend::<&str>::default();
+
+        step(&mut hb, [InputAction::MoveMouse(HOME), InputAction::Press(MouseButton::Left)]);
+        let events = step(&mut hb, [InputAction::MoveMouse(Point { x: 20.0, y: 10.0 })]);
+        assert_eq!(drag(&events), Some((DragKind::Start, Point { x: 20.0, y: 10.0 }, Point { x: 10.0, y: 0.0 })));
+
+        // holding still doesn't send anything
+        let events = step(&mut hb, []);
+        assert_eq!(drag(&events), None);
+
+        let events = step(&mut hb, [InputAction::MoveMouse(Point { x: 26.0, y: 15.0 })]);
+        assert_eq!(drag(&events), Some((DragKind::Move, Point { x: 26.0, y: 15.0 }, Point { x: 6.0, y: 5.0 })));
+        assert_eq!(events.drag.event.unwrap().start, HOME);
+
+        // the release ends the drag even without moving
+        let events = step(&mut hb, [InputAction::Release(MouseButton::Left)]);
+        assert_eq!(drag(&events), Some((DragKind::End, Point { x: 26.0, y: 15.0 }, Point { x: 0.0, y: 0.0 })));
+
+        let events = step(&mut hb, [InputAction::MoveMouse(HOME)]);
+        assert_eq!(drag(&events), None);
+    }
+
+    #[test]
+    fn test_release_without_drag() {
+        let mut hb = HeadlessBackend::<&str>::default();
+
+        step(&mut hb, [InputAction::MoveMouse(HOME), InputAction::Press(MouseButton::Left)]);
+        step(&mut hb, [InputAction::MoveMouse(Point { x: 12.0, y: 10.0 })]);
+        let events = step(&mut hb, [InputAction::Release(MouseButton::Left)]);
+        assert!(events.is_mouse_released(MouseButton::Left));
+        assert_eq!(drag(&events), None);
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::VecDeque;
+use crate::*;
+
+/// One change to the input, applied before a frame.
+#[derive(Clone, PartialEq, Debug)]
+pub enum InputAction {
+    MoveMouse(Point),
+    Press(MouseButton),
+    Release(MouseButton),
+    KeyDown(Key),
+    KeyUp(Key),
+    /// Have the OS repeat a held key this frame.
+    RepeatKey(Key),
+    /// Characters to type this frame.
+    Type(String),
+    Scroll(Point),
+}
+
+/// A draw call, in screen space with transforms already applied.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DrawCommand<Color> {
+    Rect { rect: Rect, color: Color },
+    RectOutline { rect: Rect, thickness: f32, color: Color },
+    RoundedRect { rect: Rect, radius: f32, color: Color },
+    RoundedRectOutline { rect: Rect, radius: f32, thickness: f32, color: Color },
+    Line { start: Point, end: Point, thickness: f32, color: Color },
+    Text { text: String, top_left: Point, font_size: f32, color: Color },
+    Image { image: u32, source: Option<Rect>, dest: Rect, tint: Color },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct DrawCall<Color> {
+    pub command: DrawCommand<Color>,
+    /// The clip in effect, in screen space.
+    pub clip: Option<Rect>,
+}
+
+/// An input, tick and draw backend that doesn't need a window, for testing nodes.
+///
+/// Input comes from [`InputAction`]s instead of a device, and drawing is recorded into [`Self::display_list`].
+/// Images are whatever `u32` ids the test likes, and text is measured as if every character were half as wide as it is tall.
+pub struct HeadlessBackend<Color: Copy> {
+    time: f64,
+    mouse_position: Point,
+    mouse_down: [bool; 5],
+    prev_mouse_down: [bool; 5],
+    keys_down: Vec<Key>,
+    prev_keys_down: Vec<Key>,
+    repeats: Vec<Key>,
+    chars: VecDeque<char>,
+    wheel: Point,
+    input_state: InputState,
+    draw_state: DrawState,
+    pub theme: Theme<Color>,
+    pub clipboard: Option<String>,
+    /// Everything drawn in the most recent frame.
+    pub display_list: Vec<DrawCall<Color>>,
+}
+
+impl<Color: Copy> HeadlessBackend<Color> {
+    /// Seconds between frames.
+    pub const FRAME_TIME: f64 = 1.0 / 60.0;
+    /// Width of each character relative to the font size.
+    pub const CHAR_WIDTH: f32 = 0.5;
+
+    pub fn new(theme: Theme<Color>) -> Self {
+        Self {
+            time: 0.0,
+            mouse_position: Point { x: 0.0, y: 0.0 },
+            mouse_down: [false; 5],
+            prev_mouse_down: [false; 5],
+            keys_down: Vec::new(),
+            prev_keys_down: Vec::new(),
+            repeats: Vec::new(),
+            chars: VecDeque::new(),
+            wheel: Point { x: 0.0, y: 0.0 },
+            input_state: InputState::new(),
+            draw_state: DrawState::new(),
+            theme,
+            clipboard: None,
+            display_list: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub const fn input_state(&self) -> &InputState {
+        &self.input_state
+    }
+
+    pub fn apply(&mut self, action: InputAction) {
+        match action {
+            InputAction::MoveMouse(position) => self.mouse_position = position,
+            InputAction::Press(button) => self.mouse_down[button as usize] = true,
+            InputAction::Release(button) => self.mouse_down[button as usize] = false,
+            InputAction::KeyDown(key) => if !self.keys_down.contains(&key) {
+                self.keys_down.push(key);
+            },
+            InputAction::KeyUp(key) => self.keys_down.retain(|&k| k != key),
+            InputAction::RepeatKey(key) => self.repeats.push(key),
+            InputAction::Type(text) => self.chars.extend(text.chars()),
+            InputAction::Scroll(Point { x, y }) => {
+                self.wheel.x += x;
+                self.wheel.y += y;
+            }
+        }
+    }
+
+    /// Run one frame the way an application would: poll events, tick `node`, then draw it.
+    /// Returns the events that nothing took.
+    pub fn frame<N: TickNode<Self> + DrawNode<Self>>(&mut self, node: &mut N, slot: Rect) -> Events {
+        let mut input_state = std::mem::take(&mut self.input_state);
+        let mut events = Events::check(self, &mut input_state);
+        node.dibs_tick(self, slot, &mut events);
+        if events.mouse_event.is_some_and_overlapping(slot) {
+            node.active_tick(self, slot, &mut events);
+        } else {
+            node.inactive_tick(self, slot, &events);
+        }
+        input_state.end_frame(&events);
+        self.input_state = input_state;
+
+        self.display_list.clear();
+        node.draw(self, slot);
+        debug_assert!(self.draw_state.clip().is_none(), "every push_clip should have a matching pop_clip");
+
+        self.time += Self::FRAME_TIME;
+        self.prev_mouse_down = self.mouse_down;
+        self.prev_keys_down.clone_from(&self.keys_down);
+        self.repeats.clear();
+        self.chars.clear();
+        self.wheel = Point { x: 0.0, y: 0.0 };
+        events
+    }
+
+    /// Apply each group of actions and run a frame after it. Returns the events from the last frame.
+    pub fn run<N, F, A>(&mut self, node: &mut N, slot: Rect, frames: F) -> Option<Events>
+    where
+        N: TickNode<Self> + DrawNode<Self>,
+        F: IntoIterator<Item = A>,
+        A: IntoIterator<Item = InputAction>,
+    {
+        let mut events = None;
+        for actions in frames {
+            for action in actions {
+                self.apply(action);
+            }
+            events = Some(self.frame(node, slot));
+        }
+        events
+    }
+
+    /// Move to `position`, press and release `button` over two frames.
+    pub fn click<N: TickNode<Self> + DrawNode<Self>>(&mut self, node: &mut N, slot: Rect, position: Point, button: MouseButton) {
+        self.run(node, slot, [
+            vec![InputAction::MoveMouse(position), InputAction::Press(button)],
+            vec![InputAction::Release(button)],
+        ]);
+    }
+
+    /// Press `button` at `from`, move to `to`, and release it, over enough frames to count as a drag.
+    pub fn drag<N: TickNode<Self> + DrawNode<Self>>(&mut self, node: &mut N, slot: Rect, from: Point, to: Point, button: MouseButton) {
+        self.run(node, slot, [
+            vec![InputAction::MoveMouse(from), InputAction::Press(button)],
+            vec![InputAction::MoveMouse(to)],
+            vec![InputAction::Release(button)],
+        ]);
+    }
+
+    /// The rectangles filled this frame, in draw order.
+    pub fn rects(&self) -> impl Iterator<Item = (&Rect, &Color)> {
+        self.display_list.iter().filter_map(|call| match &call.command {
+            DrawCommand::Rect { rect, color } => Some((rect, color)),
+            _ => None,
+        })
+    }
+
+    fn record(&mut self, command: DrawCommand<Color>) {
+        let clip = self.draw_state.clip().copied();
+        self.display_list.push(DrawCall { command, clip });
+    }
+}
+
+impl<Color: Copy + Default> Default for HeadlessBackend<Color> {
+    /// A backend whose theme colors everything [`Color::default`].
+    fn default() -> Self {
+        Self::new(Theme::new("default", Palette::splat(Color::default())))
+    }
+}
+
+/// A button style whose colors name the state they're drawn in, for the tests of nodes with buttons.
+#[cfg(test)]
+pub(crate) const BUTTON_STYLE: ButtonStyle<&str> = ButtonStyle {
+    disabled_color: "disabled",
+    normal_color: "normal",
+    hover_color: "hover",
+    press_color: "press",
+};
+
+#[cfg(test)]
+pub(crate) const fn rect(x_min: f32, y_min: f32, x_max: f32, y_max: f32) -> Rect {
+    Rect { x_min, y_min, x_max, y_max }
+}
+
+/// A fixed size child that remembers where, in its own space, it was last pressed, and draws its slot as "probe".
+#[cfg(test)]
+pub(crate) struct Probe {
+    pub width: f32,
+    pub height: f32,
+    pub pressed_at: Option<Point>,
+}
+
+#[cfg(test)]
+impl Probe {
+    pub const fn new(width: f32, height: f32) -> Self {
+        Self { width, height, pressed_at: None }
+    }
+}
+
+#[cfg(test)]
+impl Node for Probe {
+    fn size_range(&self) -> ((f32, Option<f32>), (f32, Option<f32>)) {
+        ((self.width, Some(self.width)), (self.height, Some(self.height)))
+    }
+}
+
+#[cfg(test)]
+impl<TB> TickNode<TB> for Probe {
+    fn active_tick(&mut self, _tb: &mut TB, _slot: Rect, events: &mut Events) {
+        if let Some(mouse) = events.mouse_event.event.as_mut() && mouse.take_press(MouseButton::Left).is_some() {
+            self.pressed_at = Some(mouse.position);
+        }
+    }
+}
+
+#[cfg(test)]
+impl<DB: DrawBackend<Color = &'static str>> DrawNode<DB> for Probe {
+    fn draw(&self, d: &mut DB, slot: Rect) {
+        d.draw_rect(&slot, &"probe");
+    }
+}
+
+impl<Color: Copy> InputBackend for HeadlessBackend<Color> {
+    #[inline]
+    fn mouse_position(&mut self) -> Point {
+        self.mouse_position
+    }
+
+    #[inline]
+    fn time(&mut self) -> f64 {
+        self.time
+    }
+
+    #[inline]
+    fn is_mouse_button_down(&mut self, button: MouseButton) -> bool {
+        self.mouse_down[button as usize]
+    }
+
+    #[inline]
+    fn is_mouse_button_pressed(&mut self, button: MouseButton) -> bool {
+        self.mouse_down[button as usize] && !self.prev_mouse_down[button as usize]
+    }
+
+    #[inline]
+    fn is_mouse_button_released(&mut self, button: MouseButton) -> bool {
+        !self.mouse_down[button as usize] && self.prev_mouse_down[button as usize]
+    }
+
+    #[inline]
+    fn mouse_wheel_move(&mut self) -> Point {
+        self.wheel
+    }
+
+    #[inline]
+    fn is_key_down(&mut self, key: Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    #[inline]
+    fn is_key_pressed(&mut self, key: Key) -> bool {
+        self.keys_down.contains(&key) && !self.prev_keys_down.contains(&key)
+    }
+
+    #[inline]
+    fn is_key_pressed_repeat(&mut self, key: Key) -> bool {
+        self.repeats.contains(&key)
+    }
+
+    #[inline]
+    fn is_key_released(&mut self, key: Key) -> bool {
+        !self.keys_down.contains(&key) && self.prev_keys_down.contains(&key)
+    }
+
+    #[inline]
+    fn char_pressed(&mut self) -> Option<char> {
+        self.chars.pop_front()
+    }
+}
+
+impl<Color: Copy> TickBackend for HeadlessBackend<Color> {
+    type Color = Color;
+
+    #[inline]
+    fn theme(&self) -> &Theme<Color> {
+        &self.theme
+    }
+
+    #[inline]
+    fn clipboard_text(&mut self) -> Option<String> {
+        self.clipboard.clone()
+    }
+
+    #[inline]
+    fn set_clipboard_text(&mut self, text: &str) {
+        self.clipboard = Some(text.to_string());
+    }
+}
+
+impl<Color: Copy> DrawBackend for HeadlessBackend<Color> {
+    type Color = Color;
+    type Image = u32;
+
+    #[inline]
+    fn theme(&self) -> &Theme<Color> {
+        &self.theme
+    }
+
+    fn draw_rect(&mut self, rect: &Rect, color: &Color) {
+        let rect = self.draw_state.transform().apply_rect(rect);
+        self.record(DrawCommand::Rect { rect, color: *color });
+    }
+
+    fn draw_rect_outline(&mut self, rect: &Rect, thickness: f32, color: &Color) {
+        let transform = self.draw_state.transform();
+        let rect = transform.apply_rect(rect);
+        self.record(DrawCommand::RectOutline { rect, thickness: thickness * transform.scale, color: *color });
+    }
+
+    fn draw_rounded_rect(&mut self, rect: &Rect, radius: f32, color: &Color) {
+        let transform = self.draw_state.transform();
+        let rect = transform.apply_rect(rect);
+        self.record(DrawCommand::RoundedRect { rect, radius: radius * transform.scale, color: *color });
+    }
+
+    fn draw_rounded_rect_outline(&mut self, rect: &Rect, radius: f32, thickness: f32, color: &Color) {
+        let transform = self.draw_state.transform();
+        let rect = transform.apply_rect(rect);
+        self.record(DrawCommand::RoundedRectOutline {
+            rect,
+            radius: radius * transform.scale,
+            thickness: thickness * transform.scale,
+            color: *color,
+        });
+    }
+
+    fn draw_line(&mut self, start: Point, end: Point, thickness: f32, color: &Color) {
+        let transform = self.draw_state.transform();
+        self.record(DrawCommand::Line {
+            start: transform.apply(start),
+            end: transform.apply(end),
+            thickness: thickness * transform.scale,
+            color: *color,
+        });
+    }
+
+    fn draw_text(&mut self, text: &str, top_left: Point, font_size: f32, color: &Color) {
+        let transform = self.draw_state.transform();
+        self.record(DrawCommand::Text {
+            text: text.to_string(),
+            top_left: transform.apply(top_left),
+            font_size: font_size * transform.scale,
+            color: *color,
+        });
+    }
+
+    #[inline]
+    fn measure_text(&mut self, text: &str, font_size: f32) -> f32 {
+        text.chars().count() as f32 * font_size * Self::CHAR_WIDTH
+    }
+
+    fn draw_image(&mut self, image: &Self::Image, source: Option<&Rect>, dest: &Rect, tint: &Color) {
+        let dest = self.draw_state.transform().apply_rect(dest);
+        self.record(DrawCommand::Image { image: *image, source: source.copied(), dest, tint: *tint });
+    }
+
+    #[inline]
+    fn push_clip(&mut self, rect: &Rect) {
+        self.draw_state.push_clip(rect);
+    }
+
+    #[inline]
+    fn pop_clip(&mut self) {
+        self.draw_state.pop_clip();
+    }
+
+    #[inline]
+    fn push_transform(&mut self, transform: &Transform) {
+        self.draw_state.push_transform(transform);
+    }
+
+    #[inline]
+    fn pop_transform(&mut self) {
+        self.draw_state.pop_transform();
+    }
+}
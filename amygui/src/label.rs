@@ -9,6 +9,25 @@ pub struct LabelStyle<Color: Copy> {
 pub struct Label<Color: Copy> {
     pub style: LabelStyle<Color>,
     pub content: String,
+    /// If set, [`Self::style`] follows the theme's style for this class.
+    pub theme_class: Option<&'static str>,
+}
+
+impl<Color: Copy> Label<Color> {
+    pub fn new(style: LabelStyle<Color>, content: impl Into<String>) -> Self {
+        Self {
+            style,
+            content: content.into(),
+            theme_class: None,
+        }
+    }
+
+    /// Take the style from the theme's `class` from now on.
+    #[inline]
+    pub fn themed(mut self, class: &'static str) -> Self {
+        self.theme_class = Some(class);
+        self
+    }
 }
 
 impl<Color: Copy> Node for Label<Color> {
@@ -17,7 +36,12 @@ impl<Color: Copy> Node for Label<Color> {
     }
 }
 
-impl<Color: Copy, TB> TickNode<TB> for Label<Color> {}
+impl<Color: Copy, TB: TickBackend<Color = Color>> TickNode<TB> for Label<Color> {
+    #[inline]
+    fn dibs_tick(&mut self, tb: &mut TB, _slot: Rect, _events: &mut Events) {
+        tb.theme().restyle(&mut self.style, self.theme_class);
+    }
+}
 
 impl<Color: Copy, DB: DrawBackend<Color = Color>> DrawNode<DB> for Label<Color> {
     #[inline]
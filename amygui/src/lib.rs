@@ -1,16 +1,21 @@
 pub mod align_box;
 pub mod area_box;
 pub mod button;
+pub mod draw_state;
 pub mod events;
+pub mod headless;
 pub mod label;
 pub mod option;
 pub mod overlay_box;
 pub mod pad_box;
 pub mod panel;
 pub mod region;
+pub mod scroll_box;
 pub mod size_box;
 pub mod split_box;
 pub mod stack_box;
+pub mod text_input;
+pub mod theme;
 pub mod uniform_grid;
 pub mod viewport;
 
@@ -24,6 +29,7 @@ pub mod prelude {
         TickBackend,
         DrawBackend,
         Point,
+        Transform,
         Rect,
         Visibility,
         Direction,
@@ -52,9 +58,20 @@ pub mod prelude {
             ButtonData,
             Button,
         },
+        draw_state::DrawState,
         events::{
             Event,
+            MouseButton,
+            Click,
             MouseEvent,
+            DragKind,
+            DragEvent,
+            InputState,
+            Key,
+            Modifiers,
+            KeyEventKind,
+            KeyEvent,
+            FocusId,
             Events,
         },
         label::{
@@ -67,18 +84,36 @@ pub mod prelude {
             PadBoxNode,
         },
         region::Region,
+        scroll_box::{
+            ScrollBoxStyle,
+            ScrollBoxNode,
+        },
         size_box::{
             SizeBoxLayout,
             SizeBoxNode,
         },
         split_box::{
+            SplitPosition,
+            SplitBoxStyle,
             SplitBoxLayout,
             SplitBoxNode,
         },
         stack_box::{
+            StackSizing,
             StackBoxLayout,
             StackBoxNode,
         },
+        text_input::{
+            TextInputStyle,
+            TextInputData,
+            TextInput,
+        },
+        theme::{
+            Palette,
+            StyleOverride,
+            Theme,
+            Themed,
+        },
         uniform_grid::{
             UniformGridLayout,
             UniformGridNode,
@@ -90,27 +125,115 @@ use prelude::*;
 
 pub trait InputBackend {
     fn mouse_position(&mut self) -> Point;
-    fn is_m1_pressed(&mut self) -> bool;
-    fn is_m1_released(&mut self) -> bool;
+    /// Seconds since some fixed point in time, used to time multi-clicks.
+    fn time(&mut self) -> f64;
+    fn is_mouse_button_down(&mut self, button: MouseButton) -> bool;
+    fn is_mouse_button_pressed(&mut self, button: MouseButton) -> bool;
+    fn is_mouse_button_released(&mut self, button: MouseButton) -> bool;
     fn mouse_wheel_move(&mut self) -> Point;
+    fn is_key_down(&mut self, key: Key) -> bool;
+    fn is_key_pressed(&mut self, key: Key) -> bool;
+    fn is_key_pressed_repeat(&mut self, key: Key) -> bool;
+    fn is_key_released(&mut self, key: Key) -> bool;
+    /// The next character typed this frame, or [`None`] once they have all been read.
+    fn char_pressed(&mut self) -> Option<char>;
 }
 
-pub trait TickBackend {}
+pub trait TickBackend {
+    type Color: Copy;
+
+    /// The theme themed widgets take their styles from.
+    fn theme(&self) -> &Theme<Self::Color>;
+    fn clipboard_text(&mut self) -> Option<String>;
+    fn set_clipboard_text(&mut self, text: &str);
+}
 
 pub trait DrawBackend {
     type Color: Copy;
+    /// Handle to an image the backend can draw, such as a texture. Owned by the application, not the UI.
+    type Image;
 
+    /// The theme themed widgets take their styles from.
+    fn theme(&self) -> &Theme<Self::Color>;
     fn draw_rect(&mut self, rect: &Rect, color: &Self::Color);
+    /// Draw the border of `rect`, inset by `thickness`.
+    fn draw_rect_outline(&mut self, rect: &Rect, thickness: f32, color: &Self::Color);
+    fn draw_rounded_rect(&mut self, rect: &Rect, radius: f32, color: &Self::Color);
+    fn draw_rounded_rect_outline(&mut self, rect: &Rect, radius: f32, thickness: f32, color: &Self::Color);
+    fn draw_line(&mut self, start: Point, end: Point, thickness: f32, color: &Self::Color);
+    /// Draw the `source` part of `image` (all of it if [`None`]) stretched over `dest`, multiplied by `tint`.
+    /// A `source` with `y_max < y_min` is flipped vertically, and likewise for x.
+    fn draw_image(&mut self, image: &Self::Image, source: Option<&Rect>, dest: &Rect, tint: &Self::Color);
     fn draw_text(&mut self, text: &str, top_left: Point, font_size: f32, color: &Self::Color);
+    /// The width `text` would take up if drawn with [`Self::draw_text`].
+    fn measure_text(&mut self, text: &str, font_size: f32) -> f32;
+    /// Confine drawing to `rect` until the matching [`Self::pop_clip`].
+    /// Nested clips are intersected with the ones outside them.
+    fn push_clip(&mut self, rect: &Rect);
+    fn pop_clip(&mut self);
+    /// Draw everything through `transform` until the matching [`Self::pop_transform`],
+    /// on top of any transforms already pushed. Clip rectangles are transformed too.
+    fn push_transform(&mut self, transform: &Transform);
+    fn pop_transform(&mut self);
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
 }
 
+/// Maps points from a node's own space into the space around it, by scaling and then offsetting them.
 #[derive(Clone, Copy)]
+pub struct Transform {
+    pub offset: Point,
+    pub scale: f32,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self { offset: Point { x: 0.0, y: 0.0 }, scale: 1.0 };
+
+    #[inline]
+    pub const fn translate(offset: Point) -> Self {
+        Self { offset, scale: 1.0 }
+    }
+
+    /// From this transform's inner space to its outer space.
+    #[inline]
+    pub const fn apply(&self, point: Point) -> Point {
+        Point {
+            x: self.offset.x + point.x * self.scale,
+            y: self.offset.y + point.y * self.scale,
+        }
+    }
+
+    /// From this transform's outer space to its inner space.
+    #[inline]
+    pub const fn invert(&self, point: Point) -> Point {
+        Point {
+            x: (point.x - self.offset.x) / self.scale,
+            y: (point.y - self.offset.y) / self.scale,
+        }
+    }
+
+    #[inline]
+    pub const fn apply_rect(&self, rect: &Rect) -> Rect {
+        let Point { x: x_min, y: y_min } = self.apply(rect.min_point());
+        let Point { x: x_max, y: y_max } = self.apply(Point { x: rect.x_max, y: rect.y_max });
+        Rect { x_min, y_min, x_max, y_max }
+    }
+
+    /// The transform that applies `inner` and then `self`.
+    #[inline]
+    pub const fn compose(&self, inner: &Transform) -> Transform {
+        Transform {
+            offset: self.apply(inner.offset),
+            scale: self.scale * inner.scale,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Rect {
     pub x_min: f32,
     pub y_min: f32,
@@ -139,6 +262,19 @@ impl Rect {
     pub const fn height(&self) -> f32 {
         self.y_max - self.y_min
     }
+
+    /// The overlap of two rectangles, which has no area if they don't overlap.
+    #[inline]
+    pub const fn intersection(&self, other: &Rect) -> Rect {
+        let x_min = self.x_min.max(other.x_min);
+        let y_min = self.y_min.max(other.y_min);
+        Rect {
+            x_min,
+            y_min,
+            x_max: self.x_max.min(other.x_max).max(x_min),
+            y_max: self.y_max.min(other.y_max).max(y_min),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -182,7 +318,7 @@ pub trait Node {
         let (width, height) = (x_max - x_min, y_max - y_min);
         debug_assert!(min_width <= width && min_height <= height, "slot should not be smaller than minimum size");
         let x_max = x_min + max_width .map_or(width,  |w| width .min(w));
-        let y_max = x_min + max_height.map_or(height, |h| height.min(h));
+        let y_max = y_min + max_height.map_or(height, |h| height.min(h));
         Rect { x_min, y_min, x_max, y_max }
     }
 }
@@ -399,19 +535,28 @@ macro_rules! impl_guinode_union {
 
 impl_guinode_union!{
     /// A union of all AmityGUI nodes.
-    pub enum(Color: Copy, T, ButtonOnPress) AmyGUINode<Color, T, ButtonOnPress> {
+    pub enum(Color: Copy, T, ButtonOnPress, TextInputOnChange, TextInputOnSubmit) AmyGUINode<Color, T, ButtonOnPress, TextInputOnChange, TextInputOnSubmit> {
         AlignBox(AlignBoxNode<T>),
         AreaBox(AreaBoxNode<T>),
         Button(Button<Color, T, ButtonOnPress>),
         Label(Label<Color>),
         PadBox(PadBoxNode<T>),
+        ScrollBox(ScrollBoxNode<Color, T>),
         SizeBox(SizeBoxNode<T>),
-        SplitBox(SplitBoxNode<T>),
+        SplitBox(SplitBoxNode<Color, T>),
         StackBox(StackBoxNode<T>),
+        TextInput(TextInput<Color, TextInputOnChange, TextInputOnSubmit>),
         UniformGrid(UniformGridNode<T>),
         Empty(Empty),
     }
-    impl(Color: Copy, ButtonOnPress, T: Node) Node;
-    impl(Color: Copy, ButtonOnPress: FnMut(&mut ButtonData<Color>), TB, T: TickNode<TB>) Tick<(TB)>;
-    impl(Color: Copy, ButtonOnPress, DB: DrawBackend<Color = Color>, T: DrawNode<DB>) Draw<(DB)>;
+    impl(Color: Copy, ButtonOnPress, TextInputOnChange, TextInputOnSubmit, T: Node) Node;
+    impl(
+        Color: Copy,
+        ButtonOnPress: FnMut(&mut ButtonData<Color>),
+        TextInputOnChange: FnMut(&mut TextInputData<Color>),
+        TextInputOnSubmit: FnMut(&mut TextInputData<Color>),
+        TB: TickBackend<Color = Color>,
+        T: TickNode<TB>,
+    ) Tick<(TB)>;
+    impl(Color: Copy, ButtonOnPress, TextInputOnChange, TextInputOnSubmit, DB: DrawBackend<Color = Color>, T: DrawNode<DB>) Draw<(DB)>;
 }
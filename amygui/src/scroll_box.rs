@@ -0,0 +1,446 @@
+use crate::{events::TransformedEvents, *};
+
+#[derive(Clone, Copy)]
+pub struct ScrollBoxStyle<Color: Copy> {
+    /// Thickness of the scrollbars.
+    pub bar_size: f32,
+    /// Distance scrolled by one notch of the mouse wheel.
+    pub scroll_speed: f32,
+    pub track_color: Color,
+    pub thumb_color: Color,
+    pub thumb_hover_color: Color,
+}
+
+/// Scrollbar positions for one frame.
+struct ScrollBar {
+    track: Rect,
+    thumb: Rect,
+}
+
+/// Where everything in a [`ScrollBoxNode`] goes for a given slot.
+struct ScrollLayout {
+    /// The visible part of the content, on screen.
+    view: Rect,
+    /// Size of the content at its natural size.
+    content_width: f32,
+    content_height: f32,
+    /// Horizontal, then vertical.
+    bars: [Option<ScrollBar>; 2],
+}
+
+impl ScrollLayout {
+    /// How far the content can scroll along each axis.
+    fn max_scroll(&self) -> Point {
+        Point {
+            x: (self.content_width - self.view.width()).max(0.0),
+            y: (self.content_height - self.view.height()).max(0.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ThumbDrag {
+    direction: Direction,
+    /// Where the thumb was grabbed, measured from its start.
+    grab: f32,
+}
+
+/// A UI element that shows its content at its natural size, scrolled to fit inside the slot.
+///
+/// Scrolls with the mouse wheel (Shift turns vertical scrolling horizontal) and with draggable scrollbars,
+/// which only appear when the content doesn't fit.
+/// The wheel is taken before the content ticks, so that hovering a child such as a button doesn't stop the scrolling.
+///
+/// The content's slot starts at (0, 0), and it is drawn and given mouse positions through a transform to match.
+pub struct ScrollBoxNode<Color: Copy, T> {
+    pub style: ScrollBoxStyle<Color>,
+    /// Whether the content can scroll sideways. Otherwise it is fit to the width of the box.
+    pub horizontal: bool,
+    /// Whether the content can scroll up and down. Otherwise it is fit to the height of the box.
+    pub vertical: bool,
+    /// How far the content is scrolled from its top left corner.
+    pub scroll: Point,
+    pub content: T,
+    /// If set, [`Self::style`] follows the theme's style for this class.
+    pub theme_class: Option<&'static str>,
+    hovered_thumb: Option<Direction>,
+    drag: Option<ThumbDrag>,
+}
+
+impl<Color: Copy, T> ScrollBoxNode<Color, T> {
+    pub const fn new(style: ScrollBoxStyle<Color>, horizontal: bool, vertical: bool, content: T) -> Self {
+        Self {
+            style,
+            horizontal,
+            vertical,
+            scroll: Point { x: 0.0, y: 0.0 },
+            content,
+            theme_class: None,
+            hovered_thumb: None,
+            drag: None,
+        }
+    }
+
+    /// Take the style from the theme's `class` from now on.
+    #[inline]
+    pub fn themed(mut self, class: &'static str) -> Self {
+        self.theme_class = Some(class);
+        self
+    }
+
+    /// Vertical scrolling only, like a list.
+    pub const fn vertical(style: ScrollBoxStyle<Color>, content: T) -> Self {
+        Self::new(style, false, true, content)
+    }
+}
+
+/// Position and length of a scrollbar thumb within its track.
+fn thumb_span(track_start: f32, track_length: f32, view_length: f32, content_length: f32, scroll: f32, min_length: f32) -> (f32, f32) {
+    let length = (track_length * view_length / content_length).clamp(min_length.min(track_length), track_length);
+    let max_scroll = content_length - view_length;
+    let t = if max_scroll > 0.0 { scroll / max_scroll } else { 0.0 };
+    (track_start + t * (track_length - length), length)
+}
+
+impl<Color: Copy, T: Node> ScrollBoxNode<Color, T> {
+    /// Size the content would take up to fill `view_length` along one axis.
+    fn natural_length(is_scrollable: bool, (min, max): (f32, Option<f32>), view_length: f32) -> f32 {
+        if is_scrollable {
+            view_length.min(max.unwrap_or(f32::INFINITY)).max(min)
+        } else {
+            view_length
+        }
+    }
+
+    fn layout(&self, slot: Rect) -> ScrollLayout {
+        let (w_range, h_range) = self.content.size_range();
+        let bar_size = self.style.bar_size;
+
+        // each scrollbar takes space from the other axis, which may make that one overflow too
+        let mut has_h_bar = false;
+        let mut has_v_bar = false;
+        for _ in 0..2 {
+            let view_width = slot.width() - if has_v_bar { bar_size } else { 0.0 };
+            let view_height = slot.height() - if has_h_bar { bar_size } else { 0.0 };
+            has_h_bar = self.horizontal && Self::natural_length(true, w_range, view_width) > view_width;
+            has_v_bar = self.vertical && Self::natural_length(true, h_range, view_height) > view_height;
+        }
+
+        let view = Rect {
+            x_max: slot.x_max - if has_v_bar { bar_size } else { 0.0 },
+            y_max: slot.y_max - if has_h_bar { bar_size } else { 0.0 },
+            ..slot
+        };
+        let content_width = Self::natural_length(self.horizontal, w_range, view.width());
+        let content_height = Self::natural_length(self.vertical, h_range, view.height());
+        let max_scroll_x = (content_width - view.width()).max(0.0);
+        let max_scroll_y = (content_height - view.height()).max(0.0);
+        let (scroll_x, scroll_y) = (self.scroll.x.clamp(0.0, max_scroll_x), self.scroll.y.clamp(0.0, max_scroll_y));
+
+        let h_bar = has_h_bar.then(|| {
+            let track = Rect { y_min: view.y_max, y_max: slot.y_max, ..view };
+            let (x_min, length) = thumb_span(track.x_min, track.width(), view.width(), content_width, scroll_x, bar_size * 2.0);
+            ScrollBar { track, thumb: Rect { x_min, x_max: x_min + length, ..track } }
+        });
+        let v_bar = has_v_bar.then(|| {
+            let track = Rect { x_min: view.x_max, x_max: slot.x_max, ..view };
+            let (y_min, length) = thumb_span(track.y_min, track.height(), view.height(), content_height, scroll_y, bar_size * 2.0);
+            ScrollBar { track, thumb: Rect { y_min, y_max: y_min + length, ..track } }
+        });
+
+        ScrollLayout {
+            view,
+            content_width,
+            content_height,
+            bars: [h_bar, v_bar],
+        }
+    }
+
+    fn clamp_scroll(&mut self, layout: &ScrollLayout) {
+        let max_scroll = layout.max_scroll();
+        self.scroll.x = self.scroll.x.clamp(0.0, max_scroll.x);
+        self.scroll.y = self.scroll.y.clamp(0.0, max_scroll.y);
+    }
+
+    /// Scroll so that the thumb starts at `thumb_start` along `direction`.
+    fn drag_thumb(&mut self, layout: &ScrollLayout, direction: Direction, thumb_start: f32) {
+        let (bar, view_length, content_length) = match direction {
+            Direction::Row => (&layout.bars[0], layout.view.width(), layout.content_width),
+            Direction::Column => (&layout.bars[1], layout.view.height(), layout.content_height),
+        };
+        let Some(bar) = bar else { return };
+        let (track_start, track_length, thumb_length) = match direction {
+            Direction::Row => (bar.track.x_min, bar.track.width(), bar.thumb.width()),
+            Direction::Column => (bar.track.y_min, bar.track.height(), bar.thumb.height()),
+        };
+        let free = track_length - thumb_length;
+        let t = if free > 0.0 { ((thumb_start - track_start) / free).clamp(0.0, 1.0) } else { 0.0 };
+        let scroll = t * (content_length - view_length).max(0.0);
+        match direction {
+            Direction::Row => self.scroll.x = scroll,
+            Direction::Column => self.scroll.y = scroll,
+        }
+    }
+
+    /// The content's slot, which starts at the origin of its own space.
+    fn content_slot(layout: &ScrollLayout) -> Rect {
+        Rect { x_min: 0.0, y_min: 0.0, x_max: layout.content_width, y_max: layout.content_height }
+    }
+
+    /// From the content's space to the space around the box.
+    fn content_transform(&self, layout: &ScrollLayout) -> Transform {
+        Transform::translate(Point { x: layout.view.x_min - self.scroll.x, y: layout.view.y_min - self.scroll.y })
+    }
+}
+
+impl<Color: Copy, T: Node> Node for ScrollBoxNode<Color, T> {
+    fn size_range(&self) -> ((f32, Option<f32>), (f32, Option<f32>)) {
+        let ((w_min, w_max), (h_min, h_max)) = self.content.size_range();
+        // a scrollable axis can be as small as it likes, but each scrollbar needs room across it
+        let bar_size = self.style.bar_size;
+        let v_bar = if self.vertical { bar_size } else { 0.0 };
+        let h_bar = if self.horizontal { bar_size } else { 0.0 };
+        let (w_min, w_max) = if self.horizontal { (v_bar, None) } else { (w_min + v_bar, w_max.map(|w| w + v_bar)) };
+        let (h_min, h_max) = if self.vertical { (h_bar, None) } else { (h_min + h_bar, h_max.map(|h| h + h_bar)) };
+        ((w_min, w_max), (h_min, h_max))
+    }
+}
+
+impl<Color: Copy, TB: TickBackend<Color = Color>, T: TickNode<TB>> TickNode<TB> for ScrollBoxNode<Color, T> {
+    fn dibs_tick(&mut self, tb: &mut TB, slot: Rect, events: &mut Events) {
+        tb.theme().restyle(&mut self.style, self.theme_class);
+        let layout = self.layout(slot);
+        self.clamp_scroll(&layout);
+
+        if let Some(drag) = self.drag {
+            // nothing else should react to the mouse while a scrollbar is being dragged
+            events.mouse_event.take_with_dibs();
+            if let Some(event) = events.drag.take_with_dibs_if(|event| event.button == MouseButton::Left) {
+                let position = match drag.direction {
+                    Direction::Row => event.position.x,
+                    Direction::Column => event.position.y,
+                };
+                self.drag_thumb(&layout, drag.direction, position - drag.grab);
+                if event.kind == DragKind::End {
+                    self.drag = None;
+                }
+            } else if !events.is_mouse_down(MouseButton::Left) {
+                self.drag = None;
+            }
+        }
+
+        let layout = self.layout(slot);
+        let scroll_events = TransformedEvents::new(events, self.content_transform(&layout));
+        self.content.dibs_tick(tb, Self::content_slot(&layout), scroll_events.events);
+    }
+
+    fn active_tick(&mut self, tb: &mut TB, slot: Rect, events: &mut Events) {
+        let layout = self.layout(slot);
+
+        // wheel
+        if let Some(mouse) = events.mouse_event.event.as_mut() && layout.view.contains(mouse.position) {
+            let max_scroll = layout.max_scroll();
+            if let Some(wheel) = mouse.scroll.take_if(|wheel| {
+                let (x, y) = if events.modifiers.shift { (wheel.y, wheel.x) } else { (wheel.x, wheel.y) };
+                (x != 0.0 && max_scroll.x > 0.0) || (y != 0.0 && max_scroll.y > 0.0)
+            }) {
+                let (x, y) = if events.modifiers.shift { (wheel.y, wheel.x) } else { (wheel.x, wheel.y) };
+                self.scroll.x -= x * self.style.scroll_speed;
+                self.scroll.y -= y * self.style.scroll_speed;
+                self.clamp_scroll(&layout);
+            }
+        }
+
+        // content
+        {
+            let layout = self.layout(slot);
+            let is_over_view = events.mouse_event.is_some_and_overlapping(layout.view);
+            let content_slot = Self::content_slot(&layout);
+            let scroll_events = TransformedEvents::new(events, self.content_transform(&layout));
+            if is_over_view {
+                self.content.active_tick(tb, content_slot, scroll_events.events);
+            } else {
+                self.content.inactive_tick(tb, content_slot, scroll_events.events);
+            }
+        }
+
+        // scrollbars
+        self.hovered_thumb = None;
+        let layout = self.layout(slot);
+        for (bar, direction) in layout.bars.iter().zip([Direction::Row, Direction::Column]) {
+            let Some(bar) = bar else { continue };
+            let Some(mut hover) = events.mouse_event.take_if_overlapping(bar.track) else { continue };
+            let (position, thumb_start, thumb_end) = match direction {
+                Direction::Row => (hover.position.x, bar.thumb.x_min, bar.thumb.x_max),
+                Direction::Column => (hover.position.y, bar.thumb.y_min, bar.thumb.y_max),
+            };
+            let is_over_thumb = bar.thumb.contains(hover.position);
+            if is_over_thumb {
+                self.hovered_thumb = Some(direction);
+            }
+            if hover.take_press(MouseButton::Left).is_some() {
+                if is_over_thumb {
+                    self.drag = Some(ThumbDrag { direction, grab: position - thumb_start });
+                } else {
+                    // jump a page towards the click
+                    let page = match direction {
+                        Direction::Row => layout.view.width(),
+                        Direction::Column => layout.view.height(),
+                    };
+                    let step = if position < thumb_start { -page } else if position >= thumb_end { page } else { 0.0 };
+                    match direction {
+                        Direction::Row => self.scroll.x += step,
+                        Direction::Column => self.scroll.y += step,
+                    }
+                    self.clamp_scroll(&layout);
+                }
+            }
+            // the mouse can only be over one scrollbar
+            break;
+        }
+    }
+
+    fn inactive_tick(&mut self, tb: &mut TB, slot: Rect, events: &Events) {
+        self.hovered_thumb = None;
+        let layout = self.layout(slot);
+        let mut events = events.clone();
+        let scroll_events = TransformedEvents::new(&mut events, self.content_transform(&layout));
+        self.content.inactive_tick(tb, Self::content_slot(&layout), scroll_events.events);
+    }
+}
+
+impl<Color: Copy, DB: DrawBackend<Color = Color>, T: DrawNode<DB>> DrawNode<DB> for ScrollBoxNode<Color, T> {
+    fn draw(&self, d: &mut DB, slot: Rect) {
+        let layout = self.layout(slot);
+
+        d.push_clip(&layout.view);
+        d.push_transform(&self.content_transform(&layout));
+        self.content.draw(d, Self::content_slot(&layout));
+        d.pop_transform();
+        d.pop_clip();
+
+        for (bar, direction) in layout.bars.iter().zip([Direction::Row, Direction::Column]) {
+            let Some(bar) = bar else { continue };
+            let is_active = self.hovered_thumb == Some(direction) || self.drag.is_some_and(|drag| drag.direction == direction);
+            d.draw_rect(&bar.track, &self.style.track_color);
+            d.draw_rect(&bar.thumb, if is_active { &self.style.thumb_hover_color } else { &self.style.thumb_color });
+        }
+    }
+}
+
+#[cfg(test)]
+mod scroll_box_tests {
+    use crate::headless::*;
+    use super::*;
+
+    const STYLE: ScrollBoxStyle<&str> = ScrollBoxStyle {
+        bar_size: 10.0,
+        scroll_speed: 20.0,
+        track_color: "track",
+        thumb_color: "thumb",
+        thumb_hover_color: "thumb hover",
+    };
+    /// Leaves a 90x100 view beside a 100 long scrollbar, so a 400 tall list scrolls by up to 300 and has a 25 long thumb.
+    const SLOT: Rect = rect(10.0, 10.0, 110.0, 110.0);
+    const VIEW: Rect = rect(10.0, 10.0, 100.0, 110.0);
+    const IN_VIEW: Point = Point { x: 50.0, y: 50.0 };
+
+    fn list() -> ScrollBoxNode<&'static str, Probe> {
+        ScrollBoxNode::vertical(STYLE, Probe::new(90.0, 400.0))
+    }
+
+    /// The thumb as drawn, after the content and the track.
+    fn thumb(hb: &HeadlessBackend<&str>) -> Rect {
+        *hb.rects().nth(2).expect("the scrollbar should be drawn").0
+    }
+
+    const fn wheel(x: f32, y: f32) -> InputAction {
+        InputAction::Scroll(Point { x, y })
+    }
+
+    #[test]
+    fn test_wheel_clamps() {
+        let mut list = list();
+        let mut hb = HeadlessBackend::default();
+
+        hb.run(&mut list, SLOT, [[InputAction::MoveMouse(IN_VIEW), wheel(0.0, -1.0)]]);
+        assert_eq!(list.scroll.y, 20.0);
+
+        hb.run(&mut list, SLOT, [[wheel(0.0, -100.0)]]);
+        assert_eq!(list.scroll.y, 300.0);
+        assert_eq!(thumb(&hb), rect(100.0, 85.0, 110.0, 110.0));
+
+        hb.run(&mut list, SLOT, [[wheel(0.0, 100.0)]]);
+        assert_eq!(list.scroll.y, 0.0);
+        assert_eq!(thumb(&hb), rect(100.0, 10.0, 110.0, 35.0));
+
+        // the list can't scroll sideways, so the wheel is left for something else
+        let events = hb.run(&mut list, SLOT, [[wheel(-1.0, 0.0)]]).unwrap();
+        assert_eq!(list.scroll, Point { x: 0.0, y: 0.0 });
+        assert_eq!(events.mouse_event.event.unwrap().scroll.event, Some(Point { x: -1.0, y: 0.0 }));
+    }
+
+    #[test]
+    fn test_thumb_drag() {
+        let mut list = list();
+        let mut hb = HeadlessBackend::default();
+
+        // grabbed 10 below its start, so the thumb starts halfway down the free part of the track
+        hb.drag(&mut list, SLOT, Point { x: 105.0, y: 20.0 }, Point { x: 105.0, y: 57.5 }, MouseButton::Left);
+        assert_eq!(list.scroll.y, 150.0);
+        assert_eq!(thumb(&hb), rect(100.0, 47.5, 110.0, 72.5));
+        assert!(list.drag.is_none());
+
+        hb.drag(&mut list, SLOT, Point { x: 105.0, y: 60.0 }, Point { x: 105.0, y: 200.0 }, MouseButton::Left);
+        assert_eq!(list.scroll.y, 300.0);
+    }
+
+    #[test]
+    fn test_page_jump() {
+        let mut list = list();
+        let mut hb = HeadlessBackend::default();
+        let below = Point { x: 105.0, y: 100.0 };
+
+        hb.click(&mut list, SLOT, below, MouseButton::Left);
+        assert_eq!(list.scroll.y, 100.0);
+        hb.click(&mut list, SLOT, below, MouseButton::Left);
+        assert_eq!(list.scroll.y, 200.0);
+        hb.click(&mut list, SLOT, below, MouseButton::Left);
+        assert_eq!(list.scroll.y, 300.0);
+
+        // the thumb has reached the click, which grabs it instead
+        hb.click(&mut list, SLOT, below, MouseButton::Left);
+        assert_eq!(list.scroll.y, 300.0);
+
+        hb.click(&mut list, SLOT, Point { x: 105.0, y: 20.0 }, MouseButton::Left);
+        assert_eq!(list.scroll.y, 200.0);
+        assert_eq!(list.content.pressed_at, None);
+    }
+
+    #[test]
+    fn test_click_reaches_scrolled_content() {
+        let mut list = list();
+        list.scroll.y = 150.0;
+        let mut hb = HeadlessBackend::default();
+
+        let events = hb.run(&mut list, SLOT, [[InputAction::MoveMouse(Point { x: 30.0, y: 40.0 }), InputAction::Press(MouseButton::Left)]]).unwrap();
+        assert_eq!(list.content.pressed_at, Some(Point { x: 20.0, y: 180.0 }));
+        // the position goes back to screen space for whatever ticks after the box
+        assert_eq!(events.mouse_event.event.unwrap().position, Point { x: 30.0, y: 40.0 });
+    }
+
+    #[test]
+    fn test_content_is_clipped_to_view() {
+        let mut list = list();
+        list.scroll.y = 150.0;
+        let mut hb = HeadlessBackend::default();
+
+        hb.frame(&mut list, SLOT);
+        assert_eq!(hb.display_list[0], DrawCall {
+            command: DrawCommand::Rect { rect: rect(10.0, -140.0, 100.0, 260.0), color: "probe" },
+            clip: Some(VIEW),
+        });
+        // the scrollbar is outside the view, and isn't clipped to it
+        assert_eq!(hb.display_list[1..].iter().map(|call| call.clip).collect::<Vec<_>>(), [None, None]);
+    }
+}
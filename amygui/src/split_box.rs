@@ -1,97 +1,174 @@
 use crate::*;
 
+/// Where the divider of a [`SplitBoxNode`] sits.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SplitPosition {
+    /// Distance from the start of the slot to the divider, so the first child keeps its size when the slot resizes.
+    Offset(f32),
+    /// Fraction of the space available to the children that goes to the first child,
+    /// so both children keep their proportions when the slot resizes.
+    Fraction(f32),
+}
+
+#[derive(Clone, Copy)]
+pub struct SplitBoxStyle<Color: Copy> {
+    /// Thickness of the divider between the children.
+    pub handle_size: f32,
+    pub handle_color: Color,
+    pub handle_hover_color: Color,
+}
+
 #[derive(Clone, Copy)]
 pub struct SplitBoxLayout {
     pub direction: Direction,
-    pub split_point: f32,
+    pub split: SplitPosition,
 }
 
-/// A UI element that displays two children sharing a variable amount of space
-pub struct SplitBoxNode<T> {
+/// A UI element that displays two children sharing a variable amount of space,
+/// with a divider between them that can be dragged to resize them.
+pub struct SplitBoxNode<Color: Copy, T> {
     pub layout: SplitBoxLayout,
+    pub style: SplitBoxStyle<Color>,
     pub content: [T; 2],
+    /// If set, [`Self::style`] follows the theme's style for this class.
+    pub theme_class: Option<&'static str>,
+    is_hovered: bool,
+    is_dragging: bool,
 }
 
-impl<T> SplitBoxNode<T> {
-    pub const fn new(direction: Direction, split_point: f32, item1: T, item2: T) -> Self {
+impl<Color: Copy, T> SplitBoxNode<Color, T> {
+    pub const fn new(direction: Direction, split: SplitPosition, style: SplitBoxStyle<Color>, item1: T, item2: T) -> Self {
         Self {
-            layout: SplitBoxLayout { direction, split_point },
+            layout: SplitBoxLayout { direction, split },
+            style,
             content: [item1, item2],
+            theme_class: None,
+            is_hovered: false,
+            is_dragging: false,
+        }
+    }
+
+    /// Take the style from the theme's `class` from now on.
+    #[inline]
+    pub fn themed(mut self, class: &'static str) -> Self {
+        self.theme_class = Some(class);
+        self
+    }
+
+    #[inline]
+    pub const fn is_dragging(&self) -> bool {
+        self.is_dragging
+    }
+}
+
+impl<Color: Copy, T: Node> SplitBoxNode<Color, T> {
+    /// Minimum length of each child along the split direction.
+    fn min_lengths(&self) -> [f32; 2] {
+        self.content.each_ref().map(|item| {
+            let ((w_min, _), (h_min, _)) = item.size_range();
+            match self.layout.direction {
+                Direction::Row => w_min,
+                Direction::Column => h_min,
+            }
+        })
+    }
+
+    /// Start of the slot along the split direction, and the length shared by the children.
+    fn span(&self, slot: Rect) -> (f32, f32) {
+        let (start, length) = match self.layout.direction {
+            Direction::Row => (slot.x_min, slot.width()),
+            Direction::Column => (slot.y_min, slot.height()),
+        };
+        (start, (length - self.style.handle_size).max(0.0))
+    }
+
+    /// Length of the first child, clamped so that neither child is smaller than its minimum.
+    fn first_length(&self, slot: Rect) -> f32 {
+        let (_, available) = self.span(slot);
+        let [min0, min1] = self.min_lengths();
+        let length = match self.layout.split {
+            SplitPosition::Offset(offset) => offset,
+            SplitPosition::Fraction(fraction) => fraction * available,
+        };
+        length.min(available - min1).max(min0)
+    }
+
+    /// Set the length of the first child, keeping the kind of [`SplitPosition`].
+    fn set_first_length(&mut self, slot: Rect, length: f32) {
+        let (_, available) = self.span(slot);
+        self.layout.split = match self.layout.split {
+            SplitPosition::Offset(_) => SplitPosition::Offset(length),
+            SplitPosition::Fraction(_) => SplitPosition::Fraction(if available > 0.0 { length / available } else { 0.0 }),
+        };
+    }
+
+    /// Move the divider so that its center is at `position` along the split direction.
+    pub fn drag_to(&mut self, slot: Rect, position: f32) {
+        let (start, _) = self.span(slot);
+        self.set_first_length(slot, position - start - self.style.handle_size * 0.5);
+        // store the clamped value, so that dragging past a limit doesn't leave the divider stuck there
+        self.set_first_length(slot, self.first_length(slot));
+    }
+
+    /// The rectangles of the first child, the handle, and the second child.
+    fn rects(&self, slot: Rect) -> [Rect; 3] {
+        let (start, _) = self.span(slot);
+        let split = start + self.first_length(slot);
+        let handle_end = split + self.style.handle_size;
+        match self.layout.direction {
+            Direction::Row => [
+                Rect { x_max: split, ..slot },
+                Rect { x_min: split, x_max: handle_end, ..slot },
+                Rect { x_min: handle_end, ..slot },
+            ],
+            Direction::Column => [
+                Rect { y_max: split, ..slot },
+                Rect { y_min: split, y_max: handle_end, ..slot },
+                Rect { y_min: handle_end, ..slot },
+            ],
         }
     }
+
+    #[inline]
+    pub fn handle_rect(&self, slot: Rect) -> Rect {
+        self.rects(slot)[1]
+    }
 }
 
-impl<T: Node> Node for SplitBoxNode<T> {
+impl<Color: Copy, T: Node> Node for SplitBoxNode<Color, T> {
     fn size_range(&self) -> ((f32, Option<f32>), (f32, Option<f32>)) {
         let ((w_min0, w_max0), (h_min0, h_max0)) = self.content[0].size_range();
         let ((w_min1, w_max1), (h_min1, h_max1)) = self.content[1].size_range();
         let (w_max, h_max) = (w_max0.zip(w_max1), h_max0.zip(h_max1));
+        let handle = self.style.handle_size;
         match self.layout.direction {
             Direction::Row => (
-                (w_min0 + w_min1, w_max.map(|(a, b)| a + b)),
+                (w_min0 + w_min1 + handle, w_max.map(|(a, b)| a + b + handle)),
                 (h_min0.max(h_min1), h_max.map(|(a, b)| a.max(b))),
             ),
             Direction::Column => (
                 (w_min0.max(w_min1), w_max.map(|(a, b)| a.max(b))),
-                (h_min0 + h_min1, h_max.map(|(a, b)| a + b)),
+                (h_min0 + h_min1 + handle, h_max.map(|(a, b)| a + b + handle)),
             ),
         }
     }
 }
 
-pub struct Iter<'a, I> {
+pub struct Iter<I> {
     iter: I,
-    layout: &'a SplitBoxLayout,
     slot: std::array::IntoIter<Rect, 2>,
 }
 
-impl<'a, I> Iter<'a, I> {
-    fn new(iter: I, layout: &'a SplitBoxLayout, slot: Rect) -> Self {
-        let rects = match layout.direction {
-            Direction::Row => {
-                let (y_min, y_max) = (slot.y_min, slot.y_max);
-                [
-                    Rect {
-                        x_min: slot.x_min,
-                        y_min,
-                        x_max: layout.split_point,
-                        y_max,
-                    },
-                    Rect {
-                        x_min: layout.split_point,
-                        y_min,
-                        x_max: slot.x_max,
-                        y_max,
-                    }
-                ]
-            }
-            Direction::Column => {
-                let (x_min, x_max) = (slot.x_min, slot.x_max);
-                [
-                    Rect {
-                        x_min,
-                        y_min: slot.y_min,
-                        x_max,
-                        y_max: layout.split_point,
-                    },
-                    Rect {
-                        x_min,
-                        y_min: layout.split_point,
-                        x_max,
-                        y_max: slot.y_max,
-                    }
-                ]
-            }
-        };
+impl<I> Iter<I> {
+    fn new(iter: I, [first, _, second]: [Rect; 3]) -> Self {
         Self {
             iter,
-            layout,
-            slot: rects.into_iter(),
+            slot: [first, second].into_iter(),
         }
     }
 }
 
-impl<'a, I: Iterator> Iterator for Iter<'a, I> {
+impl<I: Iterator> Iterator for Iter<I> {
     type Item = (I::Item, Rect);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -99,26 +176,173 @@ impl<'a, I: Iterator> Iterator for Iter<'a, I> {
     }
 }
 
-impl<'a, I: DoubleEndedIterator> DoubleEndedIterator for Iter<'a, I> {
+impl<I: DoubleEndedIterator> DoubleEndedIterator for Iter<I> {
     fn next_back(&mut self) -> Option<Self::Item> {
         self.iter.next_back().zip(self.slot.next_back())
     }
 }
 
-impl<T: Node> CollectionNode for SplitBoxNode<T> {
+impl<Color: Copy, T: Node> CollectionNode for SplitBoxNode<Color, T> {
     type Item = T;
-    type Iter<'a> = Iter<'a, std::slice::Iter<'a, T>> where Self: 'a;
-    type IterMut<'a> = Iter<'a, std::slice::IterMut<'a, T>> where Self: 'a;
+    type Iter<'a> = Iter<std::slice::Iter<'a, T>> where Self: 'a;
+    type IterMut<'a> = Iter<std::slice::IterMut<'a, T>> where Self: 'a;
 
     #[inline]
     fn children(&self, slot: Rect) -> Self::Iter<'_> {
-        Iter::new(self.content.iter(), &self.layout, slot)
+        let rects = self.rects(slot);
+        Iter::new(self.content.iter(), rects)
     }
 
     #[inline]
     fn children_mut(&mut self, slot: Rect) -> Self::IterMut<'_> {
-        Iter::new(self.content.iter_mut(), &self.layout, slot)
+        let rects = self.rects(slot);
+        Iter::new(self.content.iter_mut(), rects)
     }
 }
 
-impl<T: Node> SimpleCollectionNode for SplitBoxNode<T> {}
+impl<Color: Copy, TB: TickBackend<Color = Color>, T: TickNode<TB>> TickNode<TB> for SplitBoxNode<Color, T> {
+    fn dibs_tick(&mut self, tb: &mut TB, slot: Rect, events: &mut Events) {
+        tb.theme().restyle(&mut self.style, self.theme_class);
+        if self.is_dragging {
+            // nothing else should react to the mouse while the divider is being dragged
+            events.mouse_event.take_with_dibs();
+            if let Some(drag) = events.drag.take_with_dibs_if(|drag| drag.button == MouseButton::Left) {
+                let position = match self.layout.direction {
+                    Direction::Row => drag.position.x,
+                    Direction::Column => drag.position.y,
+                };
+                self.drag_to(slot, position);
+                if drag.kind == DragKind::End {
+                    self.is_dragging = false;
+                }
+            } else if events.is_mouse_released(MouseButton::Left) {
+                self.is_dragging = false;
+            }
+        }
+
+        for (item, slot) in self.children_mut(slot).rev() {
+            item.dibs_tick(tb, slot, events);
+        }
+    }
+
+    fn active_tick(&mut self, tb: &mut TB, slot: Rect, events: &mut Events) {
+        for (item, slot) in self.children_mut(slot).rev() {
+            if events.mouse_event.is_some_and_overlapping(slot) {
+                item.active_tick(tb, slot, events);
+            } else {
+                item.inactive_tick(tb, slot, events);
+            }
+        }
+
+        let handle = self.handle_rect(slot);
+        self.is_hovered = false;
+        if let Some(mut hover) = events.mouse_event.take_if_overlapping(handle) {
+            self.is_hovered = true;
+            if hover.take_press(MouseButton::Left).is_some() {
+                self.is_dragging = true;
+            }
+        }
+    }
+
+    fn inactive_tick(&mut self, tb: &mut TB, slot: Rect, events: &Events) {
+        self.is_hovered = false;
+        for (item, slot) in self.children_mut(slot).rev() {
+            item.inactive_tick(tb, slot, events);
+        }
+    }
+}
+
+impl<Color: Copy, DB: DrawBackend<Color = Color>, T: DrawNode<DB>> DrawNode<DB> for SplitBoxNode<Color, T> {
+    fn draw(&self, d: &mut DB, slot: Rect) {
+        let color = if self.is_hovered || self.is_dragging { &self.style.handle_hover_color } else { &self.style.handle_color };
+        d.draw_rect(&self.handle_rect(slot), color);
+        for (item, slot) in self.children(slot) {
+            item.draw(d, slot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod split_box_tests {
+    use crate::headless::*;
+    use super::*;
+
+    type Child = Button<&'static str, SizeBoxNode<Empty>, fn(&mut ButtonData<&'static str>)>;
+
+    const STYLE: SplitBoxStyle<&str> = SplitBoxStyle {
+        handle_size: 4.0,
+        handle_color: "handle",
+        handle_hover_color: "handle hover",
+    };
+
+    /// A child that is at least `min_width` wide.
+    fn child(min_width: f32) -> Child {
+        Button::new(ButtonData::new(BUTTON_STYLE), (|_| ()) as fn(&mut _), SizeBoxNode::new(min_width, 0.0, Empty))
+    }
+
+    fn split(split: SplitPosition, min_widths: [f32; 2]) -> SplitBoxNode<&'static str, Child> {
+        SplitBoxNode::new(Direction::Row, split, STYLE, child(min_widths[0]), child(min_widths[1]))
+    }
+
+    /// Handle, first child, second child.
+    fn drawn_rects(hb: &HeadlessBackend<&str>) -> Vec<Rect> {
+        hb.rects().map(|(rect, _)| *rect).collect()
+    }
+
+    #[test]
+    fn test_fraction_keeps_proportions() {
+        let mut split = split(SplitPosition::Fraction(0.5), [0.0, 0.0]);
+        let mut hb = HeadlessBackend::default();
+
+        hb.frame(&mut split, rect(0.0, 0.0, 104.0, 50.0));
+        assert_eq!(drawn_rects(&hb), [rect(50.0, 0.0, 54.0, 50.0), rect(0.0, 0.0, 50.0, 50.0), rect(54.0, 0.0, 104.0, 50.0)]);
+
+        hb.frame(&mut split, rect(0.0, 0.0, 204.0, 50.0));
+        assert_eq!(drawn_rects(&hb), [rect(100.0, 0.0, 104.0, 50.0), rect(0.0, 0.0, 100.0, 50.0), rect(104.0, 0.0, 204.0, 50.0)]);
+    }
+
+    #[test]
+    fn test_offset_keeps_first_size() {
+        let mut split = split(SplitPosition::Offset(30.0), [0.0, 0.0]);
+        let mut hb = HeadlessBackend::default();
+
+        hb.frame(&mut split, rect(10.0, 0.0, 114.0, 50.0));
+        assert_eq!(drawn_rects(&hb)[0], rect(40.0, 0.0, 44.0, 50.0));
+
+        hb.frame(&mut split, rect(10.0, 0.0, 214.0, 50.0));
+        assert_eq!(drawn_rects(&hb)[0], rect(40.0, 0.0, 44.0, 50.0));
+    }
+
+    #[test]
+    fn test_drag_handle() {
+        let mut split = split(SplitPosition::Fraction(0.5), [0.0, 0.0]);
+        let mut hb = HeadlessBackend::default();
+        let slot = rect(0.0, 0.0, 104.0, 50.0);
+
+        hb.run(&mut split, slot, [[InputAction::MoveMouse(Point { x: 52.0, y: 25.0 })]]);
+        assert_eq!(hb.rects().next(), Some((&rect(50.0, 0.0, 54.0, 50.0), &"handle hover")));
+
+        hb.drag(&mut split, slot, Point { x: 52.0, y: 25.0 }, Point { x: 82.0, y: 25.0 }, MouseButton::Left);
+        assert!(!split.is_dragging());
+        assert_eq!(split.layout.split, SplitPosition::Fraction(0.8));
+        assert_eq!(drawn_rects(&hb), [rect(80.0, 0.0, 84.0, 50.0), rect(0.0, 0.0, 80.0, 50.0), rect(84.0, 0.0, 104.0, 50.0)]);
+
+        // pressing a child doesn't move the handle
+        hb.drag(&mut split, slot, Point { x: 20.0, y: 25.0 }, Point { x: 40.0, y: 25.0 }, MouseButton::Left);
+        assert_eq!(split.layout.split, SplitPosition::Fraction(0.8));
+    }
+
+    #[test]
+    fn test_drag_clamps_to_min_sizes() {
+        let mut split = split(SplitPosition::Offset(50.0), [30.0, 20.0]);
+        let mut hb = HeadlessBackend::default();
+        let slot = rect(0.0, 0.0, 104.0, 50.0);
+
+        hb.drag(&mut split, slot, Point { x: 52.0, y: 25.0 }, Point { x: 0.0, y: 25.0 }, MouseButton::Left);
+        assert_eq!(split.layout.split, SplitPosition::Offset(30.0));
+
+        hb.drag(&mut split, slot, Point { x: 32.0, y: 25.0 }, Point { x: 200.0, y: 25.0 }, MouseButton::Left);
+        assert_eq!(split.layout.split, SplitPosition::Offset(80.0));
+        assert_eq!(drawn_rects(&hb)[0], rect(80.0, 0.0, 84.0, 50.0));
+    }
+}
@@ -1,35 +1,47 @@
 use crate::*;
 
-pub struct Iter<'a, I> {
+pub struct Iter<I> {
     iter: I,
-    layout: &'a StackBoxLayout,
-    slot: Rect,
+    rects: std::vec::IntoIter<Rect>,
 }
 
-impl<'a, I> Iter<'a, I> {
-    pub const fn new(iter: I, layout: &'a StackBoxLayout, slot: Rect) -> Self {
+impl<I> Iter<I> {
+    pub const fn new(iter: I, rects: std::vec::IntoIter<Rect>) -> Self {
         Self {
             iter,
-            layout,
-            slot,
+            rects,
         }
     }
 }
 
-impl<'a, I: Iterator> Iterator for Iter<'a, I> {
+impl<I: Iterator> Iterator for Iter<I> {
     type Item = (I::Item, Rect);
 
     fn next(&mut self) -> Option<Self::Item> {
-        _ = self.iter;
-        _ = self.layout;
-        _ = self.slot;
-        todo!()
+        self.iter.next().zip(self.rects.next())
     }
 }
 
-impl<'a, I: DoubleEndedIterator> DoubleEndedIterator for Iter<'a, I> {
+impl<I: DoubleEndedIterator> DoubleEndedIterator for Iter<I> {
     fn next_back(&mut self) -> Option<Self::Item> {
-        todo!()
+        self.iter.next_back().zip(self.rects.next_back())
+    }
+}
+
+/// How much space a child of a [`StackBoxNode`] takes along the stack direction.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StackSizing {
+    /// Exactly this size, or the child's minimum if that is larger.
+    Fixed(f32),
+    /// A share of the space left over by fixed children, proportional to the weight.
+    /// A weight of 0 keeps the child at its minimum size.
+    Flex(f32),
+}
+
+impl Default for StackSizing {
+    #[inline]
+    fn default() -> Self {
+        Self::Flex(1.0)
     }
 }
 
@@ -39,9 +51,12 @@ pub struct StackBoxLayout {
     pub gap: f32,
 }
 
+/// A UI element that displays its children one after another in a row or column.
 pub struct StackBoxNode<T> {
     pub layout: StackBoxLayout,
     pub content: Vec<T>,
+    /// Sizing of each child, by index. Children past the end use [`StackSizing::default`].
+    pub sizing: Vec<StackSizing>,
 }
 
 impl<T> StackBoxNode<T> {
@@ -53,89 +68,234 @@ impl<T> StackBoxNode<T> {
         Self {
             layout: StackBoxLayout { direction, gap },
             content,
+            sizing: Vec::new(),
         }
     }
 
     pub fn from_iter(direction: Direction, gap: f32, content: impl IntoIterator<Item = T>) -> Self {
         Self::with_content(direction, gap, Vec::from_iter(content))
     }
+
+    pub fn with_sizing(mut self, sizing: impl IntoIterator<Item = StackSizing>) -> Self {
+        self.sizing = Vec::from_iter(sizing);
+        self
+    }
+
+    #[inline]
+    pub fn sizing(&self, index: usize) -> StackSizing {
+        self.sizing.get(index).copied().unwrap_or_default()
+    }
 }
 
-impl<T: Node> Node for StackBoxNode<T> {
-    fn size_range(&self) -> ((f32, Option<f32>), (f32, Option<f32>)) {
-        let total_gap = (self.content.len() - 1) as f32 * self.layout.gap;
-        let (width, height) = match self.layout.direction {
-            Direction::Row => (total_gap, 0.0),
-            Direction::Column => (0.0, total_gap),
+impl<T: Node> StackBoxNode<T> {
+    /// Size range of a child as (along the stack, across the stack), with its sizing applied.
+    fn item_range(&self, index: usize) -> ((f32, Option<f32>), (f32, Option<f32>)) {
+        let (w_range, h_range) = self.content[index].size_range();
+        let (main, cross) = match self.layout.direction {
+            Direction::Row => (w_range, h_range),
+            Direction::Column => (h_range, w_range),
         };
-        let (mut min_width, mut max_width) = (width, Some(width));
-        let (mut min_height, mut max_height) = (height, Some(height));
-        match self.layout.direction {
-            Direction::Column => {
-                for item in &self.content {
-                    let ((w_min, w_max), (h_min, h_max)) = item.size_range();
-
-                    if let Some(max) = w_max {
-                        if let Some(max_width) = &mut max_width {
-                            *max_width = max_width.max(max);
-                        }
-                    } else {
-                        max_width = None;
-                    }
-                    min_width = min_width.max(w_min);
-
-                    if let Some(max) = h_max {
-                        if let Some(max_height) = &mut max_height {
-                            *max_height += max;
-                        }
-                    } else {
-                        max_height = None;
-                    }
-                    min_height += h_min;
-                }
+        let main = match self.sizing(index) {
+            StackSizing::Fixed(size) => {
+                let size = size.max(main.0);
+                (size, Some(size))
             }
-            Direction::Row => {
-                for item in &self.content {
-                    let ((w_min, w_max), (h_min, h_max)) = item.size_range();
-
-                    if let Some(max) = h_max {
-                        if let Some(max_height) = &mut max_height {
-                            *max_height = max_height.max(max);
-                        }
-                    } else {
-                        max_height = None;
-                    }
-                    min_height = min_height.max(h_min);
-
-                    if let Some(max) = w_max {
-                        if let Some(max_width) = &mut max_width {
-                            *max_width += max;
-                        }
-                    } else {
-                        max_width = None;
-                    }
-                    min_width += w_min;
+            StackSizing::Flex(_) => main,
+        };
+        (main, cross)
+    }
+
+    /// Length of each child along the stack when the children share `length`, not counting gaps.
+    ///
+    /// Flexible children are given their share of the space, then any that would go outside their
+    /// size range are clamped and the rest share what remains, repeating until everything fits.
+    fn item_lengths(&self, length: f32) -> Vec<f32> {
+        let ranges = (0..self.content.len()).map(|i| self.item_range(i).0).collect::<Vec<_>>();
+        let mut lengths = ranges.iter().map(|&(min, _)| min).collect::<Vec<_>>();
+        let mut is_frozen = (0..self.content.len())
+            .map(|i| !matches!(self.sizing(i), StackSizing::Flex(weight) if weight > 0.0))
+            .collect::<Vec<_>>();
+
+        loop {
+            let frozen_length = (0..lengths.len()).filter(|&i| is_frozen[i]).map(|i| lengths[i]).sum::<f32>();
+            let free = (length - frozen_length).max(0.0);
+            let total_weight = (0..lengths.len())
+                .filter(|&i| !is_frozen[i])
+                .map(|i| match self.sizing(i) { StackSizing::Flex(weight) => weight, StackSizing::Fixed(_) => 0.0 })
+                .sum::<f32>();
+            if total_weight <= 0.0 {
+                break;
+            }
+
+            let mut is_clamped = false;
+            for i in 0..lengths.len() {
+                if is_frozen[i] { continue; }
+                let StackSizing::Flex(weight) = self.sizing(i) else { unreachable!("fixed children start frozen") };
+                let (min, max) = ranges[i];
+                let share = free * weight / total_weight;
+                lengths[i] = share.max(min).min(max.unwrap_or(f32::INFINITY).max(min));
+                if lengths[i] != share {
+                    is_frozen[i] = true;
+                    is_clamped = true;
                 }
             }
+            if !is_clamped {
+                break;
+            }
+        }
+        lengths
+    }
+
+    fn rects(&self, slot: Rect) -> Vec<Rect> {
+        let total_gap = self.content.len().saturating_sub(1) as f32 * self.layout.gap;
+        let (length, mut pos) = match self.layout.direction {
+            Direction::Row => (slot.width(), slot.x_min),
+            Direction::Column => (slot.height(), slot.y_min),
+        };
+        self.item_lengths((length - total_gap).max(0.0))
+            .into_iter()
+            .map(|item_length| {
+                let rect = match self.layout.direction {
+                    Direction::Row => Rect { x_min: pos, x_max: pos + item_length, ..slot },
+                    Direction::Column => Rect { y_min: pos, y_max: pos + item_length, ..slot },
+                };
+                pos += item_length + self.layout.gap;
+                rect
+            })
+            .collect()
+    }
+}
+
+impl<T: Node> Node for StackBoxNode<T> {
+    fn size_range(&self) -> ((f32, Option<f32>), (f32, Option<f32>)) {
+        let total_gap = self.content.len().saturating_sub(1) as f32 * self.layout.gap;
+        let (mut main_min, mut main_max) = (total_gap, Some(total_gap));
+        let (mut cross_min, mut cross_max) = (0.0f32, Some(0.0f32));
+        for i in 0..self.content.len() {
+            let ((min, max), (c_min, c_max)) = self.item_range(i);
+            main_min += min;
+            main_max = main_max.zip(max).map(|(a, b)| a + b);
+            cross_min = cross_min.max(c_min);
+            cross_max = cross_max.zip(c_max).map(|(a, b)| a.max(b));
+        }
+        match self.layout.direction {
+            Direction::Row => ((main_min, main_max), (cross_min, cross_max)),
+            Direction::Column => ((cross_min, cross_max), (main_min, main_max)),
         }
-        ((min_width, max_width), (min_height, max_height))
     }
 }
 
 impl<T: Node> CollectionNode for StackBoxNode<T> {
     type Item = T;
-    type Iter<'a> = Iter<'a, std::slice::Iter<'a, T>> where Self: 'a;
-    type IterMut<'a> = Iter<'a, std::slice::IterMut<'a, T>> where Self: 'a;
+    type Iter<'a> = Iter<std::slice::Iter<'a, T>> where Self: 'a;
+    type IterMut<'a> = Iter<std::slice::IterMut<'a, T>> where Self: 'a;
 
     #[inline]
     fn children(&self, slot: Rect) -> Self::Iter<'_> {
-        Iter::new(self.content.iter(), &self.layout, slot)
+        let rects = self.rects(slot);
+        Iter::new(self.content.iter(), rects.into_iter())
     }
 
     #[inline]
     fn children_mut(&mut self, slot: Rect) -> Self::IterMut<'_> {
-        Iter::new(self.content.iter_mut(), &self.layout, slot)
+        let rects = self.rects(slot);
+        Iter::new(self.content.iter_mut(), rects.into_iter())
     }
 }
 
 impl<T: Node> SimpleCollectionNode for StackBoxNode<T> {}
+
+#[cfg(test)]
+mod stack_box_tests {
+    use crate::headless::*;
+    use super::*;
+
+    /// A child with a size range along the row, which draws its slot so the layout shows up in the display list.
+    struct Item {
+        min: f32,
+        max: Option<f32>,
+    }
+
+    impl Node for Item {
+        fn size_range(&self) -> ((f32, Option<f32>), (f32, Option<f32>)) {
+            ((self.min, self.max), (0.0, None))
+        }
+    }
+
+    impl<TB> TickNode<TB> for Item {}
+
+    impl<DB: DrawBackend<Color = &'static str>> DrawNode<DB> for Item {
+        fn draw(&self, d: &mut DB, slot: Rect) {
+            d.draw_rect(&slot, &"item");
+        }
+    }
+
+    const fn item(min: f32, max: Option<f32>) -> Item {
+        Item { min, max }
+    }
+
+    fn row(gap: f32, items: impl IntoIterator<Item = (Item, StackSizing)>) -> StackBoxNode<Item> {
+        let (content, sizing): (Vec<_>, Vec<_>) = items.into_iter().unzip();
+        StackBoxNode::with_content(Direction::Row, gap, content).with_sizing(sizing)
+    }
+
+    /// Left and right edges of each child as laid out in a slot `width` wide.
+    fn spans(stack: &mut StackBoxNode<Item>, width: f32) -> Vec<(f32, f32)> {
+        let mut hb = HeadlessBackend::default();
+        hb.frame(stack, Rect { x_min: 0.0, y_min: 0.0, x_max: width, y_max: 10.0 });
+        hb.rects().map(|(rect, _)| (rect.x_min, rect.x_max)).collect()
+    }
+
+    #[test]
+    fn test_fixed_and_flex() {
+        let mut stack = row(2.0, [
+            (item(0.0, None), StackSizing::Fixed(20.0)),
+            (item(0.0, None), StackSizing::Flex(1.0)),
+            (item(0.0, None), StackSizing::Flex(3.0)),
+        ]);
+        // 104 wide, less two gaps, less the fixed child leaves 80 to share 1:3
+        assert_eq!(spans(&mut stack, 104.0), [(0.0, 20.0), (22.0, 42.0), (44.0, 104.0)]);
+    }
+
+    #[test]
+    fn test_fixed_is_at_least_min() {
+        let mut stack = row(0.0, [
+            (item(15.0, None), StackSizing::Fixed(5.0)),
+            (item(0.0, None), StackSizing::Flex(1.0)),
+        ]);
+        assert_eq!(spans(&mut stack, 100.0), [(0.0, 15.0), (15.0, 100.0)]);
+    }
+
+    #[test]
+    fn test_flex_clamped_to_max() {
+        let mut stack = row(0.0, [
+            (item(0.0, Some(10.0)), StackSizing::Flex(1.0)),
+            (item(0.0, None), StackSizing::Flex(1.0)),
+            (item(0.0, Some(20.0)), StackSizing::Flex(2.0)),
+        ]);
+        // the clamped children's shares go to the one without a max
+        assert_eq!(spans(&mut stack, 100.0), [(0.0, 10.0), (10.0, 80.0), (80.0, 100.0)]);
+    }
+
+    #[test]
+    fn test_flex_clamped_to_min() {
+        let mut stack = row(0.0, [
+            (item(60.0, None), StackSizing::Flex(1.0)),
+            (item(0.0, None), StackSizing::Flex(1.0)),
+        ]);
+        assert_eq!(spans(&mut stack, 100.0), [(0.0, 60.0), (60.0, 100.0)]);
+    }
+
+    #[test]
+    fn test_zero_weight_stays_at_min() {
+        let mut stack = row(0.0, [
+            (item(10.0, None), StackSizing::Flex(0.0)),
+            (item(20.0, Some(50.0)), StackSizing::Flex(0.0)),
+        ]);
+        assert_eq!(spans(&mut stack, 100.0), [(0.0, 10.0), (10.0, 30.0)]);
+
+        // and takes nothing from the children that do flex
+        stack.content.push(item(0.0, None));
+        assert_eq!(spans(&mut stack, 100.0), [(0.0, 10.0), (10.0, 30.0), (30.0, 100.0)]);
+    }
+}
@@ -0,0 +1,581 @@
+use std::{cell::RefCell, ops::Range};
+use crate::*;
+
+#[derive(Clone, Copy)]
+pub struct TextInputStyle<Color: Copy> {
+    pub font_size: f32,
+    /// Space between the edge of the box and the text.
+    pub padding: f32,
+    pub text_color: Color,
+    pub background_color: Color,
+    pub focused_background_color: Color,
+    pub selection_color: Color,
+    pub caret_color: Color,
+}
+
+/// The text and editing state of a [`TextInput`].
+///
+/// Caret and selection positions are byte indices into the text, and always on a char boundary.
+#[derive(Clone)]
+pub struct TextInputData<Color: Copy> {
+    pub id: FocusId,
+    pub style: TextInputStyle<Color>,
+    /// If set, [`Self::style`] follows the theme's style for this class.
+    pub theme_class: Option<&'static str>,
+    text: String,
+    caret: usize,
+    /// The end of the selection that stays put while the caret moves.
+    /// Same as the caret when nothing is selected.
+    anchor: usize,
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+impl<Color: Copy> TextInputData<Color> {
+    pub fn new(id: FocusId, style: TextInputStyle<Color>, text: impl Into<String>) -> Self {
+        let text = text.into();
+        let end = text.len();
+        Self {
+            id,
+            style,
+            theme_class: None,
+            text,
+            caret: end,
+            anchor: end,
+        }
+    }
+
+    /// Take the style from the theme's `class` from now on.
+    #[inline]
+    pub fn themed(mut self, class: &'static str) -> Self {
+        self.theme_class = Some(class);
+        self
+    }
+
+    #[inline]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replace the text, moving the caret to the end. Does not trigger `on_change`.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.caret = self.text.len();
+        self.anchor = self.caret;
+    }
+
+    #[inline]
+    pub const fn caret(&self) -> usize {
+        self.caret
+    }
+
+    /// The selected range of the text, which is empty if nothing is selected.
+    #[inline]
+    pub fn selection(&self) -> Range<usize> {
+        self.caret.min(self.anchor)..self.caret.max(self.anchor)
+    }
+
+    #[inline]
+    pub fn selected_text(&self) -> &str {
+        &self.text[self.selection()]
+    }
+
+    /// Move the caret to `index`, extending the selection if `is_selecting`, otherwise deselecting.
+    pub fn set_caret(&mut self, index: usize, is_selecting: bool) {
+        debug_assert!(self.text.is_char_boundary(index), "caret should always be on a char boundary");
+        self.caret = index;
+        if !is_selecting {
+            self.anchor = index;
+        }
+    }
+
+    pub fn select(&mut self, range: Range<usize>) {
+        self.anchor = range.start;
+        self.caret = range.end;
+    }
+
+    #[inline]
+    pub fn select_all(&mut self) {
+        self.select(0..self.text.len());
+    }
+
+    /// Replace the selection with `text`, leaving the caret after it.
+    pub fn insert(&mut self, text: &str) {
+        let selection = self.selection();
+        let caret = selection.start + text.len();
+        self.text.replace_range(selection, text);
+        self.set_caret(caret, false);
+    }
+
+    /// Remove the selected text. Returns false if there was nothing selected.
+    pub fn delete_selection(&mut self) -> bool {
+        if self.caret == self.anchor {
+            return false;
+        }
+        self.insert("");
+        true
+    }
+
+    fn prev_char(&self, index: usize) -> usize {
+        self.text[..index].char_indices().next_back().map_or(0, |(i, _)| i)
+    }
+
+    fn next_char(&self, index: usize) -> usize {
+        self.text[index..].chars().next().map_or(index, |ch| index + ch.len_utf8())
+    }
+
+    /// Start of the word before `index`, skipping any space between them.
+    fn prev_word(&self, index: usize) -> usize {
+        let mut chars = self.text[..index].char_indices().rev().peekable();
+        while chars.next_if(|&(_, ch)| !is_word_char(ch)).is_some() {}
+        let mut start = chars.peek().map_or(0, |&(i, _)| i);
+        while let Some((i, _)) = chars.next_if(|&(_, ch)| is_word_char(ch)) {
+            start = i;
+        }
+        start
+    }
+
+    /// End of the word after `index`, skipping any space between them.
+    fn next_word(&self, index: usize) -> usize {
+        let mut chars = self.text[index..].char_indices().peekable();
+        while chars.next_if(|&(_, ch)| !is_word_char(ch)).is_some() {}
+        while chars.next_if(|&(_, ch)| is_word_char(ch)).is_some() {}
+        chars.peek().map_or(self.text.len(), |&(i, _)| index + i)
+    }
+
+    /// The word under `index`, or the run of non-word characters if it isn't in a word.
+    fn word_at(&self, index: usize) -> Range<usize> {
+        let is_word = self.text[index..].chars().next()
+            .or_else(|| self.text[..index].chars().next_back())
+            .is_some_and(is_word_char);
+        let start = self.text[..index].char_indices().rev()
+            .take_while(|&(_, ch)| is_word_char(ch) == is_word)
+            .last()
+            .map_or(index, |(i, _)| i);
+        let end = self.text[index..].char_indices()
+            .find(|&(_, ch)| is_word_char(ch) != is_word)
+            .map_or(self.text.len(), |(i, _)| index + i);
+        start..end
+    }
+
+    /// Apply an editing key. Returns whether the text changed.
+    fn handle_key<TB: TickBackend>(&mut self, tb: &mut TB, event: KeyEvent) -> bool {
+        let Modifiers { shift, ctrl, .. } = event.modifiers;
+        match event.key {
+            Key::Left => {
+                let index = if !shift && self.caret != self.anchor {
+                    self.selection().start
+                } else if ctrl {
+                    self.prev_word(self.caret)
+                } else {
+                    self.prev_char(self.caret)
+                };
+                self.set_caret(index, shift);
+                false
+            }
+            Key::Right => {
+                let index = if !shift && self.caret != self.anchor {
+                    self.selection().end
+                } else if ctrl {
+                    self.next_word(self.caret)
+                } else {
+                    self.next_char(self.caret)
+                };
+                self.set_caret(index, shift);
+                false
+            }
+            Key::Home => {
+                self.set_caret(0, shift);
+                false
+            }
+            Key::End => {
+                self.set_caret(self.text.len(), shift);
+                false
+            }
+            Key::Backspace => {
+                if !self.delete_selection() {
+                    let index = if ctrl { self.prev_word(self.caret) } else { self.prev_char(self.caret) };
+                    self.set_caret(index, true);
+                    if !self.delete_selection() {
+                        return false;
+                    }
+                }
+                true
+            }
+            Key::Delete => {
+                if !self.delete_selection() {
+                    let index = if ctrl { self.next_word(self.caret) } else { self.next_char(self.caret) };
+                    self.set_caret(index, true);
+                    if !self.delete_selection() {
+                        return false;
+                    }
+                }
+                true
+            }
+            Key::A if ctrl => {
+                self.select_all();
+                false
+            }
+            Key::C if ctrl => {
+                if self.caret != self.anchor {
+                    tb.set_clipboard_text(self.selected_text());
+                }
+                false
+            }
+            Key::X if ctrl && self.caret != self.anchor => {
+                tb.set_clipboard_text(self.selected_text());
+                self.delete_selection();
+                true
+            }
+            Key::X if ctrl => false,
+            Key::V if ctrl => {
+                match tb.clipboard_text() {
+                    Some(text) => {
+                        // single-line field
+                        let text = text.lines().collect::<Vec<_>>().join(" ");
+                        self.insert(&text);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Where each character of the text was drawn, so that clicks can be turned into caret positions.
+#[derive(Default)]
+struct TextLayout {
+    /// Distance from the start of the text to the start of each character, plus one for the end of the text.
+    offsets: Vec<f32>,
+    /// The text and font size that [`Self::offsets`] were measured for.
+    measured: Option<(String, f32)>,
+    /// How far the text is scrolled to keep the caret in view.
+    scroll: f32,
+}
+
+/// A single-line editable text field.
+///
+/// `on_change` is called whenever the user edits the text, and `on_submit` when they press Enter.
+/// Escape or clicking elsewhere gives up focus without submitting.
+pub struct TextInput<Color: Copy, C, S> {
+    pub data: TextInputData<Color>,
+    pub on_change: C,
+    pub on_submit: S,
+    is_focused: bool,
+    is_selecting: bool,
+    layout: RefCell<TextLayout>,
+}
+
+impl<Color: Copy, C, S> TextInput<Color, C, S> {
+    pub fn new(data: TextInputData<Color>, on_change: C, on_submit: S) -> Self {
+        Self {
+            data,
+            on_change,
+            on_submit,
+            is_focused: false,
+            is_selecting: false,
+            layout: RefCell::default(),
+        }
+    }
+
+    /// The caret position closest to `x`, using the positions from the last draw.
+    fn index_at(&self, slot: Rect, x: f32) -> usize {
+        let layout = self.layout.borrow();
+        let x = x - (slot.x_min + self.data.style.padding) + layout.scroll;
+        let nearest = layout.offsets.iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (*a - x).abs().total_cmp(&(*b - x).abs()))
+            .map_or(0, |(i, _)| i);
+        // offsets may be a frame out of date
+        self.data.text.char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(self.data.text.len()))
+            .nth(nearest)
+            .unwrap_or(self.data.text.len())
+    }
+}
+
+impl<Color: Copy, C, S> Node for TextInput<Color, C, S> {
+    fn size_range(&self) -> ((f32, Option<f32>), (f32, Option<f32>)) {
+        let TextInputStyle { font_size, padding, .. } = self.data.style;
+        let height = font_size + padding * 2.0;
+        ((padding * 2.0, None), (height, Some(height)))
+    }
+}
+
+impl<Color, TB, C, S> TickNode<TB> for TextInput<Color, C, S>
+where
+    Color: Copy,
+    TB: TickBackend<Color = Color>,
+    C: FnMut(&mut TextInputData<Color>),
+    S: FnMut(&mut TextInputData<Color>),
+{
+    fn dibs_tick(&mut self, tb: &mut TB, slot: Rect, events: &mut Events) {
+        tb.theme().restyle(&mut self.data.style, self.data.theme_class);
+        let id = self.data.id;
+
+        // clicking anywhere else gives up focus
+        if events.is_focused(id) && events.mouse_event.event.as_ref()
+            .is_some_and(|mouse| !slot.contains(mouse.position) && mouse.is_pressed(MouseButton::Left))
+        {
+            events.set_focus(None);
+        }
+
+        if self.is_selecting {
+            if let Some(drag) = events.drag.take_with_dibs_if(|drag| drag.button == MouseButton::Left) {
+                let index = self.index_at(slot, drag.position.x);
+                self.data.set_caret(index, true);
+                if drag.kind == DragKind::End {
+                    self.is_selecting = false;
+                }
+            } else if !events.is_mouse_down(MouseButton::Left) {
+                self.is_selecting = false;
+            }
+        }
+
+        let mut is_changed = false;
+        // swallow plain typing keys too, so that single-key shortcuts don't fire while typing
+        let is_ours = |event: &KeyEvent| event.kind != KeyEventKind::Release && (!event.modifiers.ctrl || matches!(event.key,
+            Key::Left | Key::Right | Key::Home | Key::End | Key::Backspace | Key::Delete | Key::A | Key::C | Key::X | Key::V
+        ));
+        while let Some(event) = events.take_focused_key_if(id, is_ours) {
+            match event.key {
+                Key::Enter => {
+                    (self.on_submit)(&mut self.data);
+                    events.set_focus(None);
+                }
+                Key::Escape => events.set_focus(None),
+                _ => {
+                    is_changed |= self.data.handle_key(tb, event);
+                }
+            }
+        }
+        if let Some(text) = events.take_focused_text(id) {
+            self.data.insert(&text);
+            is_changed = true;
+        }
+        if is_changed {
+            (self.on_change)(&mut self.data);
+        }
+
+        self.is_focused = events.is_focused(id);
+    }
+
+    fn active_tick(&mut self, _tb: &mut TB, slot: Rect, events: &mut Events) {
+        if let Some(mut hover) = events.mouse_event.take_if_overlapping(slot)
+            && let Some(click) = hover.take_press(MouseButton::Left)
+        {
+            let index = self.index_at(slot, hover.position.x);
+            match click.count {
+                1 => {
+                    self.data.set_caret(index, events.is_focused(self.data.id) && events.modifiers.shift);
+                    self.is_selecting = true;
+                }
+                2 => self.data.select(self.data.word_at(index)),
+                _ => self.data.select_all(),
+            }
+            events.set_focus(Some(self.data.id));
+            self.is_focused = true;
+        }
+    }
+}
+
+impl<Color: Copy, DB: DrawBackend<Color = Color>, C, S> DrawNode<DB> for TextInput<Color, C, S> {
+    fn draw(&self, d: &mut DB, slot: Rect) {
+        let style = &self.data.style;
+        let text = &self.data.text;
+        let mut layout = self.layout.borrow_mut();
+
+        // measuring every prefix is quadratic, so only do it when the text changes
+        if layout.measured.as_ref().is_none_or(|(measured, font_size)| measured != text || *font_size != style.font_size) {
+            layout.offsets.clear();
+            layout.offsets.extend(
+                text.char_indices()
+                    .map(|(i, _)| i)
+                    .chain(std::iter::once(text.len()))
+                    .map(|i| d.measure_text(&text[..i], style.font_size))
+            );
+            layout.measured = Some((text.clone(), style.font_size));
+        }
+        let x_of = |index: usize| {
+            let n = text[..index].chars().count();
+            layout.offsets[n]
+        };
+        let caret_x = x_of(self.data.caret);
+        let selection = self.data.selection();
+        let (select_x0, select_x1) = (x_of(selection.start), x_of(selection.end));
+
+        // keep the caret in view
+        let view_width = (slot.width() - style.padding * 2.0).max(0.0);
+        let text_width = layout.offsets.last().copied().unwrap_or(0.0);
+        let mut scroll = layout.scroll.min((text_width - view_width).max(0.0));
+        if caret_x - scroll > view_width {
+            scroll = caret_x - view_width;
+        } else if caret_x < scroll {
+            scroll = caret_x;
+        }
+        layout.scroll = scroll;
+
+        let background = if self.is_focused { &style.focused_background_color } else { &style.background_color };
+        d.draw_rect(&slot, background);
+
+        // room for the caret at the far end
+        d.push_clip(&Rect { x_min: slot.x_min + style.padding, x_max: slot.x_max - style.padding + 1.0, ..slot });
+        let origin = Point { x: slot.x_min + style.padding - scroll, y: slot.y_min + style.padding };
+        let (y_min, y_max) = (origin.y, origin.y + style.font_size);
+        if self.is_focused && !selection.is_empty() {
+            d.draw_rect(&Rect { x_min: origin.x + select_x0, y_min, x_max: origin.x + select_x1, y_max }, &style.selection_color);
+        }
+        d.draw_text(text, origin, style.font_size, &style.text_color);
+        if self.is_focused {
+            let x = origin.x + caret_x;
+            d.draw_rect(&Rect { x_min: x, y_min, x_max: x + 1.0, y_max }, &style.caret_color);
+        }
+        d.pop_clip();
+    }
+}
+
+#[cfg(test)]
+mod text_input_tests {
+    use crate::headless::*;
+    use super::*;
+
+    const STYLE: TextInputStyle<&str> = TextInputStyle {
+        font_size: 10.0,
+        padding: 2.0,
+        text_color: "text",
+        background_color: "background",
+        focused_background_color: "focused",
+        selection_color: "selection",
+        caret_color: "caret",
+    };
+    const SLOT: Rect = Rect { x_min: 0.0, y_min: 0.0, x_max: 100.0, y_max: 14.0 };
+
+    fn data(text: &str) -> TextInputData<&'static str> {
+        TextInputData::new(FocusId(1), STYLE, text)
+    }
+
+    /// Press `key` with `modifiers` held, and let go of all of them on the next frame.
+    fn press<N: TickNode<HeadlessBackend<&'static str>> + DrawNode<HeadlessBackend<&'static str>>>(
+        hb: &mut HeadlessBackend<&'static str>,
+        node: &mut N,
+        modifiers: &[Key],
+        key: Key,
+    ) {
+        let down = modifiers.iter().chain([&key]).map(|&k| InputAction::KeyDown(k)).collect::<Vec<_>>();
+        let up = modifiers.iter().chain([&key]).map(|&k| InputAction::KeyUp(k)).collect::<Vec<_>>();
+        hb.run(node, SLOT, [down, up]);
+    }
+
+    fn caret_x(hb: &HeadlessBackend<&'static str>) -> Option<f32> {
+        hb.rects().find(|(_, color)| **color == "caret").map(|(rect, _)| rect.x_min)
+    }
+
+    #[test]
+    fn test_prev_word() {
+        let data = data("foo_bar  baz, qux");
+        assert_eq!(data.prev_word(17), 14);
+        assert_eq!(data.prev_word(14), 9);
+        // skips the space and punctuation before the word
+        assert_eq!(data.prev_word(13), 9);
+        assert_eq!(data.prev_word(9), 0);
+        assert_eq!(data.prev_word(4), 0);
+        assert_eq!(data.prev_word(0), 0);
+    }
+
+    #[test]
+    fn test_next_word() {
+        let data = data("foo_bar  baz, qux");
+        assert_eq!(data.next_word(0), 7);
+        assert_eq!(data.next_word(4), 7);
+        assert_eq!(data.next_word(7), 12);
+        assert_eq!(data.next_word(12), 17);
+        assert_eq!(data.next_word(17), 17);
+    }
+
+    #[test]
+    fn test_word_at() {
+        let data = data("foo_bar  baz");
+        assert_eq!(data.word_at(0), 0..7);
+        assert_eq!(data.word_at(3), 0..7);
+        // in the run of spaces
+        assert_eq!(data.word_at(8), 7..9);
+        assert_eq!(data.word_at(10), 9..12);
+        // past the end belongs to the last word
+        assert_eq!(data.word_at(12), 9..12);
+    }
+
+    #[test]
+    fn test_multibyte_words() {
+        let data = data("día über");
+        assert_eq!(data.next_word(0), "día".len());
+        assert_eq!(data.prev_word(data.text().len()), "día ".len());
+        assert_eq!(data.word_at(1), 0.."día".len());
+    }
+
+    #[test]
+    fn test_char_boundaries() {
+        let mut input = TextInput::new(data("aé😀b"), |_: &mut TextInputData<&str>| {}, |_: &mut TextInputData<&str>| {});
+        let mut hb = HeadlessBackend::default();
+        // clicks land on the characters from the last draw
+        hb.run(&mut input, SLOT, [[]]);
+        hb.click(&mut input, SLOT, Point { x: 90.0, y: 7.0 }, MouseButton::Left);
+        assert_eq!(input.data.caret(), input.data.text().len());
+        assert_eq!(caret_x(&hb), Some(2.0 + 4.0 * 5.0));
+
+        let mut carets = vec![input.data.caret()];
+        for _ in 0..4 {
+            press(&mut hb, &mut input, &[], Key::Left);
+            carets.push(input.data.caret());
+        }
+        // one char further per press, however many bytes it is
+        assert_eq!(carets, [8, 7, 3, 1, 0]);
+        assert_eq!(caret_x(&hb), Some(2.0));
+
+        press(&mut hb, &mut input, &[], Key::Right);
+        press(&mut hb, &mut input, &[], Key::Right);
+        assert_eq!(input.data.caret(), "aé".len());
+        press(&mut hb, &mut input, &[], Key::Delete);
+        assert_eq!(input.data.text(), "aéb");
+        press(&mut hb, &mut input, &[], Key::Backspace);
+        assert_eq!(input.data.text(), "ab");
+        assert_eq!(input.data.caret(), 1);
+        // the layout follows the edit
+        assert_eq!(caret_x(&hb), Some(2.0 + 5.0));
+    }
+
+    #[test]
+    fn test_paste() {
+        let changes = std::cell::Cell::new(0);
+        let mut input = TextInput::new(data("hello world"), |_: &mut TextInputData<&str>| changes.set(changes.get() + 1), |_: &mut TextInputData<&str>| {});
+        let mut hb = HeadlessBackend::default();
+
+        // nothing to paste
+        hb.run(&mut input, SLOT, [[]]);
+        hb.click(&mut input, SLOT, Point { x: 90.0, y: 7.0 }, MouseButton::Left);
+        press(&mut hb, &mut input, &[Key::LeftControl], Key::V);
+        assert_eq!(input.data.text(), "hello world");
+        assert_eq!(changes.get(), 0);
+
+        // replaces the selection, and newlines become spaces
+        input.data.select(6..11);
+        hb.clipboard = Some("big\nwide".to_string());
+        press(&mut hb, &mut input, &[Key::LeftControl], Key::V);
+        assert_eq!(input.data.text(), "hello big wide");
+        assert_eq!(input.data.caret(), input.data.text().len());
+        assert_eq!(changes.get(), 1);
+
+        // copying and cutting go the other way
+        input.data.select(0..5);
+        press(&mut hb, &mut input, &[Key::LeftControl], Key::X);
+        assert_eq!(hb.clipboard.as_deref(), Some("hello"));
+        assert_eq!(input.data.text(), " big wide");
+        press(&mut hb, &mut input, &[Key::LeftControl], Key::Home);
+        press(&mut hb, &mut input, &[Key::LeftControl], Key::V);
+        assert_eq!(input.data.text(), "hello big wide");
+    }
+}
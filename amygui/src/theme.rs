@@ -0,0 +1,471 @@
+use std::{collections::HashMap, io, path::Path};
+use crate::*;
+
+/// A named set of colors that widgets draw with, by role.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette<Color: Copy> {
+    /// Behind everything, such as panel backgrounds.
+    pub background: Color,
+    /// Things that sit on the background, such as buttons and fields.
+    pub surface: Color,
+    pub hover: Color,
+    pub press: Color,
+    pub disabled: Color,
+    pub text: Color,
+    pub text_disabled: Color,
+    /// Highlights, such as the active item and drop targets.
+    pub accent: Color,
+    pub selection: Color,
+}
+
+impl<Color: Copy> Palette<Color> {
+    pub const ROLES: [&str; 9] = ["background", "surface", "hover", "press", "disabled", "text", "text_disabled", "accent", "selection"];
+
+    /// Every role the same color.
+    pub const fn splat(color: Color) -> Self {
+        Self {
+            background: color,
+            surface: color,
+            hover: color,
+            press: color,
+            disabled: color,
+            text: color,
+            text_disabled: color,
+            accent: color,
+            selection: color,
+        }
+    }
+
+    /// Look up a role by its name in [`Self::ROLES`].
+    pub fn get(&self, role: &str) -> Option<Color> {
+        Some(match role {
+            "background" => self.background,
+            "surface" => self.surface,
+            "hover" => self.hover,
+            "press" => self.press,
+            "disabled" => self.disabled,
+            "text" => self.text,
+            "text_disabled" => self.text_disabled,
+            "accent" => self.accent,
+            "selection" => self.selection,
+            _ => return None,
+        })
+    }
+}
+
+/// A value that replaces part of a widget's themed style.
+#[derive(Clone, PartialEq, Debug)]
+pub enum StyleOverride<Color> {
+    Color(Color),
+    /// A role in whichever palette is active, so it follows palette changes.
+    Role(String),
+    Number(f32),
+}
+
+/// Fonts, spacing and colors shared by every widget, with overrides for particular widgets.
+///
+/// Widgets are themed by class: a name such as `"button"`, or something more specific like `"layers.add"`
+/// for a single widget. Overrides set for a class take the place of what the palette and metrics would give it.
+#[derive(Clone, Debug)]
+pub struct Theme<Color: Copy> {
+    palettes: Vec<(String, Palette<Color>)>,
+    active: usize,
+    pub font_size: f32,
+    /// Space between the edge of a widget and its content.
+    pub padding: f32,
+    /// Space between neighboring widgets.
+    pub spacing: f32,
+    pub corner_radius: f32,
+    pub handle_size: f32,
+    pub bar_size: f32,
+    pub scroll_speed: f32,
+    overrides: HashMap<String, HashMap<String, StyleOverride<Color>>>,
+}
+
+impl<Color: Copy> Theme<Color> {
+    pub fn new(palette_name: impl Into<String>, palette: Palette<Color>) -> Self {
+        Self {
+            palettes: vec![(palette_name.into(), palette)],
+            active: 0,
+            font_size: 10.0,
+            padding: 4.0,
+            spacing: 4.0,
+            corner_radius: 3.0,
+            handle_size: 4.0,
+            bar_size: 8.0,
+            scroll_speed: 20.0,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Add a palette, or replace the one with the same name.
+    pub fn add_palette(&mut self, name: impl Into<String>, palette: Palette<Color>) {
+        let name = name.into();
+        match self.palettes.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, p)) => *p = palette,
+            None => self.palettes.push((name, palette)),
+        }
+    }
+
+    /// The palette in use.
+    #[inline]
+    pub fn palette(&self) -> &Palette<Color> {
+        &self.palettes[self.active].1
+    }
+
+    #[inline]
+    pub fn palette_name(&self) -> &str {
+        &self.palettes[self.active].0
+    }
+
+    pub fn palette_names(&self) -> impl Iterator<Item = &str> {
+        self.palettes.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Switch palettes. Returns false if there is no palette named `name`.
+    pub fn set_palette(&mut self, name: &str) -> bool {
+        match self.palettes.iter().position(|(n, _)| n == name) {
+            Some(index) => {
+                self.active = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Switch to the palette after the current one, wrapping around.
+    pub fn next_palette(&mut self) {
+        self.active = (self.active + 1) % self.palettes.len();
+    }
+
+    pub fn set_override(&mut self, class: impl Into<String>, property: impl Into<String>, value: StyleOverride<Color>) {
+        self.overrides.entry(class.into()).or_default().insert(property.into(), value);
+    }
+
+    #[inline]
+    pub fn get_override(&self, class: &str, property: &str) -> Option<&StyleOverride<Color>> {
+        self.overrides.get(class)?.get(property)
+    }
+
+    /// A color property of a widget class, or `default` if the class doesn't override it.
+    pub fn color(&self, class: &str, property: &str, default: Color) -> Color {
+        match self.get_override(class, property) {
+            Some(StyleOverride::Color(color)) => *color,
+            Some(StyleOverride::Role(role)) => self.palette().get(role).unwrap_or(default),
+            Some(StyleOverride::Number(_)) | None => default,
+        }
+    }
+
+    /// A numeric property of a widget class, or `default` if the class doesn't override it.
+    pub fn number(&self, class: &str, property: &str, default: f32) -> f32 {
+        match self.get_override(class, property) {
+            Some(StyleOverride::Number(n)) => *n,
+            _ => default,
+        }
+    }
+
+    /// Replace `style` with this theme's style for `class`, for widgets that have one.
+    #[inline]
+    pub fn restyle<S: Themed<Color>>(&self, style: &mut S, class: Option<&str>) {
+        if let Some(class) = class {
+            *style = S::from_theme(self, class);
+        }
+    }
+
+    /// Read a theme from text.
+    ///
+    /// ```text
+    /// # lines starting with '#' are comments
+    /// font_size = 10
+    /// palette = dark
+    ///
+    /// [palette dark]
+    /// background = #282828
+    /// surface = #606060
+    /// # ...and the rest of Palette::ROLES
+    ///
+    /// [button]
+    /// hover_color = accent
+    /// press_color = #c8c8c8ff
+    /// ```
+    ///
+    /// Settings before the first section are [`Theme`]'s metrics, plus `palette` to choose the active palette
+    /// (otherwise the first). `[palette NAME]` sections must give every role.
+    /// Any other section overrides the style properties of the widget class it names,
+    /// with a color (`#rrggbb` or `#rrggbbaa`), a palette role, or a number.
+    ///
+    /// `from_rgba` makes a backend color from red, green, blue and alpha.
+    pub fn parse(text: &str, from_rgba: impl Fn([u8; 4]) -> Color) -> io::Result<Self> {
+        fn error(line: usize, msg: impl std::fmt::Display) -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidData, format!("theme line {line}: {msg}"))
+        }
+
+        fn parse_color(value: &str) -> Option<[u8; 4]> {
+            let hex = value.strip_prefix('#')?;
+            if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+                return None;
+            }
+            let channel = |i: usize| hex.get(i * 2..i * 2 + 2).map(|s| u8::from_str_radix(s, 16));
+            let mut rgba = [255; 4];
+            for (i, c) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+                *c = channel(i)?.ok()?;
+            }
+            Some(rgba)
+        }
+
+        enum Section<Color> {
+            Metrics,
+            /// Name, the colors given so far in the order of [`Palette::ROLES`], and the line it starts on.
+            Palette(String, [Option<Color>; 9], usize),
+            Class(String),
+        }
+
+        let mut palettes = Vec::new();
+        let mut active = None;
+        let mut metrics = Vec::new();
+        let mut overrides = Vec::new();
+        let mut section = Section::Metrics;
+
+        fn end_section<Color: Copy>(section: Section<Color>, palettes: &mut Vec<(String, Palette<Color>)>) -> io::Result<()> {
+            if let Section::Palette(name, roles, line) = section {
+                if let Some(missing) = roles.iter().position(Option::is_none) {
+                    return Err(error(line, format!("palette {name} is missing {}", Palette::<Color>::ROLES[missing])));
+                }
+                let [background, surface, hover, press, disabled, text, text_disabled, accent, selection] = roles.map(Option::unwrap);
+                palettes.push((name, Palette { background, surface, hover, press, disabled, text, text_disabled, accent, selection }));
+            }
+            Ok(())
+        }
+
+        for (index, line) in text.lines().enumerate() {
+            let n = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                let header = header.trim();
+                let next = match header.strip_prefix("palette ") {
+                    Some(name) => Section::Palette(name.trim().to_string(), [None; 9], n),
+                    None if header.is_empty() => return Err(error(n, "empty section name")),
+                    None => Section::Class(header.to_string()),
+                };
+                end_section(std::mem::replace(&mut section, next), &mut palettes)?;
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| error(n, "expected `key = value`"))?;
+            let (key, value) = (key.trim(), value.trim());
+            match &mut section {
+                Section::Metrics if key == "palette" => active = Some((value.to_string(), n)),
+                Section::Metrics => {
+                    let number = value.parse::<f32>().map_err(|_| error(n, format!("{key} should be a number")))?;
+                    metrics.push((key.to_string(), number, n));
+                }
+                Section::Palette(_, roles, _) => {
+                    let index = Palette::<Color>::ROLES.iter().position(|&role| role == key)
+                        .ok_or_else(|| error(n, format!("unknown palette role {key}")))?;
+                    let rgba = parse_color(value).ok_or_else(|| error(n, format!("{value} is not a color")))?;
+                    roles[index] = Some(from_rgba(rgba));
+                }
+                Section::Class(class) => {
+                    let value = if value.starts_with('#') {
+                        StyleOverride::Color(from_rgba(parse_color(value).ok_or_else(|| error(n, format!("{value} is not a color")))?))
+                    } else if let Ok(number) = value.parse::<f32>() {
+                        StyleOverride::Number(number)
+                    } else if Palette::<Color>::ROLES.contains(&value) {
+                        StyleOverride::Role(value.to_string())
+                    } else {
+                        return Err(error(n, format!("{value} is not a color, number or palette role")));
+                    };
+                    overrides.push((class.clone(), key.to_string(), value));
+                }
+            }
+        }
+        end_section(section, &mut palettes)?;
+
+        let mut palettes = palettes.into_iter();
+        let (name, palette) = palettes.next().ok_or_else(|| error(text.lines().count(), "theme has no palettes"))?;
+        let mut theme = Self::new(name, palette);
+        for (name, palette) in palettes {
+            theme.add_palette(name, palette);
+        }
+        if let Some((name, n)) = active && !theme.set_palette(&name) {
+            return Err(error(n, format!("no palette named {name}")));
+        }
+        for (key, number, n) in metrics {
+            *match key.as_str() {
+                "font_size" => &mut theme.font_size,
+                "padding" => &mut theme.padding,
+                "spacing" => &mut theme.spacing,
+                "corner_radius" => &mut theme.corner_radius,
+                "handle_size" => &mut theme.handle_size,
+                "bar_size" => &mut theme.bar_size,
+                "scroll_speed" => &mut theme.scroll_speed,
+                _ => return Err(error(n, format!("unknown setting {key}"))),
+            } = number;
+        }
+        for (class, property, value) in overrides {
+            theme.set_override(class, property, value);
+        }
+        Ok(theme)
+    }
+
+    /// Read a theme from a file. See [`Self::parse`] for the format.
+    pub fn load(path: impl AsRef<Path>, from_rgba: impl Fn([u8; 4]) -> Color) -> io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?, from_rgba)
+    }
+}
+
+/// A style that can be made from a [`Theme`].
+pub trait Themed<Color: Copy> {
+    /// The style for widgets of `class`, including any overrides the theme has for it.
+    /// Property names match the style's field names.
+    fn from_theme(theme: &Theme<Color>, class: &str) -> Self;
+}
+
+impl<Color: Copy> Themed<Color> for ButtonStyle<Color> {
+    fn from_theme(theme: &Theme<Color>, class: &str) -> Self {
+        let palette = theme.palette();
+        Self {
+            disabled_color: theme.color(class, "disabled_color", palette.disabled),
+            normal_color: theme.color(class, "normal_color", palette.surface),
+            hover_color: theme.color(class, "hover_color", palette.hover),
+            press_color: theme.color(class, "press_color", palette.press),
+        }
+    }
+}
+
+impl<Color: Copy> Themed<Color> for LabelStyle<Color> {
+    fn from_theme(theme: &Theme<Color>, class: &str) -> Self {
+        Self {
+            font_size: theme.number(class, "font_size", theme.font_size),
+            color: theme.color(class, "color", theme.palette().text),
+        }
+    }
+}
+
+impl<Color: Copy> Themed<Color> for TextInputStyle<Color> {
+    fn from_theme(theme: &Theme<Color>, class: &str) -> Self {
+        let palette = theme.palette();
+        Self {
+            font_size: theme.number(class, "font_size", theme.font_size),
+            padding: theme.number(class, "padding", theme.padding),
+            text_color: theme.color(class, "text_color", palette.text),
+            background_color: theme.color(class, "background_color", palette.surface),
+            focused_background_color: theme.color(class, "focused_background_color", palette.background),
+            selection_color: theme.color(class, "selection_color", palette.selection),
+            caret_color: theme.color(class, "caret_color", palette.text),
+        }
+    }
+}
+
+impl<Color: Copy> Themed<Color> for SplitBoxStyle<Color> {
+    fn from_theme(theme: &Theme<Color>, class: &str) -> Self {
+        let palette = theme.palette();
+        Self {
+            handle_size: theme.number(class, "handle_size", theme.handle_size),
+            handle_color: theme.color(class, "handle_color", palette.surface),
+            handle_hover_color: theme.color(class, "handle_hover_color", palette.accent),
+        }
+    }
+}
+
+impl<Color: Copy> Themed<Color> for ScrollBoxStyle<Color> {
+    fn from_theme(theme: &Theme<Color>, class: &str) -> Self {
+        let palette = theme.palette();
+        Self {
+            bar_size: theme.number(class, "bar_size", theme.bar_size),
+            scroll_speed: theme.number(class, "scroll_speed", theme.scroll_speed),
+            track_color: theme.color(class, "track_color", palette.background),
+            thumb_color: theme.color(class, "thumb_color", palette.surface),
+            thumb_hover_color: theme.color(class, "thumb_hover_color", palette.hover),
+        }
+    }
+}
+
+#[cfg(test)]
+mod theme_tests {
+    use super::*;
+
+    const THEME: &str = "
+        # metrics
+        font_size = 12
+        palette = light
+
+        [palette dark]
+        background = #000000
+        surface = #111111
+        hover = #222222
+        press = #333333
+        disabled = #444444
+        text = #ffffff
+        text_disabled = #888888
+        accent = #0000ff
+        selection = #0000ff80
+
+        [palette light]
+        background = #ffffff
+        surface = #eeeeee
+        hover = #dddddd
+        press = #cccccc
+        disabled = #bbbbbb
+        text = #000000
+        text_disabled = #777777
+        accent = #ff0000
+        selection = #ff000080
+
+        [button]
+        hover_color = accent
+        press_color = #123456
+
+        [layers.add]
+        font_size = 20
+    ";
+
+    fn load() -> Theme<[u8; 4]> {
+        Theme::parse(THEME, |rgba| rgba).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let theme = load();
+        assert_eq!(theme.font_size, 12.0);
+        assert_eq!(theme.padding, 4.0);
+        assert_eq!(theme.palette_names().collect::<Vec<_>>(), ["dark", "light"]);
+        assert_eq!(theme.palette_name(), "light");
+        assert_eq!(theme.palette().selection, [255, 0, 0, 128]);
+        assert_eq!(theme.palette().surface, [238, 238, 238, 255]);
+    }
+
+    #[test]
+    fn test_overrides_follow_palette() {
+        let mut theme = load();
+        let style = ButtonStyle::from_theme(&theme, "button");
+        assert_eq!(style.normal_color, [238, 238, 238, 255]);
+        assert_eq!(style.hover_color, [255, 0, 0, 255]);
+        assert_eq!(style.press_color, [0x12, 0x34, 0x56, 255]);
+
+        theme.next_palette();
+        assert_eq!(theme.palette_name(), "dark");
+        let style = ButtonStyle::from_theme(&theme, "button");
+        assert_eq!(style.hover_color, [0, 0, 255, 255]);
+        assert_eq!(style.press_color, [0x12, 0x34, 0x56, 255]);
+
+        assert_eq!(LabelStyle::from_theme(&theme, "layers.add").font_size, 20.0);
+        assert_eq!(LabelStyle::from_theme(&theme, "label").font_size, 12.0);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let missing_role = "[palette dark]\nbackground = #000000\n";
+        assert!(Theme::parse(missing_role, |rgba| rgba).is_err());
+        assert!(Theme::parse("font_size = big", |rgba| rgba).is_err());
+        assert!(Theme::parse("", |rgba| rgba).is_err());
+        let bad_color = THEME.replace("press_color = #123456", "press_color = #12345");
+        assert!(Theme::parse(&bad_color, |rgba| rgba).is_err());
+        let unknown_palette = THEME.replace("palette = light", "palette = sepia");
+        assert!(Theme::parse(&unknown_palette, |rgba| rgba).is_err());
+    }
+}
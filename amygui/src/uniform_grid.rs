@@ -146,3 +146,50 @@ impl<T: Node> CollectionNode for UniformGridNode<T> {
 }
 
 impl<T: Node> SimpleCollectionNode for UniformGridNode<T> {}
+
+#[cfg(test)]
+mod uniform_grid_tests {
+    use crate::headless::*;
+    use super::*;
+
+    type Child = Button<&'static str, Empty, fn(&mut ButtonData<&'static str>)>;
+
+    fn grid(len: usize) -> UniformGridNode<Child> {
+        UniformGridNode::from_iter(50.0, 20.0, 10.0, 5.0, NonZeroU32::new(2).unwrap(),
+            (0..len).map(|_| Button::new(ButtonData::new(BUTTON_STYLE), (|_| ()) as fn(&mut _), Empty)))
+    }
+
+    #[test]
+    fn test_size_range() {
+        assert_eq!(grid(5).size_range(), ((110.0, Some(110.0)), (70.0, Some(70.0))));
+        assert_eq!(grid(4).size_range(), ((110.0, Some(110.0)), (45.0, Some(45.0))));
+    }
+
+    #[test]
+    fn test_layout() {
+        let mut grid = grid(5);
+        let mut hb = HeadlessBackend::default();
+        hb.frame(&mut grid, rect(100.0, 50.0, 300.0, 200.0));
+        let rects = hb.rects().map(|(rect, _)| *rect).collect::<Vec<_>>();
+        assert_eq!(rects, [
+            rect(100.0, 50.0, 150.0, 70.0), rect(160.0, 50.0, 210.0, 70.0),
+            rect(100.0, 75.0, 150.0, 95.0), rect(160.0, 75.0, 210.0, 95.0),
+            rect(100.0, 100.0, 150.0, 120.0),
+        ]);
+    }
+
+    #[test]
+    fn test_hover_reaches_one_child() {
+        let mut grid = grid(5);
+        let mut hb = HeadlessBackend::default();
+        let slot = rect(100.0, 50.0, 300.0, 200.0);
+
+        hb.run(&mut grid, slot, [[InputAction::MoveMouse(Point { x: 170.0, y: 80.0 })]]);
+        let colors = hb.rects().map(|(_, color)| *color).collect::<Vec<_>>();
+        assert_eq!(colors, ["normal", "normal", "normal", "hover", "normal"]);
+
+        // in the gap between columns
+        hb.run(&mut grid, slot, [[InputAction::MoveMouse(Point { x: 155.0, y: 80.0 })]]);
+        assert!(hb.rects().all(|(_, color)| *color == "normal"));
+    }
+}
@@ -1,39 +1,8 @@
-use crate::*;
-
-struct PanZoomEvents<'a> {
-    original_mouse_pos: Option<Point>,
-    events: &'a mut Events,
-}
-
-impl Drop for PanZoomEvents<'_> {
-    fn drop(&mut self) {
-        if let Some(event) = self.events.mouse_event.event.as_mut() {
-            let original = self.original_mouse_pos.expect("original position should be Some if mouse event exists");
-            event.position.x = original.x;
-            event.position.y = original.y;
-        }
-    }
-}
-
-impl<'a> PanZoomEvents<'a> {
-    pub fn new(events: &'a mut Events, pan: Point, zoom: f32) -> Self {
-        let original_mouse_pos;
-        if let Some(event) = events.mouse_event.event.as_mut() {
-            original_mouse_pos = Some(event.position);
-            event.position.x = (event.position.x + pan.x) * zoom;
-            event.position.y = (event.position.y + pan.y) * zoom;
-        } else {
-            original_mouse_pos = None;
-        }
-        Self {
-            original_mouse_pos,
-            events,
-        }
-    }
-}
+use crate::{events::TransformedEvents, *};
 
 /// A UI element whose contents can be zoomed and panned
 pub struct Viewport<T> {
+    /// The point in the content's space shown at the top left corner of the viewport.
     pub pan: Point,
     pub zoom: f32,
     pub width: f32,
@@ -41,6 +10,28 @@ pub struct Viewport<T> {
     pub content: T,
 }
 
+impl<T> Viewport<T> {
+    /// From the content's space to the space around the viewport.
+    #[inline]
+    pub fn transform(&self, slot: Rect) -> Transform {
+        Transform {
+            offset: Point {
+                x: slot.x_min - self.pan.x * self.zoom,
+                y: slot.y_min - self.pan.y * self.zoom,
+            },
+            scale: self.zoom,
+        }
+    }
+
+    /// Change the zoom while keeping the content under `anchor` (in the space around the viewport) in place.
+    pub fn zoom_about(&mut self, slot: Rect, anchor: Point, zoom: f32) {
+        let fixed = self.transform(slot).invert(anchor);
+        self.zoom = zoom;
+        self.pan.x = fixed.x - (anchor.x - slot.x_min) / zoom;
+        self.pan.y = fixed.y - (anchor.y - slot.y_min) / zoom;
+    }
+}
+
 impl<T: Node> Node for Viewport<T> {
     #[inline]
     fn size_range(&self) -> ((f32, Option<f32>), (f32, Option<f32>)) {
@@ -50,33 +41,46 @@ impl<T: Node> Node for Viewport<T> {
 
 impl<TB, T: TickNode<TB>> TickNode<TB> for Viewport<T> {
     fn dibs_tick(&mut self, tb: &mut TB, slot: Rect, events: &mut Events) {
-        let vp_events = PanZoomEvents::new(events, self.pan, self.zoom);
         let slot = self.bounds(slot);
-        self.content.dibs_tick(tb, slot, vp_events.events);
+        let vp_events = TransformedEvents::new(events, self.transform(slot));
+        let (item, slot) = self.child_mut(slot);
+        item.dibs_tick(tb, slot, vp_events.events);
     }
 
     fn active_tick(&mut self, tb: &mut TB, slot: Rect, events: &mut Events) {
-        let vp_events = PanZoomEvents::new(events, self.pan, self.zoom);
         let slot = self.bounds(slot);
-        self.content.active_tick(tb, slot, vp_events.events);
+        let vp_events = TransformedEvents::new(events, self.transform(slot));
+        let (item, slot) = self.child_mut(slot);
+        if vp_events.events.mouse_event.is_some_and_overlapping(slot) {
+            item.active_tick(tb, slot, vp_events.events);
+        } else {
+            item.inactive_tick(tb, slot, vp_events.events);
+        }
     }
 
     fn inactive_tick(&mut self, tb: &mut TB, slot: Rect, events: &Events) {
-        let mut events = *events;
-        let vp_events = PanZoomEvents::new(&mut events, self.pan, self.zoom);
         let slot = self.bounds(slot);
-        self.content.inactive_tick(tb, slot, vp_events.events);
+        let mut events = events.clone();
+        let vp_events = TransformedEvents::new(&mut events, self.transform(slot));
+        let (item, slot) = self.child_mut(slot);
+        item.inactive_tick(tb, slot, vp_events.events);
     }
 }
 
-impl<DB, T: DrawNode<DB>> DrawNode<DB> for Viewport<T> {
+impl<DB: DrawBackend, T: DrawNode<DB>> DrawNode<DB> for Viewport<T> {
     fn draw(&self, d: &mut DB, slot: Rect) {
-
+        let slot = self.bounds(slot);
+        d.push_clip(&slot);
+        d.push_transform(&self.transform(slot));
+        let (item, slot) = self.child(slot);
+        item.draw(d, slot);
+        d.pop_transform();
+        d.pop_clip();
     }
 }
 
-impl<T: Node> ParentNode for Viewport<SizeBoxNode<T>> {
-    type Item = SizeBoxNode<T>;
+impl<T: Node> ParentNode for Viewport<T> {
+    type Item = T;
 
     #[inline]
     fn content(&self) -> &Self::Item {
@@ -87,4 +91,70 @@ impl<T: Node> ParentNode for Viewport<SizeBoxNode<T>> {
     fn content_mut(&mut self) -> &mut Self::Item {
         &mut self.content
     }
-}
\ No newline at end of file
+
+    /// The content is laid out in its own space, starting at the origin,
+    /// as close to the size of the viewport at a zoom of 1 as its size range allows.
+    #[inline]
+    fn child(&self, _slot: Rect) -> (&Self::Item, Rect) {
+        let ((w_min, w_max), (h_min, h_max)) = self.content.size_range();
+        let width = self.width.min(w_max.unwrap_or(f32::INFINITY)).max(w_min);
+        let height = self.height.min(h_max.unwrap_or(f32::INFINITY)).max(h_min);
+        (self.content(), Rect { x_min: 0.0, y_min: 0.0, x_max: width, y_max: height })
+    }
+
+    #[inline]
+    fn child_mut(&mut self, slot: Rect) -> (&mut Self::Item, Rect) {
+        let (_, slot) = self.child(slot);
+        (self.content_mut(), slot)
+    }
+}
+
+#[cfg(test)]
+mod viewport_tests {
+    use crate::headless::*;
+    use super::*;
+
+    /// Bigger than the viewport, which only takes up the top left 100x80.
+    const SLOT: Rect = rect(20.0, 30.0, 200.0, 200.0);
+    const BOUNDS: Rect = rect(20.0, 30.0, 120.0, 110.0);
+
+    /// Twice as big, with (10, 20) of the 50x40 content at the top left corner.
+    fn viewport() -> Viewport<Probe> {
+        Viewport { pan: Point { x: 10.0, y: 20.0 }, zoom: 2.0, width: 100.0, height: 80.0, content: Probe::new(50.0, 40.0) }
+    }
+
+    #[test]
+    fn test_transform_and_clip() {
+        let mut viewport = viewport();
+        let mut hb = HeadlessBackend::default();
+
+        hb.frame(&mut viewport, SLOT);
+        assert_eq!(hb.display_list, [DrawCall {
+            command: DrawCommand::Rect { rect: rect(0.0, -10.0, 100.0, 70.0), color: "probe" },
+            clip: Some(BOUNDS),
+        }]);
+    }
+
+    #[test]
+    fn test_click_in_content_space() {
+        let mut viewport = viewport();
+        let mut hb = HeadlessBackend::default();
+
+        hb.click(&mut viewport, SLOT, Point { x: 60.0, y: 50.0 }, MouseButton::Left);
+        assert_eq!(viewport.content.pressed_at, Some(Point { x: 30.0, y: 30.0 }));
+
+        // inside the viewport, but past the edge of the content
+        viewport.content.pressed_at = None;
+        hb.click(&mut viewport, SLOT, Point { x: 110.0, y: 100.0 }, MouseButton::Left);
+        assert_eq!(viewport.content.pressed_at, None);
+    }
+
+    #[test]
+    fn test_zoom_about_keeps_anchor() {
+        let mut viewport = viewport();
+        let anchor = Point { x: 60.0, y: 50.0 };
+
+        viewport.zoom_about(BOUNDS, anchor, 4.0);
+        assert_eq!(viewport.transform(BOUNDS).invert(anchor), Point { x: 30.0, y: 30.0 });
+    }
+}
@@ -1,8 +1,8 @@
-use std::num::NonZeroU16;
+use std::{io, num::NonZeroU16, path::Path, rc::Rc};
 use raylib::prelude::*;
-use crate::raster::RcRaster;
+use crate::{file, raster::{Pixels, RcRaster}};
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum BlendFactor {
     Zero                  = ffi::RL_ZERO                     as isize,
     One                   = ffi::RL_ONE                      as isize,
@@ -21,7 +21,7 @@ pub enum BlendFactor {
     OneMinusConstantAlpha = ffi::RL_ONE_MINUS_CONSTANT_ALPHA as isize,
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
 pub enum BlendEquation {
     #[default]
     FuncAdd             = ffi::RL_FUNC_ADD              as isize,
@@ -31,7 +31,7 @@ pub enum BlendEquation {
     Max                 = ffi::RL_MAX                   as isize,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum BlendModeA {
     Alpha,
     Additive,
@@ -60,6 +60,36 @@ impl Default for BlendModeA {
     }
 }
 
+impl BlendModeA {
+    /// Remove alpha from the destination in proportion to the source alpha, leaving the destination color untouched.
+    pub const ERASE: Self = Self::CustomSeparate {
+        src_rgb: BlendFactor::Zero,
+        dst_rgb: BlendFactor::One,
+        src_alpha: BlendFactor::Zero,
+        dst_alpha: BlendFactor::OneMinusSrcAlpha,
+        eq_rgb: BlendEquation::FuncAdd,
+        eq_alpha: BlendEquation::FuncAdd,
+    };
+
+    /// Overwrite the destination with the source.
+    pub const REPLACE: Self = Self::Custom {
+        src_factor: BlendFactor::One,
+        dst_factor: BlendFactor::Zero,
+        equation: BlendEquation::FuncAdd,
+    };
+
+    /// Keep the more opaque of the source and destination alpha, taking the source color.
+    /// Meant for layering dabs of one color, which shouldn't build up past that color's own alpha.
+    pub const MAX_ALPHA: Self = Self::CustomSeparate {
+        src_rgb: BlendFactor::One,
+        dst_rgb: BlendFactor::Zero,
+        src_alpha: BlendFactor::One,
+        dst_alpha: BlendFactor::One,
+        eq_rgb: BlendEquation::FuncAdd,
+        eq_alpha: BlendEquation::Max,
+    };
+}
+
 pub trait AmyBlendModeExt: RaylibBlendModeExt {
     fn begin_blend_mode_a(&mut self, blend_mode: BlendModeA) -> RaylibBlendMode<'_, Self> {
         match blend_mode {
@@ -82,19 +112,96 @@ pub trait AmyBlendModeExt: RaylibBlendModeExt {
 }
 impl<D: RaylibBlendModeExt> AmyBlendModeExt for D {}
 
+/// Shape stamped along a stroke.
+#[derive(Clone)]
+pub enum BrushTip {
+    /// Circle, softened toward the edge by [`BrushPreset::hardness`].
+    Round,
+    /// Square, softened toward the edge by [`BrushPreset::hardness`].
+    Square,
+    /// Coverage mask taken from an image, stretched to the brush size.
+    Mask(Rc<Pixels>),
+}
+
+impl PartialEq for BrushTip {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Round, Self::Round) | (Self::Square, Self::Square) => true,
+            (Self::Mask(a), Self::Mask(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl BrushTip {
+    /// Load a mask tip from an image file.
+    ///
+    /// Images with transparency use their alpha as coverage. Opaque images use darkness, so black is fully covered.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut pixels = file::image::import_pixels(path)?;
+        let is_opaque = pixels.data.iter().all(|px| px.a == 255);
+        for px in &mut pixels.data {
+            let coverage = if is_opaque {
+                255 - ((px.r as u32 * 299 + px.g as u32 * 587 + px.b as u32 * 114) / 1000) as u8
+            } else {
+                px.a
+            };
+            *px = Color::new(255, 255, 255, coverage);
+        }
+        Ok(Self::Mask(Rc::new(pixels)))
+    }
+
+    /// Render the tip at `size`x`size` as white with coverage in alpha.
+    fn to_pixels(&self, size: u32, hardness: f32) -> Pixels {
+        let mut pixels = Pixels::new(size, size, Color::BLANK);
+        let radius = size as f32 * 0.5;
+        let hardness = hardness.clamp(0.0, 1.0);
+        for y in 0..size {
+            for x in 0..size {
+                let (dx, dy) = ((x as f32 + 0.5 - radius) / radius, (y as f32 + 0.5 - radius) / radius);
+                let coverage = match self {
+                    Self::Round | Self::Square => {
+                        let dist = match self {
+                            Self::Round => (dx * dx + dy * dy).sqrt(),
+                            _ => dx.abs().max(dy.abs()),
+                        };
+                        if dist > 1.0 {
+                            0.0
+                        } else if dist <= hardness {
+                            1.0
+                        } else {
+                            let t = (1.0 - dist) / (1.0 - hardness);
+                            t * t * (3.0 - 2.0 * t) // smoothstep
+                        }
+                    }
+                    Self::Mask(mask) => {
+                        let (mx, my) = (x * mask.w / size, y * mask.h / size);
+                        mask.get(mx, my).a as f32 / 255.0
+                    }
+                };
+                pixels.set(x, y, Color::new(255, 255, 255, (coverage * 255.0).round() as u8));
+            }
+        }
+        pixels
+    }
+}
+
 pub struct BrushPreset {
     pub size: NonZeroU16,
     pub color: Color,
     pub blend: BlendModeA,
+    pub tip: BrushTip,
+    /// How sharp the edge of [`BrushTip::Round`] and [`BrushTip::Square`] is, from 0 (soft) to 1 (hard).
+    pub hardness: f32,
+    /// Distance between dabs as a fraction of [`Self::size`].
+    pub spacing: f32,
 }
 
 impl BrushPreset {
+    pub const DEFAULT_SPACING: f32 = 0.1;
+
     pub const fn new(size: NonZeroU16, color: Color) -> Self {
-        Self {
-            size,
-            color,
-            blend: BlendModeA::Alpha,
-        }
+        Self::with_blend_mode(size, color, BlendModeA::Alpha)
     }
 
     pub const fn with_blend_mode(size: NonZeroU16, color: Color, blend: BlendModeA) -> Self {
@@ -102,32 +209,118 @@ impl BrushPreset {
             size,
             color,
             blend,
+            tip: BrushTip::Round,
+            hardness: 1.0,
+            spacing: Self::DEFAULT_SPACING,
         }
     }
+
+    pub fn with_tip(mut self, tip: BrushTip, hardness: f32, spacing: f32) -> Self {
+        self.tip = tip;
+        self.hardness = hardness;
+        self.spacing = spacing;
+        self
+    }
+
+    /// Erases to transparency instead of painting a color.
+    pub const fn eraser(size: NonZeroU16) -> Self {
+        Self::with_blend_mode(size, Color::WHITE, BlendModeA::ERASE)
+    }
+
+    #[inline]
+    pub fn is_eraser(&self) -> bool {
+        self.blend == BlendModeA::ERASE
+    }
 }
 
 pub trait BrushPresetDraw: RaylibDraw {
-    fn draw_line_brush(&mut self, preset: &BrushPreset, p1: Vector2, p2: Vector2) {
-        let thick = preset.size.get() as f32;
-        let radius = thick * 0.5;
-        let snapped_pos_prev = Vector2 {
-            x: ((p1.x - radius).round() + radius),
-            y: ((p1.y - radius).round() + radius),
-        };
-        let snapped_pos = Vector2 {
-            x: ((p2.x - radius).round() + radius),
-            y: ((p2.y - radius).round() + radius),
+    /// Stamp the tip once, centered on `pos`.
+    fn draw_dab(&mut self, preset: &BrushPreset, tip: &Texture2D, pos: Vector2) {
+        let size = preset.size.get() as f32;
+        let radius = size * 0.5;
+        let (mut x, mut y) = (pos.x - radius, pos.y - radius);
+        if preset.hardness >= 1.0 {
+            // keep hard tips on the pixel grid so they stay crisp
+            (x, y) = (x.round(), y.round());
+        }
+        self.draw_texture_pro(
+            tip,
+            Rectangle::new(0.0, 0.0, tip.width as f32, tip.height as f32),
+            Rectangle::new(x, y, size, size),
+            Vector2::zero(),
+            0.0,
+            preset.color,
+        );
+    }
+
+    /// Stamp dabs from `p1` to `p2` at the preset's spacing.
+    ///
+    /// Spacing carries over between calls, so a stroke drawn over several frames is evenly spaced.
+    fn draw_dabs(&mut self, stroke: &mut BrushStroke, p1: Vector2, p2: Vector2) {
+        let Some(tip) = stroke.tip else { return };
+        let preset = stroke.preset;
+        let spacing = (preset.spacing * preset.size.get() as f32).max(BrushStroke::MIN_SPACING);
+
+        let mut since_last_dab = match *stroke.since_last_dab {
+            Some(dist) => dist,
+            None => {
+                self.draw_dab(preset, tip, p1);
+                0.0
+            }
         };
-        self.draw_line_ex(snapped_pos_prev, snapped_pos, thick, preset.color);
-        self.draw_circle_v(snapped_pos_prev, radius, preset.color);
-        self.draw_circle_v(snapped_pos, radius, preset.color);
+
+        let delta = p2 - p1;
+        let len = delta.length();
+        if len > 0.0 {
+            let dir = delta / len;
+            let mut dist = spacing - since_last_dab;
+            while dist <= len {
+                self.draw_dab(preset, tip, p1 + dir * dist);
+                dist += spacing;
+            }
+            since_last_dab = len - (dist - spacing);
+        }
+        *stroke.since_last_dab = Some(since_last_dab);
+    }
+
+    /// Stamp dabs along every segment of a path, evenly spaced as if it were drawn in one stroke.
+    fn draw_dab_path(&mut self, stroke: &mut BrushStroke, points: &[Vector2]) {
+        match points {
+            [] => {}
+            [point] => self.draw_dabs(stroke, *point, *point),
+            _ => for pair in points.windows(2) {
+                self.draw_dabs(stroke, pair[0], pair[1]);
+            }
+        }
     }
 }
 impl<T: RaylibDraw> BrushPresetDraw for T {}
 
+/// The parts of a [`Brush`] needed to stamp dabs.
+pub struct BrushStroke<'a> {
+    pub preset: &'a BrushPreset,
+    tip: Option<&'a Texture2D>,
+    since_last_dab: &'a mut Option<f32>,
+}
+
+impl BrushStroke<'_> {
+    /// Lower bound on dab spacing in pixels, so tiny brushes don't stamp hundreds of times per pixel.
+    pub const MIN_SPACING: f32 = 0.5;
+}
+
+/// Preset values that the tip texture was generated from.
+struct TipKey {
+    size: NonZeroU16,
+    hardness: f32,
+    tip: BrushTip,
+}
+
 pub struct Brush {
     pub preset: BrushPreset,
     target: Option<RcRaster>,
+    tip: Option<(TipKey, Texture2D)>,
+    /// Distance travelled since the last dab, or [`None`] if the stroke hasn't stamped anything yet.
+    since_last_dab: Option<f32>,
 }
 
 impl Brush {
@@ -135,6 +328,8 @@ impl Brush {
         Self {
             preset,
             target: None,
+            tip: None,
+            since_last_dab: None,
         }
     }
 
@@ -142,9 +337,66 @@ impl Brush {
         Self {
             preset,
             target: Some(target),
+            tip: None,
+            since_last_dab: None,
+        }
+    }
+
+    /// Regenerate the tip texture if the preset's tip, size or hardness changed since last time.
+    pub fn update_tip(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) {
+        let BrushPreset { size, hardness, tip, .. } = &self.preset;
+        if self.tip.as_ref().is_some_and(|(key, _)| key.size == *size && key.hardness == *hardness && key.tip == *tip) {
+            return;
+        }
+        let pixels = tip.to_pixels(size.get().into(), *hardness);
+        let texture = rl.load_texture_from_image(thread, &pixels.to_image()).unwrap();
+        let filter = if *hardness >= 1.0 && !matches!(tip, BrushTip::Mask(_)) {
+            TextureFilter::TEXTURE_FILTER_POINT
+        } else {
+            TextureFilter::TEXTURE_FILTER_BILINEAR
+        };
+        unsafe { ffi::SetTextureFilter(*texture, filter as i32); }
+        self.tip = Some((TipKey { size: *size, hardness: *hardness, tip: tip.clone() }, texture));
+    }
+
+    /// The tip texture from the last [`Self::update_tip`].
+    #[inline]
+    pub fn tip(&self) -> Option<&Texture2D> {
+        self.tip.as_ref().map(|(_, texture)| texture)
+    }
+
+    /// A stroke with this brush that keeps its spacing in `since_last_dab` instead of the brush,
+    /// so that previews can be drawn without disturbing a stroke in progress.
+    #[inline]
+    pub fn preview_stroke<'a>(&'a self, since_last_dab: &'a mut Option<f32>) -> BrushStroke<'a> {
+        BrushStroke {
+            preset: &self.preset,
+            tip: self.tip(),
+            since_last_dab,
         }
     }
 
+    /// The stroke in progress, which keeps its spacing in the brush.
+    #[inline]
+    pub fn stroke(&mut self) -> BrushStroke<'_> {
+        BrushStroke {
+            preset: &self.preset,
+            tip: self.tip.as_ref().map(|(_, texture)| texture),
+            since_last_dab: &mut self.since_last_dab,
+        }
+    }
+
+    /// Start a new stroke, so that the first dab lands exactly where it starts.
+    #[inline]
+    pub fn begin_stroke(&mut self) {
+        self.since_last_dab = None;
+    }
+
+    #[inline]
+    pub fn target(&self) -> Option<&RcRaster> {
+        self.target.as_ref()
+    }
+
     #[inline]
     pub fn set_target(&mut self, target: RcRaster) -> Option<RcRaster> {
         self.target.replace(target)
@@ -156,6 +408,105 @@ impl Brush {
     }
 }
 
+/// Collects the dabs of one stroke apart from the brush target, so that where dabs overlap they don't build up.
+///
+/// Dabs are layered with [`BlendModeA::MAX_ALPHA`], and the whole stroke is blended onto the target as it was before
+/// the stroke began, once per paint. A semi-transparent color then paints at its own alpha no matter the spacing.
+///
+/// Blend factors can't keep color straight where the target is transparent, so [`BlendModeA::Alpha`] strokes are
+/// blended by [`stroke_over_shader`] instead. Other blend modes use their factors as they are.
+pub struct StrokeBuffer {
+    before: Texture2D,
+    dabs: RenderTexture2D,
+}
+
+impl StrokeBuffer {
+    /// Start a stroke on a target that holds `before`.
+    ///
+    /// Returns [`None`] if the buffers couldn't be created on the GPU.
+    pub fn begin(rl: &mut RaylibHandle, thread: &RaylibThread, before: &Pixels) -> Option<Self> {
+        let before_texture = rl.load_texture_from_image(thread, &before.to_image()).ok()?;
+        let mut dabs = rl.load_render_texture(thread, before.w, before.h).ok()?;
+        rl.begin_texture_mode(thread, &mut dabs).clear_background(Color::BLANK);
+        Some(Self { before: before_texture, dabs })
+    }
+
+    /// Stamp dabs from `p1` to `p2` into the stroke and update the brush target.
+    pub fn paint_dabs(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, brush: &mut Brush, p1: Vector2, p2: Vector2) {
+        {
+            let mut d = rl.begin_texture_mode(thread, &mut self.dabs);
+            let mut d = d.begin_blend_mode_a(BlendModeA::MAX_ALPHA);
+            d.draw_dabs(&mut brush.stroke(), p1, p2);
+        }
+        self.composite(rl, thread, brush);
+    }
+
+    /// Stamp dabs along a path into the stroke and update the brush target.
+    pub fn paint_dab_path(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, brush: &mut Brush, points: &[Vector2]) {
+        {
+            let mut d = rl.begin_texture_mode(thread, &mut self.dabs);
+            let mut d = d.begin_blend_mode_a(BlendModeA::MAX_ALPHA);
+            d.draw_dab_path(&mut brush.stroke(), points);
+        }
+        self.composite(rl, thread, brush);
+    }
+
+    /// Redraw the brush target as it was before the stroke, with the stroke so far blended on top.
+    fn composite(&self, rl: &mut RaylibHandle, thread: &RaylibThread, brush: &Brush) {
+        let Some(target) = brush.target() else { return };
+        let mut target = target.borrow_mut();
+        let (w, h) = (self.before.width as f32, self.before.height as f32);
+        // render textures are stored upside down
+        let dabs_rec = Rectangle::new(0.0, 0.0, w, -h);
+        let mut d = rl.begin_texture_mode(thread, &mut *target);
+        if brush.preset.blend == BlendModeA::Alpha {
+            let shader = stroke_over_shader();
+            let mut d = d.begin_blend_mode_a(BlendModeA::REPLACE);
+            // the texture has to be bound after the shader starts, which flushes the batch and unbinds extra textures.
+            // ends before the blend mode, which flushes the batch while the shader is still active
+            unsafe {
+                ffi::BeginShaderMode(shader);
+                ffi::SetShaderValueTexture(shader, ffi::GetShaderLocation(shader, c"before".as_ptr()), *self.before);
+            }
+            d.draw_texture_rec(&self.dabs, dabs_rec, Vector2::zero(), Color::WHITE);
+            unsafe { ffi::EndShaderMode(); }
+        } else {
+            {
+                let mut d = d.begin_blend_mode_a(BlendModeA::REPLACE);
+                d.draw_texture(&self.before, 0, 0, Color::WHITE);
+            }
+            let mut d = d.begin_blend_mode_a(brush.preset.blend);
+            d.draw_texture_rec(&self.dabs, dabs_rec, Vector2::zero(), Color::WHITE);
+        }
+    }
+}
+
+thread_local! {
+    static STROKE_OVER_SHADER: std::cell::OnceCell<ffi::Shader> = const { std::cell::OnceCell::new() };
+}
+
+/// Shader that outputs the stroke drawn over the `before` texture, both with straight color, for [`StrokeBuffer`].
+///
+/// `before` is an ordinary texture, so it is sampled with its rows flipped to line up with the stroke's render texture.
+/// Loaded the first time it is needed and kept until the program exits.
+fn stroke_over_shader() -> ffi::Shader {
+    const FRAG: &std::ffi::CStr = c"#version 330
+in vec2 fragTexCoord;
+in vec4 fragColor;
+uniform sampler2D texture0;
+uniform sampler2D before;
+uniform vec4 colDiffuse;
+out vec4 finalColor;
+void main() {
+    vec4 src = texture(texture0, fragTexCoord) * colDiffuse * fragColor;
+    vec4 dst = texture(before, vec2(fragTexCoord.x, 1.0 - fragTexCoord.y));
+    float a = src.a + dst.a * (1.0 - src.a);
+    vec3 rgb = src.rgb * src.a + dst.rgb * dst.a * (1.0 - src.a);
+    finalColor = vec4(a > 0.0 ? rgb / a : vec3(0.0), a);
+}";
+    STROKE_OVER_SHADER.with(|shader| *shader.get_or_init(|| unsafe { ffi::LoadShaderFromMemory(std::ptr::null(), FRAG.as_ptr()) }))
+}
+
 pub struct BrushTargetMode<'a, 'b, T>(&'a mut T, std::cell::RefMut<'b, RenderTexture2D>);
 
 impl<'a, 'b, T> Drop for BrushTargetMode<'a, 'b, T> {
@@ -166,17 +517,50 @@ impl<'a, 'b, T> Drop for BrushTargetMode<'a, 'b, T> {
 
 pub trait BrushTargetModeExt: RaylibTextureModeExt {
     #[must_use]
-    fn begin_brush_target_mode<'a, 'b>(&'a mut self, thread: &RaylibThread, brush: &'b mut Brush) -> Option<(BrushTargetMode<'a, 'b, Self>, &'b BrushPreset)> {
-        if let Some(target_rc) = &mut brush.target {
+    fn begin_brush_target_mode<'a, 'b>(&'a mut self, thread: &RaylibThread, brush: &'b mut Brush) -> Option<(BrushTargetMode<'a, 'b, Self>, BrushStroke<'b>)> {
+        let Brush { preset, target, tip, since_last_dab } = brush;
+        if let Some(target_rc) = target {
             let target_borrow = target_rc.borrow_mut();
             // ends in [`BrushTargetMode::drop`]
             // uses ffi because the texture can't be passed as a mutable reference, it is stored in an Rc
             // the deref is local and the mutable reference would be dropped when this function closes
             unsafe { ffi::BeginTextureMode(**target_borrow); }
-            Some((BrushTargetMode(self, target_borrow), &brush.preset))
+            let stroke = BrushStroke {
+                preset,
+                tip: tip.as_ref().map(|(_, texture)| texture),
+                since_last_dab,
+            };
+            Some((BrushTargetMode(self, target_borrow), stroke))
         } else { None }
     }
 }
 
 impl<T: RaylibTextureModeExt> BrushTargetModeExt for T {}
 impl<'a, 'b, T> RaylibDraw for BrushTargetMode<'a, 'b, T> {}
+
+#[cfg(test)]
+mod brush_tests {
+    use std::cell::RefCell;
+    use crate::{raster, test_support::with_window};
+    use super::*;
+
+    #[test]
+    #[ignore = "needs a display to draw on the GPU"]
+    fn test_stroke_on_transparent_raster() {
+        with_window(|rl, thread| {
+            let before = Pixels::new(16, 16, Color::BLANK);
+            let mut rtex = rl.load_render_texture(thread, 16, 16).unwrap();
+            raster::write_pixels(&mut rtex, &before);
+            let preset = BrushPreset::new(NonZeroU16::new(4).unwrap(), Color::new(255, 0, 0, 128));
+            let mut brush = Brush::with_target(preset, RcRaster::new(RefCell::new(rtex)));
+            brush.update_tip(rl, thread);
+
+            // dabs overlap along the line, but the color stays straight and at its own alpha
+            let mut buffer = StrokeBuffer::begin(rl, thread, &before).unwrap();
+            buffer.paint_dabs(rl, thread, &mut brush, Vector2::new(6.0, 8.0), Vector2::new(10.0, 8.0));
+            let pixels = raster::read_pixels(&brush.target().unwrap().borrow());
+            assert_eq!(pixels.get(8, 8), Color::new(255, 0, 0, 128));
+            assert_eq!(pixels.get(0, 0), Color::BLANK);
+        });
+    }
+}
@@ -0,0 +1,402 @@
+use std::{fs::File, io::{self, BufReader, BufWriter, Write}, path::Path};
+use raylib::prelude::*;
+use crate::{brush::BrushPreset, file::native, fill::{self, FillOptions}, history::{Edit, History}, layer::{path_after_removal, Canvas, EffectTable, Layer, LayerBlend, LayerContent, LayerPath, LayerTree, RasterTable}, raster::{self, Pixels, RcRaster}, selection::{Mask, Selection, SelectionOp}};
+
+/// Everything that makes up one piece of artwork.
+pub struct Document {
+    pub rasters: RasterTable,
+    pub effects: EffectTable,
+    pub layers: LayerTree,
+    pub history: History,
+    /// The layer selected in the layers panel, which the brush paints on.
+    pub active_layer: Option<LayerPath>,
+    /// The pixels that painting is confined to. [`None`] when nothing is selected, which leaves the whole canvas paintable.
+    pub selection: Option<Selection>,
+}
+
+impl Document {
+    pub const fn new(canvas: Canvas) -> Self {
+        Self {
+            rasters: RasterTable::new(canvas),
+            effects: EffectTable::new(),
+            layers: LayerTree::new(),
+            history: History::new(),
+            active_layer: None,
+            selection: None,
+        }
+    }
+
+    /// Whether the document is still how it started: a single raster layer and nothing to undo or redo.
+    /// Such a document can be replaced or resized without losing any work.
+    pub fn is_untouched(&self) -> bool {
+        let mut layers = self.layers.layers().into_iter();
+        !self.history.can_undo() && !self.history.can_redo()
+            && layers.next().is_some_and(|layer| matches!(layer.content, LayerContent::Raster { .. }))
+            && layers.next().is_none()
+    }
+
+    /// The raster of the active layer, if the active layer is a raster layer.
+    pub fn active_raster(&self) -> Option<RcRaster> {
+        match &self.layers.get(self.active_layer.as_deref()?)?.content {
+            LayerContent::Raster { artwork } => artwork.upgrade(),
+            LayerContent::Group { .. } => None,
+        }
+    }
+
+    /// Insert a layer at `path` as an undoable step.
+    pub fn insert_layer(&mut self, path: &[usize], layer: Layer) -> Result<(), Layer> {
+        self.layers.insert(path, layer)?;
+        self.history.push(Edit::InsertLayer { path: path.to_vec(), layer: None });
+        Ok(())
+    }
+
+    /// Remove the layer at `path` as an undoable step.
+    ///
+    /// The layer is kept in the history rather than returned, so its raster stays alive for redo.
+    pub fn remove_layer(&mut self, path: &[usize]) -> bool {
+        if let Some(layer) = self.layers.remove(path) {
+            self.history.push(Edit::RemoveLayer { path: path.to_vec(), layer: Some(layer) });
+            true
+        } else { false }
+    }
+
+    /// Move the layer at `from` to `to` as an undoable step, where `to` is a path in the tree after it was taken out.
+    pub fn move_layer(&mut self, from: &[usize], to: &[usize]) -> bool {
+        if self.layers.move_layer(from, to) {
+            self.history.push(Edit::MoveLayer { from: from.to_vec(), to: to.to_vec() });
+            true
+        } else { false }
+    }
+
+    /// Show or hide the layer at `path` as an undoable step.
+    pub fn toggle_visible(&mut self, path: &[usize]) -> bool {
+        let Some(layer) = self.layers.get_mut(path) else { return false };
+        layer.visible = !layer.visible;
+        self.history.push(Edit::ToggleVisible { path: path.to_vec() });
+        true
+    }
+
+    /// Swap the layer with the one above it. Returns the layer's new path.
+    pub fn move_layer_up(&mut self, path: &[usize]) -> Option<LayerPath> {
+        let (&last, parent) = path.split_last()?;
+        if last + 1 >= self.layers.siblings(path)?.len() { return None; }
+        let to = [parent, &[last + 1]].concat();
+        self.move_layer(path, &to).then_some(to)
+    }
+
+    /// Swap the layer with the one below it. Returns the layer's new path.
+    pub fn move_layer_down(&mut self, path: &[usize]) -> Option<LayerPath> {
+        let (&last, parent) = path.split_last()?;
+        let to = [parent, &[last.checked_sub(1)?]].concat();
+        self.move_layer(path, &to).then_some(to)
+    }
+
+    /// Move the layer to the top of the group at `group`. Returns the layer's new path.
+    pub fn move_into_group(&mut self, path: &[usize], group: &[usize]) -> Option<LayerPath> {
+        let group = path_after_removal(path, group)?;
+        let LayerContent::Group { children, .. } = &self.layers.get(&group)?.content else { return None };
+        let to = [group.as_slice(), &[children.len()]].concat();
+        self.move_layer(path, &to).then_some(to)
+    }
+
+    /// Replace the group at `path` with its children, as one undoable step.
+    pub fn ungroup(&mut self, path: &[usize]) -> bool {
+        let Some((&last, parent)) = path.split_last() else { return false };
+        let Some(Layer { content: LayerContent::Group { children, .. }, .. }) = self.layers.get(path) else { return false };
+        // each child goes in front of the group, pushing the group back by one
+        let mut edits = (0..children.len())
+            .map(|i| Edit::MoveLayer {
+                from: [parent, &[last + i, 0]].concat(),
+                to: [parent, &[last + i]].concat(),
+            })
+            .collect::<Vec<_>>();
+        edits.push(Edit::RemoveLayer { path: [parent, &[last + children.len()]].concat(), layer: None });
+        self.history.apply(Edit::Batch(edits), &mut self.layers);
+        true
+    }
+
+    /// Insert a copy of the layer above it as an undoable step. Returns the path of the copy.
+    pub fn duplicate_layer(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, path: &[usize]) -> Option<LayerPath> {
+        let (&last, parent) = path.split_last()?;
+        let copy = self.layers.get(path)?.duplicate(rl, thread, &mut self.rasters);
+        let to = [parent, &[last + 1]].concat();
+        self.insert_layer(&to, copy).ok().map(|()| to)
+    }
+
+    /// Combine the layer with the one below it into a single raster, baking in both layers' effects.
+    ///
+    /// The lower layer's opacity is baked in too, since the upper layer was drawn over the lower one at that opacity.
+    /// The result keeps the lower layer's name, visibility and blend mode, at full opacity. Returns its path.
+    pub fn merge_down(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, path: &[usize]) -> Option<LayerPath> {
+        let (&last, parent) = path.split_last()?;
+        let below = [parent, &[last.checked_sub(1)?]].concat();
+        let canvas = *self.rasters.canvas();
+        for path in [&below[..], path] {
+            self.layers.get_mut(path)?.update_buffers(rl, thread, &canvas);
+        }
+        let (lower, upper) = (self.layers.get(&below)?, self.layers.get(path)?);
+
+        let mut rtex = canvas.load_buffer(rl, thread);
+        lower.composite_as(rl, thread, &canvas, &mut rtex, lower.opacity, LayerBlend::Normal);
+        upper.composite_onto(rl, thread, &canvas, &mut rtex);
+        let mut pixels = raster::read_pixels(&rtex);
+        pixels.unpremultiply();
+        let raster_rc = self.rasters.create_raster(rl, thread);
+        raster::write_pixels(&mut raster_rc.borrow_mut(), &pixels);
+
+        let mut merged = Layer::new(LayerContent::new_raster(raster_rc)).named(lower.name.clone());
+        merged.visible = lower.visible;
+        merged.blend = lower.blend;
+        self.history.apply(Edit::Batch(vec![
+            Edit::RemoveLayer { path: path.to_vec(), layer: None },
+            Edit::RemoveLayer { path: below.clone(), layer: None },
+            Edit::InsertLayer { path: below.clone(), layer: Some(merged) },
+        ]), &mut self.layers);
+        Some(below)
+    }
+
+    /// Replace every layer with a single raster of the composite, as one undoable step.
+    pub fn flatten_all(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) {
+        let canvas = *self.rasters.canvas();
+        let count = self.layers.layers().into_iter().count();
+        if count == 0 { return; }
+        let pixels = self.layers.flatten_pixels(rl, thread, &canvas);
+        let raster_rc = self.rasters.create_raster(rl, thread);
+        raster::write_pixels(&mut raster_rc.borrow_mut(), &pixels);
+
+        let mut edits = (0..count)
+            .map(|_| Edit::RemoveLayer { path: vec![0], layer: None })
+            .collect::<Vec<_>>();
+        edits.push(Edit::InsertLayer { path: vec![0], layer: Some(Layer::new(LayerContent::new_raster(raster_rc)).named("Background")) });
+        self.history.apply(Edit::Batch(edits), &mut self.layers);
+    }
+
+    /// The pixel under `pos`, or [`None`] if `pos` is off the canvas.
+    fn pixel_at(&self, pos: Vector2) -> Option<(u32, u32)> {
+        let canvas = self.rasters.canvas();
+        let (x, y) = (pos.x.floor(), pos.y.floor());
+        (x >= 0.0 && y >= 0.0 && x < f32::from(canvas.get_w()) && y < f32::from(canvas.get_h())).then_some((x as u32, y as u32))
+    }
+
+    /// Flood fill `raster` with the preset's color, starting from the pixel under `pos`, as an undoable step.
+    /// An eraser preset clears the region instead. Only selected pixels are filled.
+    ///
+    /// Returns false if `pos` is off the canvas or nothing changed.
+    pub fn fill(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, raster: &RcRaster, pos: Vector2, preset: &BrushPreset, options: &FillOptions) -> bool {
+        let Some((x, y)) = self.pixel_at(pos) else { return false };
+        let before = raster::read_pixels(&raster.borrow());
+        let mut region = if options.sample_merged {
+            let canvas = *self.rasters.canvas();
+            fill::region(&self.layers.flatten_pixels(rl, thread, &canvas), x, y, options.tolerance, options.mode)
+        } else {
+            fill::region(&before, x, y, options.tolerance, options.mode)
+        };
+        if let Some(selection) = &self.selection {
+            for (is_filled, &is_selected) in region.iter_mut().zip(&selection.mask().data) {
+                *is_filled &= is_selected;
+            }
+        }
+        let mut after = before.clone();
+        let Some((x, y, w, h)) = fill::paint_region(&mut after, &region, preset.color, preset.is_eraser()) else { return false };
+        let after = after.crop(x, y, w, h);
+        raster::write_pixels_at(&mut raster.borrow_mut(), x, y, &after);
+        self.history.push(Edit::Pixels {
+            raster: RcRaster::downgrade(raster),
+            x,
+            y,
+            before: before.crop(x, y, w, h),
+            after,
+        });
+        true
+    }
+
+    /// A copy of the pixels the eyedropper picks from: the active raster, or the composite of every layer if `sample_merged`.
+    ///
+    /// Returns [`None`] if there is nothing to sample.
+    pub fn sample_pixels(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, sample_merged: bool) -> Option<Pixels> {
+        if sample_merged {
+            let canvas = *self.rasters.canvas();
+            Some(self.layers.flatten_pixels(rl, thread, &canvas))
+        } else {
+            Some(raster::read_pixels(&self.active_raster()?.borrow()))
+        }
+    }
+
+    /// The color of the pixel under `pos` in `pixels`, which came from [`Self::sample_pixels`].
+    ///
+    /// Returns [`None`] if `pos` is off the canvas.
+    pub fn pick_color(&self, pixels: &Pixels, pos: Vector2) -> Option<Color> {
+        let (x, y) = self.pixel_at(pos)?;
+        Some(pixels.get(x, y))
+    }
+
+    /// Combine `mask` into the selection. Subtracting from or intersecting with nothing selected leaves nothing selected.
+    pub fn select(&mut self, mut mask: Mask, op: SelectionOp) {
+        if op != SelectionOp::Replace {
+            let Some(selection) = self.selection.take() else {
+                if op == SelectionOp::Add {
+                    self.selection = Selection::new(mask);
+                }
+                return;
+            };
+            let other = mask;
+            mask = selection.into_mask();
+            mask.combine(&other, op);
+        }
+        self.selection = Selection::new(mask);
+    }
+
+    /// Select the pixels similar to the one under `pos` in the active raster, the same ones [`Self::fill`] would fill.
+    ///
+    /// Returns false if `pos` is off the canvas or there is nothing to sample.
+    pub fn select_similar(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, pos: Vector2, options: &FillOptions, op: SelectionOp) -> bool {
+        let Some((x, y)) = self.pixel_at(pos) else { return false };
+        let canvas = *self.rasters.canvas();
+        let sample = if options.sample_merged {
+            self.layers.flatten_pixels(rl, thread, &canvas)
+        } else {
+            let Some(raster) = self.active_raster() else { return false };
+            raster::read_pixels(&raster.borrow())
+        };
+        let region = fill::region(&sample, x, y, options.tolerance, options.mode);
+        self.select(Mask::from_region(canvas.get_w().into(), canvas.get_h().into(), region), op);
+        true
+    }
+
+    pub fn select_all(&mut self) {
+        let canvas = self.rasters.canvas();
+        self.selection = Selection::new(Mask::new(canvas.get_w().into(), canvas.get_h().into(), true));
+    }
+
+    #[inline]
+    pub fn deselect(&mut self) {
+        self.selection = None;
+    }
+
+    /// Select everything that isn't selected. Inverting an empty selection selects everything.
+    pub fn invert_selection(&mut self) {
+        match self.selection.take() {
+            Some(selection) => {
+                let mut mask = selection.into_mask();
+                mask.invert();
+                self.selection = Selection::new(mask);
+            }
+            None => self.select_all(),
+        }
+    }
+
+    /// The active layer stays on the same layer, or moves to the topmost raster if undoing took that layer out.
+    pub fn undo(&mut self) -> bool {
+        let is_undone = self.history.undo(&mut self.layers, &mut self.active_layer);
+        if self.active_layer.is_none() {
+            self.active_layer = self.layers.topmost_raster();
+        }
+        is_undone
+    }
+
+    /// The active layer stays on the same layer, or moves to the topmost raster if redoing took that layer out.
+    pub fn redo(&mut self) -> bool {
+        let is_redone = self.history.redo(&mut self.layers, &mut self.active_layer);
+        if self.active_layer.is_none() {
+            self.active_layer = self.layers.topmost_raster();
+        }
+        is_redone
+    }
+
+    /// Save in the native project format.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        native::write(&mut w, self)?;
+        w.flush()
+    }
+
+    /// Load a file saved by [`Self::save`].
+    pub fn load(rl: &mut RaylibHandle, thread: &RaylibThread, path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut r = BufReader::new(File::open(path)?);
+        native::read(rl, thread, &mut r)
+    }
+}
+
+#[cfg(test)]
+mod document_tests {
+    use crate::test_support::*;
+    use super::*;
+
+    /// `a G(b c) d`
+    fn document() -> Document {
+        let mut doc = Document::new(const { unsafe { Canvas::new_unchecked(4, 4) } });
+        doc.layers = tree();
+        doc
+    }
+
+    #[test]
+    fn test_ungroup_keeps_order() {
+        let mut doc = document();
+        assert!(doc.ungroup(&[1]));
+        assert_eq!(describe(doc.layers.layers()), "a b c d");
+        assert!(!doc.ungroup(&[0]), "not a group");
+        assert!(!doc.ungroup(&[4]));
+    }
+
+    #[test]
+    fn test_ungroup_undoes_as_one_step() {
+        let mut doc = document();
+        assert!(doc.ungroup(&[1]));
+        doc.active_layer = Some(vec![2]);
+
+        assert!(doc.undo());
+        assert_eq!(describe(doc.layers.layers()), "a G(b c) d");
+        assert!(!doc.history.can_undo());
+        assert_eq!(doc.active_layer, Some(vec![1, 1]), "the active layer should follow `c` back into the group");
+
+        assert!(doc.redo());
+        assert_eq!(describe(doc.layers.layers()), "a b c d");
+        assert_eq!(doc.active_layer, Some(vec![2]));
+    }
+
+    #[test]
+    fn test_undo_reverts_layer_edits_in_order() {
+        let mut doc = document();
+        assert!(doc.insert_layer(&[3], raster("e")).is_ok());
+        assert!(doc.move_layer(&[0], &[0, 2]));
+        assert!(doc.remove_layer(&[1]));
+        assert_eq!(describe(doc.layers.layers()), "G(b c a) e");
+
+        assert!(doc.undo() && doc.undo());
+        assert_eq!(describe(doc.layers.layers()), "a G(b c) d e");
+        assert!(doc.undo());
+        assert_eq!(describe(doc.layers.layers()), "a G(b c) d");
+        assert!(!doc.undo());
+
+        assert!(doc.redo() && doc.redo() && doc.redo());
+        assert_eq!(describe(doc.layers.layers()), "G(b c a) e");
+    }
+
+    #[test]
+    #[ignore = "needs a display to draw on the GPU"]
+    fn test_merge_down_bakes_lower_opacity() {
+        const BLUE: Color = Color::new(0, 0, 255, 255);
+        with_window(|rl, thread| {
+            let mut doc = Document::new(const { unsafe { Canvas::new_unchecked(4, 4) } });
+            let mut upper_pixels = Pixels::new(4, 4, Color::BLANK);
+            upper_pixels.set(0, 0, BLUE);
+            for (name, pixels, opacity) in [("lower", Pixels::new(4, 4, Color::RED), 0.5), ("upper", upper_pixels, 1.0)] {
+                let raster_rc = doc.rasters.create_raster(rl, thread);
+                raster::write_pixels(&mut raster_rc.borrow_mut(), &pixels);
+                let mut layer = Layer::new(LayerContent::new_raster(raster_rc)).named(name);
+                layer.opacity = opacity;
+                doc.layers.push(layer);
+            }
+
+            let path = doc.merge_down(rl, thread, &[1]).unwrap();
+            let merged = doc.layers.get(&path).unwrap();
+            assert_eq!(merged.name, "lower");
+            assert_eq!(merged.opacity, 1.0);
+            let LayerContent::Raster { artwork } = &merged.content else { panic!("merging should make a raster") };
+            let pixels = raster::read_pixels(&artwork.upgrade().unwrap().borrow());
+            // the upper layer is as opaque as it was, and the lower one is as faint
+            assert_eq!(pixels.get(0, 0), BLUE);
+            assert_eq!(pixels.get(1, 1), Color::new(255, 0, 0, 128));
+        });
+    }
+}
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, rc::{Rc, Weak}};
+use std::{cell::RefCell, ffi::{CStr, CString}, rc::{Rc, Weak}};
 use ide::EffectCode;
 use raylib::prelude::*;
 
@@ -7,6 +7,8 @@ pub mod ide;
 
 pub struct Effect {
     shader: Shader,
+    vert_src: Option<CString>,
+    frag_src: Option<CString>,
     vert_code: Option<EffectCode>,
     frag_code: Option<EffectCode>,
 }
@@ -15,11 +17,39 @@ pub type RcEffect = Rc<RefCell<Effect>>;
 pub type WeakEffect = Weak<RefCell<Effect>>;
 
 impl Effect {
+    /// Compile an effect from GLSL source. [`None`] uses raylib's default shader for that stage.
+    pub fn from_source(rl: &mut RaylibHandle, thread: &RaylibThread, vert_src: Option<CString>, frag_src: Option<CString>) -> Self {
+        _ = (rl, thread); // shader loading requires the window to be open
+        let shader = unsafe {
+            Shader::from_raw(ffi::LoadShaderFromMemory(
+                vert_src.as_ref().map_or(std::ptr::null(), |src| src.as_ptr()),
+                frag_src.as_ref().map_or(std::ptr::null(), |src| src.as_ptr()),
+            ))
+        };
+        Self {
+            shader,
+            vert_src,
+            frag_src,
+            vert_code: None,
+            frag_code: None,
+        }
+    }
+
     #[inline]
     pub fn shader(&self) -> &Shader {
         &self.shader
     }
 
+    #[inline]
+    pub fn vert_src(&self) -> Option<&CStr> {
+        self.vert_src.as_deref()
+    }
+
+    #[inline]
+    pub fn frag_src(&self) -> Option<&CStr> {
+        self.frag_src.as_deref()
+    }
+
     #[inline]
     pub fn begin_shader_mode<'a, D: RaylibShaderModeExt>(&'a mut self, d: &'a mut D) -> RaylibShaderMode<'a, D> {
         d.begin_shader_mode(&mut self.shader)
@@ -0,0 +1,143 @@
+//! Plain image files, for handing artwork to other programs.
+
+use std::{ffi::CString, io, num::NonZeroU16, path::{Path, PathBuf}};
+use raylib::prelude::*;
+use crate::{document::Document, layer::{Layer, LayerContent}, raster::{self, Pixels, RcRaster}};
+use super::invalid_data;
+
+fn path_cstr(path: &Path) -> io::Result<CString> {
+    path.to_str()
+        .and_then(|path| CString::new(path).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path is not valid UTF-8"))
+}
+
+/// Write pixels to disk. The format is chosen from the file extension.
+pub fn export_pixels(pixels: &Pixels, path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path_cstr(path.as_ref())?;
+    let image = pixels.to_image();
+    if unsafe { ffi::ExportImage(*image, path.as_ptr()) } {
+        Ok(())
+    } else {
+        Err(invalid_data("unsupported image format"))
+    }
+}
+
+/// Encode pixels as a PNG in memory.
+pub fn encode_png(pixels: &Pixels) -> io::Result<Vec<u8>> {
+    let image = pixels.to_image();
+    let mut size = 0;
+    unsafe {
+        let data = ffi::ExportImageToMemory(*image, c".png".as_ptr(), &mut size);
+        if data.is_null() {
+            return Err(invalid_data("failed to encode PNG"));
+        }
+        let bytes = std::slice::from_raw_parts(data, size as usize).to_vec();
+        ffi::MemFree(data.cast());
+        Ok(bytes)
+    }
+}
+
+/// Decode a PNG from memory.
+pub fn decode_png(bytes: &[u8]) -> io::Result<Pixels> {
+    let size = i32::try_from(bytes.len()).map_err(|_| invalid_data("PNG is too large"))?;
+    let image = unsafe { Image::from_raw(ffi::LoadImageFromMemory(c".png".as_ptr(), bytes.as_ptr(), size)) };
+    if image.data.is_null() {
+        return Err(invalid_data("failed to decode PNG"));
+    }
+    Ok(Pixels::from_image(&image))
+}
+
+/// Export the whole layer tree flattened into one image, as it appears in the viewport.
+pub fn export_png(rl: &mut RaylibHandle, thread: &RaylibThread, doc: &mut Document, path: impl AsRef<Path>) -> io::Result<()> {
+    let canvas = *doc.rasters.canvas();
+    export_pixels(&doc.layers.flatten_pixels(rl, thread, &canvas), path)
+}
+
+fn export_layers_in(layers: &[&Layer], dir: &Path, prefix: &str, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for (i, layer) in layers.iter().enumerate() {
+        let name = format!("{prefix}{i}");
+        match &layer.content {
+            LayerContent::Raster { artwork } => {
+                if let Some(raster_rc) = artwork.upgrade() {
+                    let path = dir.join(format!("layer_{name}.png"));
+                    export_pixels(&raster::read_pixels(&raster_rc.borrow()), &path)?;
+                    paths.push(path);
+                }
+            }
+            LayerContent::Group { children, .. } => {
+                export_layers_in(&children.iter().collect::<Vec<_>>(), dir, &format!("{name}-"), paths)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Export each raster layer to its own PNG in `dir`, creating it if it doesn't exist.
+///
+/// Files are named after the layer's position in the tree, so the second child of the first layer is `layer_0-1.png`.
+/// Returns the paths that were written.
+pub fn export_layers_png(doc: &Document, dir: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir.as_ref())?;
+    let mut paths = Vec::new();
+    export_layers_in(&doc.layers.layers().into_iter().collect::<Vec<_>>(), dir.as_ref(), "", &mut paths)?;
+    Ok(paths)
+}
+
+/// File extensions that [`import_image`] accepts.
+pub const IMPORT_EXTENSIONS: [&str; 3] = ["png", "qoi", "bmp"];
+
+/// Read an image file from disk. The format is chosen from the file extension.
+pub fn import_pixels(path: impl AsRef<Path>) -> io::Result<Pixels> {
+    let path = path.as_ref();
+    let is_supported = path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMPORT_EXTENSIONS.iter().any(|supported| ext.eq_ignore_ascii_case(supported)));
+    if !is_supported {
+        return Err(invalid_data("unsupported image format"));
+    }
+    if !path.is_file() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "image file not found"));
+    }
+    let image = unsafe { Image::from_raw(ffi::LoadImage(path_cstr(path)?.as_ptr())) };
+    if image.data.is_null() {
+        return Err(invalid_data("failed to decode image"));
+    }
+    Ok(Pixels::from_image(&image))
+}
+
+/// Open an image file as a new raster layer on top of the layer tree, as an undoable step.
+///
+/// If `fit_canvas` is set, the canvas is resized to match the image first. The resize can't be undone and only
+/// covers rasters, so it is refused with [`io::ErrorKind::InvalidInput`] unless the document [is untouched](Document::is_untouched).
+/// Otherwise the image is placed in the top left corner and cropped to the canvas.
+/// Returns the new raster, and whether the image had to be cropped.
+pub fn import_image(rl: &mut RaylibHandle, thread: &RaylibThread, doc: &mut Document, path: impl AsRef<Path>, fit_canvas: bool) -> io::Result<(RcRaster, bool)> {
+    if fit_canvas && !doc.is_untouched() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "only an untouched canvas can be fit to an image"));
+    }
+    let mut pixels = import_pixels(path)?;
+
+    if fit_canvas {
+        let (w, h) = u16::try_from(pixels.w).ok().zip(u16::try_from(pixels.h).ok())
+            .and_then(|(w, h)| NonZeroU16::new(w).zip(NonZeroU16::new(h)))
+            .ok_or_else(|| invalid_data("image is too large for the canvas"))?;
+        doc.rasters.resize_canvas(rl, thread, w, h);
+        // the selection no longer lines up with the canvas
+        doc.deselect();
+    }
+
+    let canvas = doc.rasters.canvas();
+    let (canvas_w, canvas_h) = (u32::from(canvas.get_w()), u32::from(canvas.get_h()));
+    let is_cropped = pixels.w > canvas_w || pixels.h > canvas_h;
+    if is_cropped {
+        pixels = pixels.crop(0, 0, pixels.w.min(canvas_w), pixels.h.min(canvas_h));
+    }
+
+    let raster_rc = doc.rasters.create_raster(rl, thread).clone();
+    raster::write_pixels_at(&mut raster_rc.borrow_mut(), 0, 0, &pixels);
+    let top = doc.layers.layers().into_iter().count();
+    if doc.insert_layer(&[top], Layer::new(LayerContent::new_raster(&raster_rc))).is_err() {
+        panic!("there is always room on top of the layer tree");
+    }
+    Ok((raster_rc, is_cropped))
+}
@@ -0,0 +1,90 @@
+use std::io;
+
+/// The program's own project format.
+pub mod native;
+/// Single images such as PNG.
+pub mod image;
+/// OpenRaster, for exchanging layered files with other paint programs.
+pub mod ora;
+/// Minimal zip container used by [`ora`].
+pub mod zip;
+
+#[inline]
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Groups nested deeper than this are refused, so that a crafted file can't overflow the stack while it is read.
+const MAX_GROUP_DEPTH: usize = 256;
+
+/// Check a layer opacity read from a file, clamping it to 0..=1.
+fn read_opacity(opacity: f32) -> io::Result<f32> {
+    if opacity.is_finite() {
+        Ok(opacity.clamp(0.0, 1.0))
+    } else {
+        Err(invalid_data("layer opacity is not a number"))
+    }
+}
+
+/// Little-endian reader over an in-memory buffer.
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if n > self.0.len() {
+            return Err(invalid_data("unexpected end of data"));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// A `u32` length followed by that many bytes.
+    fn bytes(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
+
+trait PutBytes {
+    fn put_u8(&mut self, x: u8);
+    fn put_u16(&mut self, x: u16);
+    fn put_u32(&mut self, x: u32);
+    fn put_i32(&mut self, x: i32);
+    fn put_f32(&mut self, x: f32);
+    /// A `u32` length followed by the bytes.
+    fn put_bytes(&mut self, x: &[u8]);
+}
+
+impl PutBytes for Vec<u8> {
+    #[inline] fn put_u8 (&mut self, x: u8 ) { self.push(x); }
+    #[inline] fn put_u16(&mut self, x: u16) { self.extend_from_slice(&x.to_le_bytes()); }
+    #[inline] fn put_u32(&mut self, x: u32) { self.extend_from_slice(&x.to_le_bytes()); }
+    #[inline] fn put_i32(&mut self, x: i32) { self.extend_from_slice(&x.to_le_bytes()); }
+    #[inline] fn put_f32(&mut self, x: f32) { self.extend_from_slice(&x.to_le_bytes()); }
+
+    fn put_bytes(&mut self, x: &[u8]) {
+        self.put_u32(u32::try_from(x.len()).expect("chunk data should be smaller than 4GiB"));
+        self.extend_from_slice(x);
+    }
+}
@@ -0,0 +1,270 @@
+//! Chunked binary container.
+//!
+//! ```text
+//! magic    b"AMYRAST\0"
+//! version  u16
+//! chunk*   tag: [u8; 4], len: u32, data: [u8; len]
+//! ```
+//!
+//! All integers are little-endian. Chunks appear in the order `CNVS`, `RAST`*, `EFCT`*, `TREE`, `END\0`.
+//! Rasters and effects are numbered by the order of their chunks, and the layer tree refers to them by that number.
+//! Unknown chunks are skipped, so a new kind of chunk can be added without bumping [`VERSION`].
+//! Changing the layout of an existing chunk does need a bump, and files with a newer version are refused.
+
+use std::{ffi::CString, io::{self, Read, Write}, num::NonZeroU16};
+use raylib::prelude::*;
+use crate::{document::Document, effect::{Effect, RcEffect}, history::History, layer::{Canvas, EffectTable, Layer, LayerBlend, LayerContent, LayerTree, RasterTable}, raster::{self, Pixels}};
+use super::{invalid_data, read_opacity, Bytes, PutBytes, MAX_GROUP_DEPTH};
+
+const MAGIC: &[u8; 8] = b"AMYRAST\0";
+
+/// Increment whenever the layout of an existing chunk changes.
+///
+/// - 1: initial version
+/// - 2: layer names
+/// - 3: layer opacity, visibility and blend mode
+pub const VERSION: u16 = 3;
+
+const TAG_CANVAS: [u8; 4] = *b"CNVS";
+const TAG_RASTER: [u8; 4] = *b"RAST";
+const TAG_EFFECT: [u8; 4] = *b"EFCT";
+const TAG_TREE:   [u8; 4] = *b"TREE";
+const TAG_END:    [u8; 4] = *b"END\0";
+
+const KIND_RASTER: u8 = 0;
+const KIND_GROUP:  u8 = 1;
+
+const fn blend_id(blend: LayerBlend) -> u8 {
+    match blend {
+        LayerBlend::Normal   => 0,
+        LayerBlend::Multiply => 1,
+        LayerBlend::Screen   => 2,
+        LayerBlend::Add      => 3,
+        LayerBlend::Overlay  => 4,
+    }
+}
+
+fn blend_from_id(id: u8) -> io::Result<LayerBlend> {
+    LayerBlend::ALL.into_iter()
+        .find(|&blend| blend_id(blend) == id)
+        .ok_or_else(|| invalid_data("unknown layer blend mode"))
+}
+
+fn write_chunk<W: Write>(w: &mut W, tag: [u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&tag)?;
+    w.write_all(&u32::try_from(data.len()).expect("chunk should be smaller than 4GiB").to_le_bytes())?;
+    w.write_all(data)
+}
+
+fn write_layers(buf: &mut Vec<u8>, doc: &Document, layers: &[&Layer]) {
+    buf.put_u32(layers.len() as u32);
+    for layer in layers {
+        let effect = layer.effect.as_ref()
+            .and_then(|effect| effect.upgrade())
+            .and_then(|effect_rc| doc.effects.index_of(&effect_rc))
+            .map_or(-1, |i| i as i32);
+        buf.put_i32(effect);
+        buf.put_bytes(layer.name.as_bytes());
+        buf.put_f32(layer.opacity);
+        buf.put_u8(layer.visible as u8);
+        buf.put_u8(blend_id(layer.blend));
+
+        match &layer.content {
+            LayerContent::Raster { artwork } => {
+                let raster = artwork.upgrade()
+                    .and_then(|raster_rc| doc.rasters.index_of(&raster_rc))
+                    .map_or(-1, |i| i as i32);
+                buf.put_u8(KIND_RASTER);
+                buf.put_i32(raster);
+            }
+            LayerContent::Group { children, .. } => {
+                buf.put_u8(KIND_GROUP);
+                write_layers(buf, doc, &children.iter().collect::<Vec<_>>());
+            }
+        }
+    }
+}
+
+pub fn write<W: Write>(w: &mut W, doc: &Document) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    w.write_all(&VERSION.to_le_bytes())?;
+
+    let canvas = doc.rasters.canvas();
+    let mut buf = Vec::new();
+    buf.put_u16(canvas.get_w());
+    buf.put_u16(canvas.get_h());
+    write_chunk(w, TAG_CANVAS, &buf)?;
+
+    for raster_rc in doc.rasters.rasters() {
+        let pixels = raster::read_pixels(&raster_rc.borrow());
+        buf.clear();
+        buf.put_u32(pixels.w);
+        buf.put_u32(pixels.h);
+        buf.reserve(pixels.data.len() * 4);
+        for px in &pixels.data {
+            buf.extend_from_slice(&[px.r, px.g, px.b, px.a]);
+        }
+        write_chunk(w, TAG_RASTER, &buf)?;
+    }
+
+    for effect_rc in doc.effects.effects() {
+        let effect = effect_rc.borrow();
+        buf.clear();
+        for src in [effect.vert_src(), effect.frag_src()] {
+            buf.put_u8(src.is_some() as u8);
+            buf.put_bytes(src.map_or(&[], |src| src.to_bytes()));
+        }
+        write_chunk(w, TAG_EFFECT, &buf)?;
+    }
+
+    buf.clear();
+    write_layers(&mut buf, doc, &doc.layers.layers().into_iter().collect::<Vec<_>>());
+    write_chunk(w, TAG_TREE, &buf)?;
+
+    write_chunk(w, TAG_END, &[])
+}
+
+/// Read a list of layers that is nested `depth` groups below the root.
+fn read_layers(rl: &mut RaylibHandle, thread: &RaylibThread, version: u16, bytes: &mut Bytes, rasters: &RasterTable, effects: &[RcEffect], depth: usize) -> io::Result<Vec<Layer>> {
+    if depth > MAX_GROUP_DEPTH {
+        return Err(invalid_data("groups are nested too deeply"));
+    }
+    let len = bytes.u32()?;
+    let mut layers = Vec::new();
+    for _ in 0..len {
+        let effect = match bytes.i32()? {
+            -1 => None,
+            i => Some(effects.get(i as usize).ok_or_else(|| invalid_data("layer refers to a missing effect"))?),
+        };
+
+        let name = if version >= 2 {
+            String::from_utf8(bytes.bytes()?.to_vec()).map_err(|_| invalid_data("layer name is not valid UTF-8"))?
+        } else {
+            String::new()
+        };
+
+        let (opacity, visible, blend) = if version >= 3 {
+            (read_opacity(bytes.f32()?)?, bytes.u8()? != 0, blend_from_id(bytes.u8()?)?)
+        } else {
+            (1.0, true, LayerBlend::Normal)
+        };
+
+        let content = match bytes.u8()? {
+            KIND_RASTER => match bytes.i32()? {
+                -1 => LayerContent::empty_raster(),
+                i => LayerContent::new_raster(rasters.rasters().get(i as usize).ok_or_else(|| invalid_data("layer refers to a missing raster"))?),
+            },
+            KIND_GROUP => {
+                let buffer = rasters.canvas().load_buffer(rl, thread);
+                let children = read_layers(rl, thread, version, bytes, rasters, effects, depth + 1)?;
+                LayerContent::with_children(buffer, children)
+            }
+            _ => return Err(invalid_data("unknown layer kind")),
+        };
+
+        let mut layer = match effect {
+            Some(effect_rc) => Layer::with_effect(content, effect_rc),
+            None => Layer::new(content),
+        }.named(name);
+        layer.opacity = opacity;
+        layer.visible = visible;
+        layer.blend = blend;
+        layers.push(layer);
+    }
+    Ok(layers)
+}
+
+pub fn read<R: Read>(rl: &mut RaylibHandle, thread: &RaylibThread, r: &mut R) -> io::Result<Document> {
+    let mut magic = [0; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a project file"));
+    }
+
+    let mut version = [0; 2];
+    r.read_exact(&mut version)?;
+    let version = u16::from_le_bytes(version);
+    if version == 0 || version > VERSION {
+        return Err(invalid_data("project file was saved by a newer version"));
+    }
+
+    let mut rasters: Option<RasterTable> = None;
+    let mut effects = EffectTable::new();
+    let mut layers = LayerTree::new();
+
+    let mut data = Vec::new();
+    loop {
+        let mut header = [0; 8];
+        r.read_exact(&mut header)?;
+        let tag: [u8; 4] = header[..4].try_into().unwrap();
+        let len = u32::from_le_bytes(header[4..].try_into().unwrap());
+        // the length comes from the file, so only let the allocation grow as far as the data actually goes
+        data.clear();
+        r.by_ref().take(len.into()).read_to_end(&mut data)?;
+        if data.len() != len as usize {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let mut bytes = Bytes(&data);
+
+        match tag {
+            TAG_CANVAS => {
+                // a second canvas would throw away the rasters read into the first
+                if rasters.is_some() {
+                    return Err(invalid_data("more than one canvas"));
+                }
+                let w = NonZeroU16::new(bytes.u16()?).ok_or_else(|| invalid_data("canvas width is zero"))?;
+                let h = NonZeroU16::new(bytes.u16()?).ok_or_else(|| invalid_data("canvas height is zero"))?;
+                rasters = Some(RasterTable::new(Canvas::new(w, h)));
+            }
+
+            TAG_RASTER => {
+                let rasters = rasters.as_mut().ok_or_else(|| invalid_data("raster appears before canvas"))?;
+                let (w, h) = (bytes.u32()?, bytes.u32()?);
+                if w != rasters.canvas().get_w().into() || h != rasters.canvas().get_h().into() {
+                    return Err(invalid_data("raster size does not match canvas"));
+                }
+                let rgba = bytes.take(w as usize * h as usize * 4)?;
+                let pixels = Pixels {
+                    w,
+                    h,
+                    data: rgba.chunks_exact(4).map(|px| Color::new(px[0], px[1], px[2], px[3])).collect(),
+                };
+                let raster_rc = rasters.create_raster(rl, thread);
+                raster::write_pixels(&mut raster_rc.borrow_mut(), &pixels);
+            }
+
+            TAG_EFFECT => {
+                let mut src = [None, None];
+                for src in &mut src {
+                    let is_some = bytes.u8()? != 0;
+                    let code = bytes.bytes()?;
+                    if is_some {
+                        *src = Some(CString::new(code).map_err(|_| invalid_data("shader source contains a null byte"))?);
+                    }
+                }
+                let [vert_src, frag_src] = src;
+                effects.create_effect(Effect::from_source(rl, thread, vert_src, frag_src));
+            }
+
+            TAG_TREE => {
+                let rasters = rasters.as_ref().ok_or_else(|| invalid_data("layer tree appears before canvas"))?;
+                for layer in read_layers(rl, thread, version, &mut bytes, rasters, effects.effects(), 0)? {
+                    layers.push(layer);
+                }
+            }
+
+            TAG_END => break,
+
+            _ => {} // skip unknown chunks
+        }
+    }
+
+    Ok(Document {
+        rasters: rasters.ok_or_else(|| invalid_data("missing canvas"))?,
+        effects,
+        active_layer: layers.topmost_raster(),
+        layers,
+        history: History::new(),
+        selection: None,
+    })
+}
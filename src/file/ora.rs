@@ -0,0 +1,427 @@
+//! OpenRaster, the layered format shared with Krita, MyPaint and GIMP.
+//!
+//! An `.ora` file is a zip archive holding one PNG per layer and a `stack.xml` describing how they are stacked.
+//! `<stack>` maps to [`LayerContent::Group`] and `<layer>` maps to [`LayerContent::Raster`].
+//! OpenRaster lists layers topmost first, which is the reverse of [`LayerTree`].
+//!
+//! Effects have no OpenRaster equivalent and are not exported.
+//! Composite ops other than the ones [`LayerBlend`] supports are imported as normal.
+
+use std::{fmt::Write as _, fs, io, num::NonZeroU16, path::Path};
+use raylib::prelude::*;
+use crate::{document::Document, layer::{Canvas, Layer, LayerBlend, LayerContent, LayerTree}, raster::{self, Pixels}};
+use super::{image::{decode_png, encode_png}, invalid_data, read_opacity, zip::{ZipArchive, ZipWriter}, MAX_GROUP_DEPTH};
+
+const MIMETYPE: &[u8] = b"image/openraster";
+const THUMBNAIL_SIZE: f32 = 256.0;
+
+/// Attributes shared by `<layer>` and `<stack>`.
+struct Attrs {
+    opacity: f32,
+    visible: bool,
+    blend: LayerBlend,
+}
+
+impl Attrs {
+    fn of(layer: &Layer) -> Self {
+        Self {
+            opacity: layer.opacity,
+            visible: layer.visible,
+            blend: layer.blend,
+        }
+    }
+
+    fn apply(self, layer: &mut Layer) {
+        layer.opacity = self.opacity;
+        layer.visible = self.visible;
+        layer.blend = self.blend;
+    }
+}
+
+const fn composite_op(blend: LayerBlend) -> &'static str {
+    match blend {
+        LayerBlend::Normal   => "svg:src-over",
+        LayerBlend::Multiply => "svg:multiply",
+        LayerBlend::Screen   => "svg:screen",
+        LayerBlend::Overlay  => "svg:overlay",
+        LayerBlend::Add      => "svg:plus",
+    }
+}
+
+enum StackItem {
+    Layer {
+        name: String,
+        src: String,
+        x: i32,
+        y: i32,
+        attrs: Attrs,
+    },
+    Stack {
+        name: String,
+        children: Vec<StackItem>,
+        attrs: Attrs,
+    },
+}
+
+/* -------------------------------- stack.xml -------------------------------- */
+
+enum Tag<'a> {
+    Open {
+        name: &'a str,
+        attrs: Vec<(&'a str, String)>,
+        is_empty: bool,
+    },
+    Close,
+}
+
+impl Tag<'_> {
+    fn attr(&self, key: &str) -> Option<&str> {
+        match self {
+            Self::Open { attrs, .. } => attrs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str()),
+            Self::Close => None,
+        }
+    }
+
+    fn layer_attrs(&self) -> io::Result<Attrs> {
+        let opacity = read_opacity(self.attr("opacity").map_or(Ok(1.0), str::parse).map_err(|_| invalid_data("invalid opacity in stack.xml"))?)?;
+        let visible = self.attr("visibility") != Some("hidden");
+        let blend = self.attr("composite-op")
+            .and_then(|op| LayerBlend::ALL.into_iter().find(|&blend| composite_op(blend) == op))
+            .unwrap_or_default();
+        Ok(Attrs { opacity, visible, blend })
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '&'  => escaped.push_str("&amp;"),
+            '<'  => escaped.push_str("&lt;"),
+            '>'  => escaped.push_str("&gt;"),
+            '"'  => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else { break };
+        let entity = &rest[1..end];
+        let ch = match entity {
+            "amp"  => Some('&'),
+            "lt"   => Some('<'),
+            "gt"   => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity.strip_prefix("#x").map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        if let Some(ch) = ch {
+            unescaped.push(ch);
+            rest = &rest[end + 1..];
+        } else {
+            unescaped.push('&');
+            rest = &rest[1..];
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Split the document into tags, skipping text, comments and processing instructions.
+fn tags(xml: &str) -> io::Result<Vec<Tag<'_>>> {
+    let unterminated = || invalid_data("unterminated tag in stack.xml");
+    let mut tags = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = &after[after.find("-->").ok_or_else(unterminated)? + 3..];
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = &rest[rest.find('>').ok_or_else(unterminated)? + 1..];
+        } else if rest.starts_with("</") {
+            rest = &rest[rest.find('>').ok_or_else(unterminated)? + 1..];
+            tags.push(Tag::Close);
+        } else {
+            let end = rest.find('>').ok_or_else(unterminated)?;
+            let (body, is_empty) = match rest[1..end].strip_suffix('/') {
+                Some(body) => (body, true),
+                None => (&rest[1..end], false),
+            };
+            rest = &rest[end + 1..];
+
+            let body = body.trim();
+            let name_end = body.find(char::is_whitespace).unwrap_or(body.len());
+            let name = &body[..name_end];
+            let mut attrs = Vec::new();
+            let mut body = body[name_end..].trim_start();
+            while !body.is_empty() {
+                let eq = body.find('=').ok_or_else(|| invalid_data("malformed attribute in stack.xml"))?;
+                let key = body[..eq].trim();
+                let value = body[eq + 1..].trim_start();
+                let quote = value.chars().next().filter(|&q| q == '"' || q == '\'').ok_or_else(|| invalid_data("unquoted attribute in stack.xml"))?;
+                let value_end = value[1..].find(quote).ok_or_else(|| invalid_data("unterminated attribute in stack.xml"))?;
+                attrs.push((key, unescape(&value[1..value_end + 1])));
+                body = value[value_end + 2..].trim_start();
+            }
+            tags.push(Tag::Open { name, attrs, is_empty });
+        }
+    }
+    Ok(tags)
+}
+
+/// Skip past the end of an element whose opening tag was just consumed.
+fn skip_element<'a>(tags: &mut impl Iterator<Item = Tag<'a>>) {
+    let mut depth = 1;
+    while depth > 0 {
+        match tags.next() {
+            Some(Tag::Open { is_empty: false, .. }) => depth += 1,
+            Some(Tag::Open { is_empty: true, .. }) => {}
+            Some(Tag::Close) => depth -= 1,
+            None => break,
+        }
+    }
+}
+
+/// Read the children of a `<stack>` whose opening tag was just consumed, which is nested `depth` stacks below the root.
+fn parse_stack<'a>(tags: &mut impl Iterator<Item = Tag<'a>>, depth: usize) -> io::Result<Vec<StackItem>> {
+    if depth > MAX_GROUP_DEPTH {
+        return Err(invalid_data("stacks are nested too deeply in stack.xml"));
+    }
+    let mut items = Vec::new();
+    while let Some(tag) = tags.next() {
+        let Tag::Open { name: tag_name, is_empty, .. } = &tag else { return Ok(items) };
+        let (tag_name, is_empty) = (*tag_name, *is_empty);
+        let name = tag.attr("name").unwrap_or_default().to_owned();
+        let attrs = tag.layer_attrs()?;
+        match tag_name {
+            "layer" => {
+                let src = tag.attr("src").ok_or_else(|| invalid_data("layer without src in stack.xml"))?.to_owned();
+                let coord = |key| tag.attr(key).map_or(Ok(0), str::parse).map_err(|_| invalid_data("invalid layer position in stack.xml"));
+                let (x, y) = (coord("x")?, coord("y")?);
+                if !is_empty {
+                    skip_element(tags);
+                }
+                items.push(StackItem::Layer { name, src, x, y, attrs });
+            }
+            "stack" => {
+                let children = if is_empty { Vec::new() } else { parse_stack(tags, depth + 1)? };
+                items.push(StackItem::Stack { name, children, attrs });
+            }
+            _ => if !is_empty {
+                skip_element(tags);
+            },
+        }
+    }
+    Err(invalid_data("unterminated stack in stack.xml"))
+}
+
+/// Returns the canvas size and the root stack.
+fn parse_image(xml: &str) -> io::Result<((u32, u32), Vec<StackItem>)> {
+    let mut tags = tags(xml)?.into_iter();
+    let image = tags.next().filter(|tag| matches!(tag, Tag::Open { name: "image", .. })).ok_or_else(|| invalid_data("stack.xml has no image"))?;
+    let size = |key| image.attr(key).and_then(|s| s.parse().ok()).ok_or_else(|| invalid_data("image size missing from stack.xml"));
+    let size = (size("w")?, size("h")?);
+    loop {
+        match tags.next() {
+            Some(Tag::Open { name: "stack", is_empty, .. }) => {
+                let items = if is_empty { Vec::new() } else { parse_stack(&mut tags, 0)? };
+                return Ok((size, items));
+            }
+            Some(Tag::Open { is_empty: false, .. }) => skip_element(&mut tags),
+            Some(_) => {}
+            None => return Err(invalid_data("stack.xml has no root stack")),
+        }
+    }
+}
+
+/* --------------------------------- import ---------------------------------- */
+
+/// Copy `pixels` into the raster with their top left corner at (`x`, `y`), discarding anything outside the canvas.
+fn place_pixels(raster: &mut raster::Raster, canvas: &Canvas, pixels: &Pixels, x: i32, y: i32) {
+    let (src_x, src_y) = (x.min(0).unsigned_abs(), y.min(0).unsigned_abs());
+    let (dst_x, dst_y) = (x.max(0) as u32, y.max(0) as u32);
+    let w = pixels.w.saturating_sub(src_x).min(u32::from(canvas.get_w()).saturating_sub(dst_x));
+    let h = pixels.h.saturating_sub(src_y).min(u32::from(canvas.get_h()).saturating_sub(dst_y));
+    if w > 0 && h > 0 {
+        raster::write_pixels_at(raster, dst_x, dst_y, &pixels.crop(src_x, src_y, w, h));
+    }
+}
+
+fn build_layers(rl: &mut RaylibHandle, thread: &RaylibThread, zip: &ZipArchive, doc: &mut Document, items: Vec<StackItem>) -> io::Result<Vec<Layer>> {
+    let mut layers = Vec::with_capacity(items.len());
+    for item in items.into_iter().rev() {
+        let (mut layer, attrs) = match item {
+            StackItem::Layer { name, src, x, y, attrs } => {
+                let pixels = decode_png(&zip.read(&src)?)?;
+                let canvas = *doc.rasters.canvas();
+                let raster_rc = doc.rasters.create_raster(rl, thread);
+                place_pixels(&mut raster_rc.borrow_mut(), &canvas, &pixels, x, y);
+                (Layer::new(LayerContent::new_raster(raster_rc)).named(name), attrs)
+            }
+            StackItem::Stack { name, children, attrs } => {
+                let buffer = doc.rasters.canvas().load_buffer(rl, thread);
+                let children = build_layers(rl, thread, zip, doc, children)?;
+                (Layer::new(LayerContent::with_children(buffer, children)).named(name), attrs)
+            }
+        };
+        attrs.apply(&mut layer);
+        layers.push(layer);
+    }
+    Ok(layers)
+}
+
+/// Open an OpenRaster file as a new document.
+pub fn import_ora(rl: &mut RaylibHandle, thread: &RaylibThread, path: impl AsRef<Path>) -> io::Result<Document> {
+    let data = fs::read(path)?;
+    let zip = ZipArchive::new(&data)?;
+    if zip.names().any(|name| name == "mimetype") && zip.read("mimetype")? != MIMETYPE {
+        return Err(invalid_data("not an OpenRaster file"));
+    }
+    let xml = String::from_utf8(zip.read("stack.xml")?).map_err(|_| invalid_data("stack.xml is not valid UTF-8"))?;
+    let ((w, h), items) = parse_image(&xml)?;
+
+    let (w, h) = u16::try_from(w).ok().zip(u16::try_from(h).ok())
+        .and_then(|(w, h)| NonZeroU16::new(w).zip(NonZeroU16::new(h)))
+        .ok_or_else(|| invalid_data("unsupported canvas size"))?;
+    let mut doc = Document::new(Canvas::new(w, h));
+    for layer in build_layers(rl, thread, &zip, &mut doc, items)? {
+        doc.layers.push(layer);
+    }
+    doc.active_layer = doc.layers.topmost_raster();
+    Ok(doc)
+}
+
+/* --------------------------------- export ---------------------------------- */
+
+fn write_stack(zip: &mut ZipWriter, xml: &mut String, canvas: &Canvas, layers: &[&Layer], depth: usize, next_id: &mut usize) -> io::Result<()> {
+    for (i, layer) in layers.iter().enumerate().rev() {
+        let name = if layer.name.is_empty() { format!("Layer {}", i + 1) } else { escape(&layer.name) };
+        let indent = "  ".repeat(depth);
+        let Attrs { opacity, visible, blend } = Attrs::of(layer);
+        let attrs = format!(
+            r#"opacity="{opacity}" visibility="{}" composite-op="{}""#,
+            if visible { "visible" } else { "hidden" },
+            composite_op(blend),
+        );
+        match &layer.content {
+            LayerContent::Raster { artwork } => {
+                let pixels = match artwork.upgrade() {
+                    Some(raster_rc) => raster::read_pixels(&raster_rc.borrow()),
+                    None => Pixels::new(canvas.get_w().into(), canvas.get_h().into(), Color::BLANK),
+                };
+                let src = format!("data/layer{next_id}.png");
+                *next_id += 1;
+                zip.add(&src, &encode_png(&pixels)?, false)?;
+                writeln!(xml, r#"{indent}<layer name="{name}" src="{src}" x="0" y="0" {attrs}/>"#).unwrap();
+            }
+            LayerContent::Group { children, .. } => {
+                writeln!(xml, r#"{indent}<stack name="{name}" {attrs}>"#).unwrap();
+                write_stack(zip, xml, canvas, &children.iter().collect::<Vec<_>>(), depth + 1, next_id)?;
+                writeln!(xml, "{indent}</stack>").unwrap();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Save the document as an OpenRaster file.
+pub fn export_ora(rl: &mut RaylibHandle, thread: &RaylibThread, doc: &mut Document, path: impl AsRef<Path>) -> io::Result<()> {
+    let canvas = *doc.rasters.canvas();
+    let mut zip = ZipWriter::new();
+    // must be first and uncompressed so the file can be identified by its magic bytes
+    zip.add("mimetype", MIMETYPE, false)?;
+
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(xml, r#"<image version="0.0.5" w="{}" h="{}">"#, canvas.get_w(), canvas.get_h()).unwrap();
+    writeln!(xml, "  <stack>").unwrap();
+    let mut next_id = 0;
+    // PNGs are already compressed
+    write_stack(&mut zip, &mut xml, &canvas, &doc.layers.layers().into_iter().collect::<Vec<_>>(), 2, &mut next_id)?;
+    writeln!(xml, "  </stack>").unwrap();
+    writeln!(xml, "</image>").unwrap();
+    zip.add("stack.xml", xml.as_bytes(), true)?;
+
+    let composite = doc.layers.flatten_pixels(rl, thread, &canvas);
+    zip.add("mergedimage.png", &encode_png(&composite)?, false)?;
+
+    let scale = (THUMBNAIL_SIZE / f32::from(canvas.get_w().max(canvas.get_h()))).min(1.0);
+    let mut thumbnail = composite.to_image();
+    unsafe {
+        ffi::ImageResize(&mut *thumbnail, (f32::from(canvas.get_w()) * scale).max(1.0) as i32, (f32::from(canvas.get_h()) * scale).max(1.0) as i32);
+    }
+    zip.add("Thumbnails/thumbnail.png", &encode_png(&Pixels::from_image(&thumbnail))?, false)?;
+
+    fs::write(path, zip.finish())
+}
+
+#[cfg(test)]
+mod ora_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_image() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<image version="0.0.5" w="64" h="32">
+  <!-- comment -->
+  <stack>
+    <layer name="Ink &amp; Paint" src="data/a.png" x="-2" y="3" opacity="0.5" composite-op="svg:multiply"/>
+    <stack name='Group' visibility="hidden" composite-op="svg:color-dodge">
+      <layer name="Sketch" src="data/b.png"></layer>
+      <text>ignored</text>
+    </stack>
+  </stack>
+</image>"#;
+        let ((w, h), items) = parse_image(xml).unwrap();
+        assert_eq!((w, h), (64, 32));
+        assert_eq!(items.len(), 2);
+        let StackItem::Layer { name, src, x, y, attrs } = &items[0] else { panic!("expected layer") };
+        assert_eq!((name.as_str(), src.as_str(), *x, *y), ("Ink & Paint", "data/a.png", -2, 3));
+        assert!(attrs.opacity == 0.5 && attrs.visible && attrs.blend == LayerBlend::Multiply);
+        let StackItem::Stack { name, children, attrs } = &items[1] else { panic!("expected stack") };
+        assert_eq!(name, "Group");
+        assert!(attrs.opacity == 1.0 && !attrs.visible && attrs.blend == LayerBlend::Normal);
+        assert_eq!(children.len(), 1);
+        assert!(matches!(&children[0], StackItem::Layer { name, .. } if name == "Sketch"));
+    }
+
+    #[test]
+    fn test_parse_opacity() {
+        let parse = |opacity: &str| parse_image(&format!(r#"<image w="1" h="1"><stack><layer src="a.png" opacity="{opacity}"/></stack></image>"#))
+            .map(|(_, items)| match &items[0] {
+                StackItem::Layer { attrs, .. } => attrs.opacity,
+                StackItem::Stack { .. } => panic!("expected layer"),
+            });
+        assert!(parse("0.25").unwrap() == 0.25);
+        assert!(parse("1.5").unwrap() == 1.0);
+        assert!(parse("-1").unwrap() == 0.0);
+        assert!(parse("NaN").is_err());
+        assert!(parse("inf").is_err());
+    }
+
+    #[test]
+    fn test_parse_deep_stacks() {
+        let nested = |depth| format!(r#"<image w="1" h="1"><stack>{}{}</stack></image>"#, "<stack>".repeat(depth), "</stack>".repeat(depth));
+        assert!(parse_image(&nested(MAX_GROUP_DEPTH)).is_ok());
+        assert!(parse_image(&nested(MAX_GROUP_DEPTH + 1)).is_err());
+        assert!(parse_image(&nested(100_000)).is_err());
+    }
+
+    #[test]
+    fn test_escape_round_trip() {
+        let s = r#"<"a" & 'b'>"#;
+        assert_eq!(unescape(&escape(s)), s);
+        assert_eq!(unescape("&#65;&#x42;&bogus;"), "AB&bogus;");
+    }
+}
@@ -0,0 +1,266 @@
+//! Just enough of the zip container to read and write OpenRaster files.
+//!
+//! Supports stored and deflated entries without encryption, zip64 or multiple disks.
+//! Deflate is provided by raylib's `CompressData`/`DecompressData`, which work on raw deflate streams.
+
+use std::io;
+use raylib::prelude::*;
+use super::{invalid_data, Bytes, PutBytes};
+
+const SIG_LOCAL:   u32 = 0x04034b50;
+const SIG_CENTRAL: u32 = 0x02014b50;
+const SIG_END:     u32 = 0x06054b50;
+
+const METHOD_STORE:   u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+
+/// 1980-01-01, the earliest date zip can represent.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+pub fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut c = i as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+                k += 1;
+            }
+            table[i] = c;
+            i += 1;
+        }
+        table
+    };
+    !data.iter().fold(!0u32, |crc, &b| TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+fn deflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    let size = i32::try_from(data.len()).map_err(|_| invalid_data("zip entry is too large"))?;
+    let mut comp_size = 0;
+    unsafe {
+        let comp = ffi::CompressData(data.as_ptr(), size, &mut comp_size);
+        if comp.is_null() {
+            return Err(invalid_data("failed to compress zip entry"));
+        }
+        let bytes = std::slice::from_raw_parts(comp, comp_size as usize).to_vec();
+        ffi::MemFree(comp.cast());
+        Ok(bytes)
+    }
+}
+
+fn inflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    let size = i32::try_from(data.len()).map_err(|_| invalid_data("zip entry is too large"))?;
+    let mut uncomp_size = 0;
+    unsafe {
+        let uncomp = ffi::DecompressData(data.as_ptr(), size, &mut uncomp_size);
+        if uncomp.is_null() {
+            return Err(invalid_data("failed to decompress zip entry"));
+        }
+        let bytes = std::slice::from_raw_parts(uncomp, uncomp_size as usize).to_vec();
+        ffi::MemFree(uncomp.cast());
+        Ok(bytes)
+    }
+}
+
+struct CentralEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    comp_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+#[derive(Default)]
+pub struct ZipWriter {
+    buf: Vec<u8>,
+    entries: Vec<CentralEntry>,
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a file. Entries are written in the order they are added.
+    pub fn add(&mut self, name: &str, data: &[u8], compress: bool) -> io::Result<()> {
+        let (method, comp) = if compress {
+            (METHOD_DEFLATE, deflate(data)?)
+        } else {
+            (METHOD_STORE, data.to_vec())
+        };
+        let too_large = || invalid_data("zip archive is too large");
+        let entry = CentralEntry {
+            name: name.to_owned(),
+            method,
+            crc: crc32(data),
+            comp_size: u32::try_from(comp.len()).map_err(|_| too_large())?,
+            size: u32::try_from(data.len()).map_err(|_| too_large())?,
+            offset: u32::try_from(self.buf.len()).map_err(|_| too_large())?,
+        };
+
+        let buf = &mut self.buf;
+        buf.put_u32(SIG_LOCAL);
+        buf.put_u16(20); // version needed to extract
+        buf.put_u16(0); // flags
+        buf.put_u16(entry.method);
+        buf.put_u16(0); // time
+        buf.put_u16(DOS_DATE);
+        buf.put_u32(entry.crc);
+        buf.put_u32(entry.comp_size);
+        buf.put_u32(entry.size);
+        buf.put_u16(name.len() as u16);
+        buf.put_u16(0); // extra length
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(&comp);
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Write the central directory and return the whole archive.
+    pub fn finish(mut self) -> Vec<u8> {
+        let buf = &mut self.buf;
+        let dir_offset = buf.len() as u32;
+        for entry in &self.entries {
+            buf.put_u32(SIG_CENTRAL);
+            buf.put_u16(20); // version made by
+            buf.put_u16(20); // version needed to extract
+            buf.put_u16(0); // flags
+            buf.put_u16(entry.method);
+            buf.put_u16(0); // time
+            buf.put_u16(DOS_DATE);
+            buf.put_u32(entry.crc);
+            buf.put_u32(entry.comp_size);
+            buf.put_u32(entry.size);
+            buf.put_u16(entry.name.len() as u16);
+            buf.put_u16(0); // extra length
+            buf.put_u16(0); // comment length
+            buf.put_u16(0); // disk number
+            buf.put_u16(0); // internal attributes
+            buf.put_u32(0); // external attributes
+            buf.put_u32(entry.offset);
+            buf.extend_from_slice(entry.name.as_bytes());
+        }
+        let dir_size = buf.len() as u32 - dir_offset;
+
+        buf.put_u32(SIG_END);
+        buf.put_u16(0); // this disk
+        buf.put_u16(0); // disk with central directory
+        buf.put_u16(self.entries.len() as u16);
+        buf.put_u16(self.entries.len() as u16);
+        buf.put_u32(dir_size);
+        buf.put_u32(dir_offset);
+        buf.put_u16(0); // comment length
+        self.buf
+    }
+}
+
+pub struct ZipArchive<'a> {
+    data: &'a [u8],
+    entries: Vec<CentralEntry>,
+}
+
+impl<'a> ZipArchive<'a> {
+    pub fn new(data: &'a [u8]) -> io::Result<Self> {
+        const END_LEN: usize = 22;
+        // the end record is followed by a comment of up to u16::MAX bytes
+        let end = (0..=data.len().saturating_sub(END_LEN))
+            .rev()
+            .take(END_LEN + u16::MAX as usize)
+            .find(|&i| data[i..].starts_with(&SIG_END.to_le_bytes()))
+            .ok_or_else(|| invalid_data("not a zip archive"))?;
+
+        let mut bytes = Bytes(&data[end + 4..]);
+        bytes.take(6)?; // disk numbers, entries on this disk
+        let count = bytes.u16()?;
+        bytes.u32()?; // directory size
+        let dir_offset = bytes.u32()? as usize;
+
+        let mut bytes = Bytes(data.get(dir_offset..).ok_or_else(|| invalid_data("zip directory is out of bounds"))?);
+        let mut entries = Vec::with_capacity(count.into());
+        for _ in 0..count {
+            if bytes.u32()? != SIG_CENTRAL {
+                return Err(invalid_data("corrupt zip directory"));
+            }
+            bytes.take(4)?; // versions
+            let flags = bytes.u16()?;
+            let method = bytes.u16()?;
+            bytes.take(4)?; // time, date
+            let crc = bytes.u32()?;
+            let comp_size = bytes.u32()?;
+            let size = bytes.u32()?;
+            let name_len = bytes.u16()?;
+            let extra_len = bytes.u16()?;
+            let comment_len = bytes.u16()?;
+            bytes.take(8)?; // disk number, attributes
+            let offset = bytes.u32()?;
+            let name = String::from_utf8_lossy(bytes.take(name_len.into())?).into_owned();
+            bytes.take(usize::from(extra_len) + usize::from(comment_len))?;
+            if flags & 1 != 0 {
+                return Err(invalid_data("encrypted zip entries are not supported"));
+            }
+            entries.push(CentralEntry { name, method, crc, comp_size, size, offset });
+        }
+
+        Ok(Self { data, entries })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.name.as_str())
+    }
+
+    /// Extract a file by name.
+    pub fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        let entry = self.entries.iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("zip archive has no entry `{name}`")))?;
+
+        let mut bytes = Bytes(self.data.get(entry.offset as usize..).ok_or_else(|| invalid_data("zip entry is out of bounds"))?);
+        if bytes.u32()? != SIG_LOCAL {
+            return Err(invalid_data("corrupt zip entry"));
+        }
+        bytes.take(22)?; // up to the name length
+        let name_len = bytes.u16()?;
+        let extra_len = bytes.u16()?;
+        bytes.take(usize::from(name_len) + usize::from(extra_len))?;
+        let comp = bytes.take(entry.comp_size as usize)?;
+
+        let data = match entry.method {
+            METHOD_STORE => comp.to_vec(),
+            METHOD_DEFLATE => inflate(comp)?,
+            _ => return Err(invalid_data("unsupported zip compression method")),
+        };
+        if data.len() != entry.size as usize || crc32(&data) != entry.crc {
+            return Err(invalid_data("zip entry failed checksum"));
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod zip_tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn test_stored_round_trip() {
+        let mut zip = ZipWriter::new();
+        zip.add("mimetype", b"image/openraster", false).unwrap();
+        zip.add("data/empty.txt", b"", false).unwrap();
+        let data = zip.finish();
+
+        let archive = ZipArchive::new(&data).unwrap();
+        assert_eq!(archive.names().collect::<Vec<_>>(), ["mimetype", "data/empty.txt"]);
+        assert_eq!(archive.read("mimetype").unwrap(), b"image/openraster");
+        assert_eq!(archive.read("data/empty.txt").unwrap(), b"");
+        assert!(archive.read("stack.xml").is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use raylib::prelude::*;
+use crate::raster::Pixels;
+
+/// Which pixels a fill spreads to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FillMode {
+    /// Only similar pixels connected to the starting pixel, not counting diagonals.
+    Contiguous,
+    /// Every similar pixel on the canvas.
+    Global,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FillOptions {
+    /// How far each channel of a pixel may be from the starting pixel and still be filled. 0 only fills exact matches.
+    pub tolerance: u8,
+    pub mode: FillMode,
+    /// Compare against the composite of all the layers instead of only the layer being filled.
+    pub sample_merged: bool,
+}
+
+impl FillOptions {
+    pub const DEFAULT_TOLERANCE: u8 = 32;
+
+    pub const fn new() -> Self {
+        Self {
+            tolerance: Self::DEFAULT_TOLERANCE,
+            mode: FillMode::Contiguous,
+            sample_merged: false,
+        }
+    }
+}
+
+impl Default for FillOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether every channel of `a` is within `tolerance` of `b`.
+/// Fully transparent pixels all match each other, whatever color they hold.
+#[inline]
+fn is_similar(a: Color, b: Color, tolerance: u8) -> bool {
+    (a.a == 0 && b.a == 0) || (
+        a.r.abs_diff(b.r) <= tolerance &&
+        a.g.abs_diff(b.g) <= tolerance &&
+        a.b.abs_diff(b.b) <= tolerance &&
+        a.a.abs_diff(b.a) <= tolerance
+    )
+}
+
+/// The pixels of `sample` that a fill starting at (`x`, `y`) reaches, one per pixel, top row first.
+pub fn region(sample: &Pixels, x: u32, y: u32, tolerance: u8, mode: FillMode) -> Vec<bool> {
+    let seed = sample.get(x, y);
+    let matches = |i: usize| is_similar(sample.data[i], seed, tolerance);
+    match mode {
+        FillMode::Global => (0..sample.data.len()).map(matches).collect(),
+
+        FillMode::Contiguous => {
+            let (w, h) = (sample.w as usize, sample.h as usize);
+            let mut filled = vec![false; w * h];
+            let mut stack = vec![(x as usize, y as usize)];
+            // scanline fill: fill a whole span of the row at once, then look for spans above and below it
+            while let Some((x, y)) = stack.pop() {
+                let row = y * w;
+                if filled[row + x] { continue; }
+                let (mut x0, mut x1) = (x, x);
+                while x0 > 0 && !filled[row + x0 - 1] && matches(row + x0 - 1) { x0 -= 1; }
+                while x1 + 1 < w && !filled[row + x1 + 1] && matches(row + x1 + 1) { x1 += 1; }
+                filled[row + x0..=row + x1].fill(true);
+
+                for ny in [y.wrapping_sub(1), y + 1] {
+                    if ny >= h { continue; }
+                    let nrow = ny * w;
+                    let mut is_in_span = false;
+                    for nx in x0..=x1 {
+                        let is_match = !filled[nrow + nx] && matches(nrow + nx);
+                        if is_match && !is_in_span {
+                            stack.push((nx, ny));
+                        }
+                        is_in_span = is_match;
+                    }
+                }
+            }
+            filled
+        }
+    }
+}
+
+/// Straight-alpha "source over" compositing.
+pub fn blend_over(dst: Color, src: Color) -> Color {
+    let (sa, da) = (f32::from(src.a) / 255.0, f32::from(dst.a) / 255.0);
+    let a = sa + da * (1.0 - sa);
+    if a <= 0.0 {
+        return Color::BLANK;
+    }
+    let mix = |s: u8, d: u8| ((f32::from(s) * sa + f32::from(d) * da * (1.0 - sa)) / a).round() as u8;
+    Color::new(mix(src.r, dst.r), mix(src.g, dst.g), mix(src.b, dst.b), (a * 255.0).round() as u8)
+}
+
+/// Paint `color` over every pixel of `target` in `region`, or clear them if `is_eraser`.
+///
+/// Returns the rectangle that changed as `(x, y, w, h)`, or [`None`] if the region is empty.
+pub fn paint_region(target: &mut Pixels, region: &[bool], color: Color, is_eraser: bool) -> Option<(u32, u32, u32, u32)> {
+    debug_assert_eq!(target.data.len(), region.len(), "region should be the same size as the target");
+    let w = target.w as usize;
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for (i, (px, _)) in target.data.iter_mut().zip(region).enumerate().filter(|(_, (_, is_filled))| **is_filled) {
+        *px = if is_eraser { Color::BLANK } else { blend_over(*px, color) };
+        let (x, y) = (i % w, i / w);
+        bounds = Some(match bounds {
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            None => (x, y, x, y),
+        });
+    }
+    bounds.map(|(x0, y0, x1, y1)| (x0 as u32, y0 as u32, (x1 - x0 + 1) as u32, (y1 - y0 + 1) as u32))
+}
+
+#[cfg(test)]
+mod fill_tests {
+    use super::*;
+
+    const RED: Color = Color::new(255, 0, 0, 255);
+    const DARK_RED: Color = Color::new(240, 0, 0, 255);
+    const BLUE: Color = Color::new(0, 0, 255, 255);
+
+    /// Left and right halves of red separated by a blue column, with a slightly different red in the corner.
+    fn pixels() -> Pixels {
+        let mut pixels = Pixels::new(5, 3, RED);
+        for y in 0..3 {
+            pixels.set(2, y, BLUE);
+        }
+        pixels.set(4, 2, DARK_RED);
+        pixels
+    }
+
+    fn count(region: &[bool]) -> usize {
+        region.iter().filter(|&&is_filled| is_filled).count()
+    }
+
+    #[test]
+    fn test_contiguous() {
+        let region = region(&pixels(), 0, 0, 0, FillMode::Contiguous);
+        assert_eq!(count(&region), 6);
+        assert!(region[0] && region[1] && !region[2] && !region[3]);
+    }
+
+    #[test]
+    fn test_global() {
+        let pixels = pixels();
+        assert_eq!(count(&region(&pixels, 0, 0, 0, FillMode::Global)), 11);
+        assert_eq!(count(&region(&pixels, 0, 0, 16, FillMode::Global)), 12);
+        assert_eq!(count(&region(&pixels, 2, 1, 0, FillMode::Global)), 3);
+    }
+
+    #[test]
+    fn test_tolerance() {
+        let pixels = pixels();
+        assert_eq!(count(&region(&pixels, 3, 0, 0, FillMode::Contiguous)), 5);
+        assert_eq!(count(&region(&pixels, 3, 0, 15, FillMode::Contiguous)), 6);
+    }
+
+    #[test]
+    fn test_transparent_matches_any_color() {
+        let mut pixels = Pixels::new(2, 1, Color::BLANK);
+        pixels.set(1, 0, Color::new(255, 255, 255, 0));
+        assert_eq!(count(&region(&pixels, 0, 0, 0, FillMode::Contiguous)), 2);
+    }
+
+    #[test]
+    fn test_paint_region() {
+        let mut target = pixels();
+        let region = region(&target, 3, 0, 0, FillMode::Contiguous);
+        assert_eq!(paint_region(&mut target, &region, BLUE, false), Some((3, 0, 2, 3)));
+        assert_eq!(target.get(3, 0), BLUE);
+        assert_eq!(target.get(4, 2), DARK_RED);
+        assert_eq!(target.get(0, 0), RED);
+
+        let half_blue = Color::new(0, 0, 255, 128);
+        let mut target = Pixels::new(1, 1, Color::BLANK);
+        paint_region(&mut target, &[true], half_blue, false);
+        assert_eq!(target.get(0, 0), half_blue);
+
+        assert_eq!(paint_region(&mut target, &[false], BLUE, false), None);
+        assert_eq!(paint_region(&mut target, &[true], BLUE, true), Some((0, 0, 1, 1)));
+        assert_eq!(target.get(0, 0), Color::BLANK);
+    }
+}
@@ -0,0 +1,301 @@
+use raylib::prelude::*;
+use crate::{layer::{path_after_insertion, path_after_move, path_after_removal, Layer, LayerPath, LayerTree}, raster::{self, Pixels, RcRaster, WeakRaster}};
+
+/// A single undoable change to a document.
+pub enum Edit {
+    /// Pixels of a raster changed within a rectangle.
+    Pixels {
+        raster: WeakRaster,
+        x: u32,
+        y: u32,
+        before: Pixels,
+        after: Pixels,
+    },
+    /// A layer was inserted at `path`. Holds the layer while the edit is undone.
+    InsertLayer {
+        path: LayerPath,
+        layer: Option<Layer>,
+    },
+    /// A layer was removed from `path`. Holds the layer until the edit is undone.
+    RemoveLayer {
+        path: LayerPath,
+        layer: Option<Layer>,
+    },
+    /// A layer was moved from `from` to `to`, where `to` is a path in the tree after it was taken out.
+    MoveLayer {
+        from: LayerPath,
+        to: LayerPath,
+    },
+    /// The layer at `path` was shown or hidden.
+    ToggleVisible {
+        path: LayerPath,
+    },
+    /// Several edits that are undone and redone as one step, in order.
+    Batch(Vec<Edit>),
+}
+
+impl Edit {
+    fn insert(layers: &mut LayerTree, path: &[usize], layer: &mut Option<Layer>) {
+        let layer = layer.take().expect("edit should hold its layer while it is not applied");
+        if layers.insert(path, layer).is_err() {
+            panic!("history should not get out of sync with the layer tree");
+        }
+    }
+
+    fn remove(layers: &mut LayerTree, path: &[usize], layer: &mut Option<Layer>) {
+        *layer = Some(layers.remove(path).expect("history should not get out of sync with the layer tree"));
+    }
+
+    fn move_layer(layers: &mut LayerTree, from: &[usize], to: &[usize]) {
+        assert!(layers.move_layer(from, to), "history should not get out of sync with the layer tree");
+    }
+
+    fn toggle_visible(layers: &mut LayerTree, path: &[usize]) {
+        let layer = layers.get_mut(path).expect("history should not get out of sync with the layer tree");
+        layer.visible = !layer.visible;
+    }
+
+    /// Where the layer at `path` ends up when this edit is undone, or [`None`] if undoing it takes that layer out.
+    fn path_after_undo(&self, path: &[usize]) -> Option<LayerPath> {
+        match self {
+            Self::Pixels { .. } | Self::ToggleVisible { .. } => Some(path.to_vec()),
+            Self::InsertLayer { path: inserted, .. } => path_after_removal(inserted, path),
+            Self::RemoveLayer { path: removed, .. } => Some(path_after_insertion(removed, path)),
+            Self::MoveLayer { from, to } => Some(path_after_move(to, from, path)),
+            Self::Batch(edits) => edits.iter().rev().try_fold(path.to_vec(), |path, edit| edit.path_after_undo(&path)),
+        }
+    }
+
+    /// Where the layer at `path` ends up when this edit is redone, or [`None`] if redoing it takes that layer out.
+    fn path_after_redo(&self, path: &[usize]) -> Option<LayerPath> {
+        match self {
+            Self::Pixels { .. } | Self::ToggleVisible { .. } => Some(path.to_vec()),
+            Self::InsertLayer { path: inserted, .. } => Some(path_after_insertion(inserted, path)),
+            Self::RemoveLayer { path: removed, .. } => path_after_removal(removed, path),
+            Self::MoveLayer { from, to } => Some(path_after_move(from, to, path)),
+            Self::Batch(edits) => edits.iter().try_fold(path.to_vec(), |path, edit| edit.path_after_redo(&path)),
+        }
+    }
+
+    fn undo(&mut self, layers: &mut LayerTree) {
+        match self {
+            Self::Pixels { raster, x, y, before, .. } => {
+                if let Some(raster_rc) = raster.upgrade() {
+                    raster::write_pixels_at(&mut raster_rc.borrow_mut(), *x, *y, before);
+                }
+            }
+            Self::InsertLayer { path, layer } => Self::remove(layers, path, layer),
+            Self::RemoveLayer { path, layer } => Self::insert(layers, path, layer),
+            Self::MoveLayer { from, to } => Self::move_layer(layers, to, from),
+            Self::ToggleVisible { path } => Self::toggle_visible(layers, path),
+            Self::Batch(edits) => {
+                for edit in edits.iter_mut().rev() {
+                    edit.undo(layers);
+                }
+            }
+        }
+    }
+
+    fn redo(&mut self, layers: &mut LayerTree) {
+        match self {
+            Self::Pixels { raster, x, y, after, .. } => {
+                if let Some(raster_rc) = raster.upgrade() {
+                    raster::write_pixels_at(&mut raster_rc.borrow_mut(), *x, *y, after);
+                }
+            }
+            Self::InsertLayer { path, layer } => Self::insert(layers, path, layer),
+            Self::RemoveLayer { path, layer } => Self::remove(layers, path, layer),
+            Self::MoveLayer { from, to } => Self::move_layer(layers, from, to),
+            Self::ToggleVisible { path } => Self::toggle_visible(layers, path),
+            Self::Batch(edits) => {
+                for edit in edits.iter_mut() {
+                    edit.redo(layers);
+                }
+            }
+        }
+    }
+}
+
+/// Undo/redo stacks for a document.
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// Maximum number of undo steps to keep. The oldest steps are forgotten first.
+    pub limit: usize,
+}
+
+impl History {
+    pub const DEFAULT_LIMIT: usize = 100;
+
+    pub const fn new() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            limit: Self::DEFAULT_LIMIT,
+        }
+    }
+
+    /// Record an edit that has already been applied.
+    pub fn push(&mut self, edit: Edit) {
+        self.redo.clear();
+        self.undo.push(edit);
+        if self.undo.len() > self.limit {
+            let excess = self.undo.len() - self.limit;
+            self.undo.drain(..excess);
+        }
+    }
+
+    /// Apply an edit that has not been applied yet and record it.
+    ///
+    /// Layers being inserted are held by the edit, as if it had been undone.
+    pub fn apply(&mut self, mut edit: Edit, layers: &mut LayerTree) {
+        edit.redo(layers);
+        self.push(edit);
+    }
+
+    #[inline]
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    #[inline]
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// `active_layer` follows the layer it points to, and becomes [`None`] if that layer is taken out.
+    ///
+    /// Returns false if there was nothing to undo.
+    pub fn undo(&mut self, layers: &mut LayerTree, active_layer: &mut Option<LayerPath>) -> bool {
+        if let Some(mut edit) = self.undo.pop() {
+            edit.undo(layers);
+            *active_layer = active_layer.take().and_then(|path| edit.path_after_undo(&path));
+            self.redo.push(edit);
+            true
+        } else { false }
+    }
+
+    /// `active_layer` follows the layer it points to, and becomes [`None`] if that layer is taken out.
+    ///
+    /// Returns false if there was nothing to redo.
+    pub fn redo(&mut self, layers: &mut LayerTree, active_layer: &mut Option<LayerPath>) -> bool {
+        if let Some(mut edit) = self.redo.pop() {
+            edit.redo(layers);
+            *active_layer = active_layer.take().and_then(|path| edit.path_after_redo(&path));
+            self.undo.push(edit);
+            true
+        } else { false }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Tracks the region touched by a brush stroke from press to release, so that only that region is kept in the history.
+pub struct StrokeRecorder {
+    raster: RcRaster,
+    before: Pixels,
+    bounds: Option<(f32, f32, f32, f32)>,
+}
+
+impl StrokeRecorder {
+    /// Take a snapshot of the raster before it gets painted on.
+    pub fn begin(raster: &RcRaster) -> Self {
+        Self {
+            raster: raster.clone(),
+            before: raster::read_pixels(&raster.borrow()),
+            bounds: None,
+        }
+    }
+
+    /// The raster as it was when the stroke began.
+    #[inline]
+    pub fn before(&self) -> &Pixels {
+        &self.before
+    }
+
+    /// Mark a circle as touched.
+    pub fn include(&mut self, center: Vector2, radius: f32) {
+        let (x_min, y_min, x_max, y_max) = (center.x - radius, center.y - radius, center.x + radius, center.y + radius);
+        self.bounds = Some(match self.bounds {
+            Some((x0, y0, x1, y1)) => (x0.min(x_min), y0.min(y_min), x1.max(x_max), y1.max(y_max)),
+            None => (x_min, y_min, x_max, y_max),
+        });
+    }
+
+    /// Returns [`None`] if nothing on the canvas was touched.
+    pub fn finish(self) -> Option<Edit> {
+        let (x_min, y_min, x_max, y_max) = self.bounds?;
+        let (w, h) = (self.before.w, self.before.h);
+        // pad by a pixel for antialiasing
+        let x0 = ((x_min.floor() - 1.0).max(0.0) as u32).min(w);
+        let y0 = ((y_min.floor() - 1.0).max(0.0) as u32).min(h);
+        let x1 = ((x_max.ceil() + 1.0).max(0.0) as u32).min(w);
+        let y1 = ((y_max.ceil() + 1.0).max(0.0) as u32).min(h);
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+        let after = raster::read_pixels(&self.raster.borrow()).crop(x0, y0, x1 - x0, y1 - y0);
+        Some(Edit::Pixels {
+            raster: RcRaster::downgrade(&self.raster),
+            x: x0,
+            y: y0,
+            before: self.before.crop(x0, y0, x1 - x0, y1 - y0),
+            after,
+        })
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use super::*;
+
+    fn insert(path: &[usize]) -> Edit {
+        Edit::InsertLayer { path: path.to_vec(), layer: None }
+    }
+
+    fn remove(path: &[usize]) -> Edit {
+        Edit::RemoveLayer { path: path.to_vec(), layer: None }
+    }
+
+    #[test]
+    fn test_insert_shifts_later_siblings() {
+        let edit = insert(&[1]);
+        assert_eq!(edit.path_after_redo(&[0]), Some(vec![0]));
+        assert_eq!(edit.path_after_redo(&[1]), Some(vec![2]));
+        assert_eq!(edit.path_after_undo(&[2, 0]), Some(vec![1, 0]));
+        assert_eq!(edit.path_after_undo(&[1]), None, "undoing the insert takes the layer out");
+    }
+
+    #[test]
+    fn test_remove_is_insert_reversed() {
+        let edit = remove(&[0, 1]);
+        assert_eq!(edit.path_after_redo(&[0, 1]), None);
+        assert_eq!(edit.path_after_redo(&[0, 2]), Some(vec![0, 1]));
+        assert_eq!(edit.path_after_undo(&[0, 1]), Some(vec![0, 2]));
+        assert_eq!(edit.path_after_undo(&[1, 1]), Some(vec![1, 1]), "other groups are untouched");
+    }
+
+    #[test]
+    fn test_move_follows_the_moved_layer() {
+        // [0] moves into the group that was at [2], which is at [1] once [0] is taken out
+        let edit = Edit::MoveLayer { from: vec![0], to: vec![1, 0] };
+        assert_eq!(edit.path_after_redo(&[0]), Some(vec![1, 0]));
+        assert_eq!(edit.path_after_redo(&[2, 0]), Some(vec![1, 1]));
+        assert_eq!(edit.path_after_redo(&[1]), Some(vec![0]));
+        for path in [vec![0], vec![1], vec![2, 0]] {
+            assert_eq!(edit.path_after_undo(&edit.path_after_redo(&path).unwrap()), Some(path));
+        }
+    }
+
+    #[test]
+    fn test_batch_maps_in_order() {
+        let edit = Edit::Batch(vec![insert(&[0]), remove(&[2])]);
+        assert_eq!(edit.path_after_redo(&[0]), Some(vec![1]));
+        assert_eq!(edit.path_after_redo(&[1]), None);
+        assert_eq!(edit.path_after_undo(&[1]), Some(vec![0]));
+        assert_eq!(edit.path_after_undo(&[0]), None);
+    }
+}
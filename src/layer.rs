@@ -1,6 +1,6 @@
-use std::{cell::RefCell, num::NonZeroU16};
+use std::{cell::{Cell, RefCell}, mem::ManuallyDrop, num::NonZeroU16, thread::LocalKey};
 use raylib::prelude::*;
-use crate::{effect::{Effect, RcEffect, WeakEffect}, raster::{RcRaster, WeakRaster}};
+use crate::{brush::{AmyBlendModeExt, BlendEquation, BlendFactor, BlendModeA}, effect::{Effect, RcEffect, WeakEffect}, raster::{self, Pixels, RcRaster, WeakRaster}};
 
 pub enum LayerContent {
     Raster {
@@ -40,26 +40,182 @@ impl LayerContent {
     }
 }
 
+/// How a layer combines with the layers beneath it.
+///
+/// Overlay depends on the color underneath, which blend factors can't reach, so it is drawn by [`overlay_shader`]
+/// against a copy of what's beneath the layer. Modes that aren't offered are opened as [`Self::Normal`].
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum LayerBlend {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Add,
+}
+
+impl LayerBlend {
+    pub const ALL: [Self; 5] = [Self::Normal, Self::Multiply, Self::Screen, Self::Overlay, Self::Add];
+
+    /// Blend factors for drawing the layer, whose color has already been multiplied by its alpha
+    /// (see [`premultiply_shader`]). That is what lets multiply and screen respect transparency and opacity.
+    ///
+    /// Some modes take more than one pass; the layer is drawn again with [`Self::second_pass`] if there is one.
+    /// Overlay does all of its blending in [`overlay_shader`], so it just replaces what's there.
+    pub const fn blend_mode(self) -> BlendModeA {
+        use {BlendFactor as F, BlendEquation as E};
+        let (src_rgb, dst_rgb, src_alpha, dst_alpha) = match self {
+            Self::Normal   => (F::One, F::OneMinusSrcAlpha, F::One, F::OneMinusSrcAlpha),
+            // alpha is left alone for the second pass, which needs the backdrop's
+            Self::Multiply => (F::DstColor, F::OneMinusSrcAlpha, F::Zero, F::One),
+            Self::Screen   => (F::OneMinusDstColor, F::One, F::One, F::OneMinusSrcAlpha),
+            Self::Overlay  => return BlendModeA::REPLACE,
+            Self::Add      => (F::One, F::One, F::One, F::OneMinusSrcAlpha),
+        };
+        BlendModeA::CustomSeparate {
+            src_rgb,
+            dst_rgb,
+            src_alpha,
+            dst_alpha,
+            eq_rgb: E::FuncAdd,
+            eq_alpha: E::FuncAdd,
+        }
+    }
+
+    /// Blend factors for drawing the layer a second time, after [`Self::blend_mode`].
+    ///
+    /// Multiply is `cs·cb + cs·(1 - ab) + cb·(1 - as)`. The first pass can only reach the backdrop's color,
+    /// so the layer's own color where the backdrop is transparent is added here. Without it, multiply paints black
+    /// over transparent pixels, such as the bottom of every group.
+    pub const fn second_pass(self) -> Option<BlendModeA> {
+        use {BlendFactor as F, BlendEquation as E};
+        match self {
+            Self::Multiply => Some(BlendModeA::CustomSeparate {
+                src_rgb: F::OneMinusDstAlpha,
+                dst_rgb: F::One,
+                src_alpha: F::One,
+                dst_alpha: F::OneMinusSrcAlpha,
+                eq_rgb: E::FuncAdd,
+                eq_alpha: E::FuncAdd,
+            }),
+            Self::Normal | Self::Screen | Self::Overlay | Self::Add => None,
+        }
+    }
+}
+
+thread_local! {
+    static PREMULTIPLY_SHADER: std::cell::OnceCell<ffi::Shader> = const { std::cell::OnceCell::new() };
+    static OVERLAY_SHADER: std::cell::OnceCell<ffi::Shader> = const { std::cell::OnceCell::new() };
+    static EFFECT_BUFFER: Cell<Option<ffi::RenderTexture2D>> = const { Cell::new(None) };
+    static BACKDROP_BUFFER: Cell<Option<ffi::RenderTexture2D>> = const { Cell::new(None) };
+}
+
+/// Shader that outputs color multiplied by alpha, for use with [`LayerBlend::blend_mode`].
+///
+/// Loaded the first time it is needed and kept until the program exits.
+fn premultiply_shader() -> ffi::Shader {
+    const FRAG: &std::ffi::CStr = c"#version 330
+in vec2 fragTexCoord;
+in vec4 fragColor;
+uniform sampler2D texture0;
+uniform vec4 colDiffuse;
+out vec4 finalColor;
+void main() {
+    vec4 color = texture(texture0, fragTexCoord) * colDiffuse * fragColor;
+    finalColor = vec4(color.rgb * color.a, color.a);
+}";
+    PREMULTIPLY_SHADER.with(|shader| *shader.get_or_init(|| unsafe { ffi::LoadShaderFromMemory(std::ptr::null(), FRAG.as_ptr()) }))
+}
+
+/// Shader that outputs the layer overlaid on `backdrop`, premultiplied, for use with [`BlendModeA::REPLACE`].
+///
+/// `backdrop` must be a premultiplied copy of what the layer is drawn onto, sampled at the same coordinates as the layer.
+/// `isPremultiplied` says whether the layer's own color is too.
+/// Loaded the first time it is needed and kept until the program exits.
+fn overlay_shader() -> ffi::Shader {
+    const FRAG: &std::ffi::CStr = c"#version 330
+in vec2 fragTexCoord;
+in vec4 fragColor;
+uniform sampler2D texture0;
+uniform sampler2D backdrop;
+uniform float isPremultiplied;
+uniform vec4 colDiffuse;
+out vec4 finalColor;
+void main() {
+    vec4 src = texture(texture0, fragTexCoord) * colDiffuse * fragColor;
+    src.rgb *= mix(src.a, 1.0, isPremultiplied);
+    vec4 dst = texture(backdrop, fragTexCoord);
+    vec3 cs = src.a > 0.0 ? src.rgb / src.a : vec3(0.0);
+    vec3 cb = dst.a > 0.0 ? dst.rgb / dst.a : vec3(0.0);
+    vec3 overlay = mix(2.0 * cs * cb, 1.0 - 2.0 * (1.0 - cs) * (1.0 - cb), step(0.5, cb));
+    finalColor = vec4(
+        src.rgb * (1.0 - dst.a) + dst.rgb * (1.0 - src.a) + src.a * dst.a * overlay,
+        src.a + dst.a * (1.0 - src.a)
+    );
+}";
+    OVERLAY_SHADER.with(|shader| *shader.get_or_init(|| unsafe { ffi::LoadShaderFromMemory(std::ptr::null(), FRAG.as_ptr()) }))
+}
+
+/// A render texture the size of the canvas for intermediate results, shared by everything that uses `key`.
+///
+/// Reloaded when the canvas changes size, and otherwise kept until the program exits. Holds whatever was last drawn to it.
+fn scratch_buffer(key: &'static LocalKey<Cell<Option<ffi::RenderTexture2D>>>, canvas: &Canvas) -> ManuallyDrop<RenderTexture2D> {
+    let (w, h) = (i32::from(canvas.get_w()), i32::from(canvas.get_h()));
+    let rtex = key.with(|buffer| match buffer.get() {
+        Some(rtex) if rtex.texture.width == w && rtex.texture.height == h => rtex,
+        old => {
+            if let Some(old) = old {
+                unsafe { ffi::UnloadRenderTexture(old); }
+            }
+            let rtex = unsafe { ffi::LoadRenderTexture(w, h) };
+            buffer.set(Some(rtex));
+            rtex
+        }
+    });
+    ManuallyDrop::new(unsafe { RenderTexture2D::from_raw(rtex) })
+}
+
+/// Draw `layers` bottom to top over whatever `target` already holds.
+fn composite(rl: &mut RaylibHandle, thread: &RaylibThread, canvas: &Canvas, layers: &[Layer], target: &mut RenderTexture2D) {
+    for layer in layers {
+        layer.composite_onto(rl, thread, canvas, target);
+    }
+}
+
 pub struct Layer {
+    pub name: String,
     pub content: LayerContent,
     pub effect: Option<WeakEffect>,
+    /// From 0 (transparent) to 1 (opaque).
+    pub opacity: f32,
+    pub visible: bool,
+    pub blend: LayerBlend,
 }
 
 impl Layer {
     pub const fn new(content: LayerContent) -> Self {
         Self {
+            name: String::new(),
             content,
             effect: None,
+            opacity: 1.0,
+            visible: true,
+            blend: LayerBlend::Normal,
         }
     }
 
     pub fn with_effect(content: LayerContent, effect: &RcEffect) -> Self {
         Self {
-            content,
             effect: Some(RcEffect::downgrade(effect)),
+            ..Self::new(content)
         }
     }
 
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
     pub fn rtex<T, F: FnOnce(&RenderTexture2D) -> T>(&self, f: F) -> Option<T> {
         match &self.content {
             LayerContent::Raster { artwork, .. } => {
@@ -85,41 +241,116 @@ impl Layer {
     }
 
     // this is in its own function for the purpose of recursion
-    pub fn update_buffers<D: RaylibTextureModeExt>(&mut self, d: &mut D, thread: &RaylibThread, canvas: &Canvas) {
+    pub fn update_buffers(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, canvas: &Canvas) {
+        if !self.visible { return; }
         if let LayerContent::Group { buffer, children } = &mut self.content {
             for child in &mut *children {
-                child.update_buffers(d, thread, canvas);
+                child.update_buffers(rl, thread, canvas);
             }
 
-            {
-                let mut d = d.begin_texture_mode(thread, buffer);
-                d.clear_background(Color::BLANK);
-                for child in &*children {
-                    child.rtex(|rtex: &RenderTexture2D| {
-                        if let Some(effect_rc) = child.effect.as_ref().and_then(|effect| effect.upgrade()) {
-                            let mut effect_borrow = effect_rc.borrow_mut();
-                            let mut d = effect_borrow.begin_shader_mode(&mut d);
-                            d.draw_texture_pro(rtex, canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, Color::WHITE);
-                        } else {
-                            d.draw_texture_pro(rtex, canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, Color::WHITE);
-                        }
-                    });
-                }
-            }
+            rl.begin_texture_mode(thread, buffer).clear_background(Color::BLANK);
+            composite(rl, thread, canvas, children, buffer);
+        }
+    }
+
+    /// Draw the layer over whatever `target` holds, if it is visible. `target` must be the size of the canvas.
+    pub fn composite_onto(&self, rl: &mut RaylibHandle, thread: &RaylibThread, canvas: &Canvas, target: &mut RenderTexture2D) {
+        if self.visible {
+            self.composite_as(rl, thread, canvas, target, self.opacity, self.blend);
         }
     }
 
-    pub fn draw<D: RaylibDraw>(&self, d: &mut D, canvas: &Canvas) {
+    /// Same as [`Self::composite_onto`], but with opacity and blend mode overridden, regardless of visibility.
+    pub fn composite_as(&self, rl: &mut RaylibHandle, thread: &RaylibThread, canvas: &Canvas, target: &mut RenderTexture2D, opacity: f32, blend: LayerBlend) {
+        let alpha = (opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+        // group buffers are composited, so their color is already multiplied by alpha
+        let is_group = matches!(self.content, LayerContent::Group { .. });
+        let tint = if is_group { Color::new(alpha, alpha, alpha, alpha) } else { Color::new(255, 255, 255, alpha) };
         self.rtex(|rtex: &RenderTexture2D| {
-            if let Some(effect_rc) = self.effect.as_ref().and_then(|effect| effect.upgrade()) {
-                let mut effect_borrow = effect_rc.borrow_mut();
-                let mut d = effect_borrow.begin_shader_mode(d);
-                d.draw_texture_pro(rtex, canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, Color::WHITE);
+            // the effect gets a pass of its own, so that its result can be premultiplied like any other layer.
+            // it sees the group buffer as it is, so the result of that stays premultiplied
+            let effect_buffer = self.effect.as_ref().and_then(|effect| effect.upgrade()).map(|effect_rc| {
+                let mut effect_buffer = scratch_buffer(&EFFECT_BUFFER, canvas);
+                {
+                    let mut d = rl.begin_texture_mode(thread, &mut *effect_buffer);
+                    let mut effect_borrow = effect_rc.borrow_mut();
+                    let mut d = effect_borrow.begin_shader_mode(&mut d);
+                    let mut d = d.begin_blend_mode_a(BlendModeA::REPLACE);
+                    d.draw_texture_pro(rtex, canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, Color::WHITE);
+                }
+                effect_buffer
+            });
+            let source = effect_buffer.as_deref().unwrap_or(rtex);
+
+            if blend == LayerBlend::Overlay {
+                let mut backdrop = scratch_buffer(&BACKDROP_BUFFER, canvas);
+                {
+                    let mut d = rl.begin_texture_mode(thread, &mut *backdrop);
+                    let mut d = d.begin_blend_mode_a(BlendModeA::REPLACE);
+                    d.draw_texture_pro(&*target, canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, Color::WHITE);
+                }
+                let mut d = rl.begin_texture_mode(thread, target);
+                let mut d = d.begin_blend_mode_a(blend.blend_mode());
+                let shader = overlay_shader();
+                let is_premultiplied: f32 = if is_group { 1.0 } else { 0.0 };
+                // the backdrop has to be bound after the shader starts, which flushes the batch and unbinds extra textures.
+                // ends before the blend mode, which flushes the batch while the shader is still active
+                unsafe {
+                    ffi::BeginShaderMode(shader);
+                    ffi::SetShaderValueTexture(shader, ffi::GetShaderLocation(shader, c"backdrop".as_ptr()), backdrop.texture);
+                    ffi::SetShaderValue(
+                        shader,
+                        ffi::GetShaderLocation(shader, c"isPremultiplied".as_ptr()),
+                        (&raw const is_premultiplied).cast(),
+                        ffi::ShaderUniformDataType::SHADER_UNIFORM_FLOAT as i32,
+                    );
+                }
+                d.draw_texture_pro(source, canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, tint);
+                unsafe { ffi::EndShaderMode(); }
             } else {
-                d.draw_texture_pro(rtex, canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, Color::WHITE);
+                let mut d = rl.begin_texture_mode(thread, target);
+                for blend_mode in [Some(blend.blend_mode()), blend.second_pass()].into_iter().flatten() {
+                    let mut d = d.begin_blend_mode_a(blend_mode);
+                    if is_group {
+                        d.draw_texture_pro(source, canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, tint);
+                    } else {
+                        // ends before the blend mode, which flushes the batch while the shader is still active
+                        unsafe { ffi::BeginShaderMode(premultiply_shader()); }
+                        d.draw_texture_pro(source, canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, tint);
+                        unsafe { ffi::EndShaderMode(); }
+                    }
+                }
             }
         });
     }
+
+    /// Deep copy of the layer, with copies of its rasters added to `rasters`. Effects are shared rather than copied.
+    pub fn duplicate(&self, rl: &mut RaylibHandle, thread: &RaylibThread, rasters: &mut RasterTable) -> Self {
+        let content = match &self.content {
+            LayerContent::Raster { artwork } => match artwork.upgrade() {
+                Some(raster_rc) => {
+                    let pixels = raster::read_pixels(&raster_rc.borrow());
+                    let copy_rc = rasters.create_raster(rl, thread);
+                    raster::write_pixels(&mut copy_rc.borrow_mut(), &pixels);
+                    LayerContent::new_raster(copy_rc)
+                }
+                None => LayerContent::empty_raster(),
+            },
+            LayerContent::Group { children, .. } => {
+                let buffer = rasters.canvas().load_buffer(rl, thread);
+                let children = children.iter().map(|child| child.duplicate(rl, thread, rasters)).collect::<Vec<_>>();
+                LayerContent::with_children(buffer, children)
+            }
+        };
+        Self {
+            name: self.name.clone(),
+            content,
+            effect: self.effect.clone(),
+            opacity: self.opacity,
+            visible: self.visible,
+            blend: self.blend,
+        }
+    }
 }
 
 /// Shape and position of the artwork canvas.
@@ -155,6 +386,16 @@ impl Canvas {
     pub const fn get_h(&self) -> u16 {
         self.h.get()
     }
+
+    /// Create a blank render texture the size of the canvas, such as a group buffer.
+    pub fn load_buffer(&self, mut rl: &mut RaylibHandle, thread: &RaylibThread) -> RenderTexture2D {
+        let mut rtex = rl.load_render_texture(thread, self.w.get().into(), self.h.get().into()).unwrap();
+        {
+            let mut d = (&mut rl).begin_texture_mode(thread, &mut rtex);
+            d.clear_background(Color::BLANK);
+        }
+        rtex
+    }
 }
 
 pub struct RasterTable {
@@ -180,6 +421,16 @@ impl RasterTable {
         &mut self.canvas
     }
 
+    #[inline]
+    pub fn rasters(&self) -> &[RcRaster] {
+        &self.rasters
+    }
+
+    /// Index of the raster in the table, if it belongs to this table.
+    pub fn index_of(&self, raster: &RcRaster) -> Option<usize> {
+        self.rasters.iter().position(|raster_rc| RcRaster::ptr_eq(raster_rc, raster))
+    }
+
     pub fn create_raster<'a>(&'a mut self, mut rl: &mut RaylibHandle, thread: &RaylibThread) -> &'a RcRaster {
         let mut rtex = rl.load_render_texture(thread, self.canvas.w.get().into(), self.canvas.h.get().into()).unwrap();
         {
@@ -222,6 +473,16 @@ impl EffectTable {
         }
     }
 
+    #[inline]
+    pub fn effects(&self) -> &[RcEffect] {
+        &self.effects
+    }
+
+    /// Index of the effect in the table, if it belongs to this table.
+    pub fn index_of(&self, effect: &RcEffect) -> Option<usize> {
+        self.effects.iter().position(|effect_rc| RcEffect::ptr_eq(effect_rc, effect))
+    }
+
     pub fn create_effect(&mut self, effect: Effect) -> &RcEffect {
         self.effects.push(RcEffect::new(RefCell::new(effect)));
         self.effects.last().expect("should have at least 1 element after pushing")
@@ -233,14 +494,54 @@ impl EffectTable {
     }
 }
 
+/// Location of a layer in a [`LayerTree`], as the index at each level of nesting from the root.
+pub type LayerPath = Vec<usize>;
+
+/// Where the layer at `path` ends up after the layer at `removed` is taken out of the tree.
+///
+/// Returns [`None`] if `path` is `removed` or inside it.
+pub fn path_after_removal(removed: &[usize], path: &[usize]) -> Option<LayerPath> {
+    if path.starts_with(removed) {
+        return None;
+    }
+    let mut path = path.to_vec();
+    if let Some((&last, parent)) = removed.split_last() && path.len() > parent.len() && path.starts_with(parent) && path[parent.len()] > last {
+        path[parent.len()] -= 1;
+    }
+    Some(path)
+}
+
+/// Where the layer at `path` ends up after a layer is inserted at `inserted`.
+pub fn path_after_insertion(inserted: &[usize], path: &[usize]) -> LayerPath {
+    let mut path = path.to_vec();
+    if let Some((&last, parent)) = inserted.split_last() && path.len() > parent.len() && path.starts_with(parent) && path[parent.len()] >= last {
+        path[parent.len()] += 1;
+    }
+    path
+}
+
+/// Where the layer at `path` ends up after [`LayerTree::move_layer`] moves the layer at `from` to `to`.
+pub fn path_after_move(from: &[usize], to: &[usize], path: &[usize]) -> LayerPath {
+    match path.strip_prefix(from) {
+        Some(rest) => [to, rest].concat(),
+        None => {
+            let path = path_after_removal(from, path).expect("only paths inside `from` are removed");
+            path_after_insertion(to, &path)
+        }
+    }
+}
+
 pub struct LayerTree {
     layers: Vec<Layer>,
+    /// Every layer composited together, as of the last [`Self::update_buffers`].
+    buffer: Option<RenderTexture2D>,
 }
 
 impl LayerTree {
     pub const fn new() -> Self {
         Self {
             layers: Vec::new(),
+            buffer: None,
         }
     }
 
@@ -254,8 +555,204 @@ impl LayerTree {
         &self.layers
     }
 
+    /// The list that the layer at `path` belongs to, or the root list if `path` is empty.
+    fn siblings_mut(&mut self, path: &[usize]) -> Option<&mut Vec<Layer>> {
+        let Some((_, parent_path)) = path.split_last() else { return Some(&mut self.layers) };
+        let mut siblings = &mut self.layers;
+        for &i in parent_path {
+            match &mut siblings.get_mut(i)?.content {
+                LayerContent::Group { children, .. } => siblings = children,
+                LayerContent::Raster { .. } => return None,
+            }
+        }
+        Some(siblings)
+    }
+
+    /// The list that the layer at `path` belongs to, or the root list if `path` is empty.
+    pub fn siblings(&self, path: &[usize]) -> Option<&[Layer]> {
+        let Some((_, parent_path)) = path.split_last() else { return Some(&self.layers) };
+        match &self.get(parent_path)?.content {
+            LayerContent::Group { children, .. } => Some(children),
+            LayerContent::Raster { .. } => None,
+        }
+    }
+
+    /// Path of the raster layer drawn on top of all others, looking inside groups.
+    pub fn topmost_raster(&self) -> Option<LayerPath> {
+        fn search(layers: &[Layer], path: &mut LayerPath) -> bool {
+            for (i, layer) in layers.iter().enumerate().rev() {
+                path.push(i);
+                match &layer.content {
+                    LayerContent::Raster { .. } => return true,
+                    LayerContent::Group { children, .. } => if search(children, path) { return true },
+                }
+                path.pop();
+            }
+            false
+        }
+        let mut path = LayerPath::new();
+        search(&self.layers, &mut path).then_some(path)
+    }
+
+    pub fn get(&self, path: &[usize]) -> Option<&Layer> {
+        let (&first, rest) = path.split_first()?;
+        let mut layer = self.layers.get(first)?;
+        for &i in rest {
+            match &layer.content {
+                LayerContent::Group { children, .. } => layer = children.get(i)?,
+                LayerContent::Raster { .. } => return None,
+            }
+        }
+        Some(layer)
+    }
+
+    pub fn get_mut(&mut self, path: &[usize]) -> Option<&mut Layer> {
+        let (&last, _) = path.split_last()?;
+        self.siblings_mut(path)?.get_mut(last)
+    }
+
+    /// Insert a layer so that it ends up at `path`, shifting later siblings back.
+    ///
+    /// Gives the layer back if the parent of `path` is not a group or the index is past the end.
+    pub fn insert(&mut self, path: &[usize], layer: Layer) -> Result<(), Layer> {
+        let Some((&last, _)) = path.split_last() else { return Err(layer) };
+        match self.siblings_mut(path) {
+            Some(siblings) if last <= siblings.len() => {
+                siblings.insert(last, layer);
+                Ok(())
+            }
+            _ => Err(layer),
+        }
+    }
+
+    pub fn remove(&mut self, path: &[usize]) -> Option<Layer> {
+        let (&last, _) = path.split_last()?;
+        let siblings = self.siblings_mut(path)?;
+        (last < siblings.len()).then(|| siblings.remove(last))
+    }
+
+    /// Take the layer at `from` out and insert it at `to`, where `to` is a path in the tree after the removal.
+    ///
+    /// Leaves the tree unchanged and returns false if either path is invalid.
+    pub fn move_layer(&mut self, from: &[usize], to: &[usize]) -> bool {
+        if to.is_empty() || self.get(from).is_none() {
+            return false;
+        }
+        let layer = self.remove(from).expect("layer should exist after checking");
+        match self.insert(to, layer) {
+            Ok(()) => true,
+            Err(layer) => {
+                if self.insert(from, layer).is_err() {
+                    panic!("layer should fit back where it was removed from");
+                }
+                false
+            }
+        }
+    }
+
+    /// Bring every group buffer up to date, then composite the whole tree into [`Self::buffer`].
+    pub fn update_buffers(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, canvas: &Canvas) {
+        for layer in &mut self.layers {
+            layer.update_buffers(rl, thread, canvas);
+        }
+        let buffer = match &mut self.buffer {
+            Some(buffer) if buffer.texture.width == i32::from(canvas.get_w()) && buffer.texture.height == i32::from(canvas.get_h()) => {
+                rl.begin_texture_mode(thread, buffer).clear_background(Color::BLANK);
+                buffer
+            }
+            buffer => buffer.insert(canvas.load_buffer(rl, thread)),
+        };
+        composite(rl, thread, canvas, &self.layers, buffer);
+    }
+
+    /// Every layer composited together, as of the last [`Self::update_buffers`].
+    ///
+    /// The color is multiplied by its alpha, so it draws correctly with [`LayerBlend::blend_mode`].
     #[inline]
-    pub fn layers_mut(&mut self) -> impl IntoIterator<Item = &mut Layer> {
-        &mut self.layers
+    pub const fn buffer(&self) -> Option<&RenderTexture2D> {
+        self.buffer.as_ref()
+    }
+
+    /// Composite every layer into a new render texture the size of the canvas.
+    ///
+    /// The color of the result is multiplied by its alpha, so it draws correctly with [`LayerBlend::blend_mode`].
+    pub fn flatten(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, canvas: &Canvas) -> RenderTexture2D {
+        for layer in &mut self.layers {
+            layer.update_buffers(rl, thread, canvas);
+        }
+        let mut rtex = canvas.load_buffer(rl, thread);
+        composite(rl, thread, canvas, &self.layers, &mut rtex);
+        rtex
+    }
+
+    /// [`Self::flatten`] read back as straight (not premultiplied) pixels, ready to be saved or used as a raster.
+    pub fn flatten_pixels(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, canvas: &Canvas) -> Pixels {
+        let mut pixels = raster::read_pixels(&self.flatten(rl, thread, canvas));
+        pixels.unpremultiply();
+        pixels
+    }
+}
+
+#[cfg(test)]
+mod layer_tests {
+    use crate::test_support::*;
+    use super::*;
+
+    #[test]
+    fn test_path_after_removal() {
+        assert_eq!(path_after_removal(&[1], &[0]), Some(vec![0]));
+        assert_eq!(path_after_removal(&[1], &[2]), Some(vec![1]));
+        assert_eq!(path_after_removal(&[1], &[1]), None);
+        assert_eq!(path_after_removal(&[1], &[1, 0]), None, "children go with their group");
+        assert_eq!(path_after_removal(&[0], &[1, 1]), Some(vec![0, 1]), "a group shifting takes its children along");
+        assert_eq!(path_after_removal(&[1, 0], &[1, 1]), Some(vec![1, 0]));
+        assert_eq!(path_after_removal(&[1, 0], &[2]), Some(vec![2]), "removing inside a group doesn't move what's outside it");
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut layers = tree();
+        assert!(layers.insert(&[1, 1], raster("x")).is_ok());
+        assert!(layers.insert(&[3], raster("y")).is_ok());
+        assert_eq!(describe(layers.layers()), "a G(b x c) d y");
+        assert!(layers.insert(&[5], raster("z")).is_err(), "past the end");
+        assert!(layers.insert(&[0, 0], raster("z")).is_err(), "into a raster");
+        assert!(layers.insert(&[], raster("z")).is_err());
+        assert_eq!(describe(layers.layers()), "a G(b x c) d y");
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut layers = tree();
+        assert_eq!(layers.remove(&[1, 0]).map(|layer| layer.name), Some("b".to_owned()));
+        assert_eq!(describe(layers.layers()), "a G(c) d");
+        assert!(layers.remove(&[1, 1]).is_none());
+        assert!(layers.remove(&[]).is_none());
+        assert_eq!(layers.remove(&[1]).map(|layer| describe([&layer])), Some("G(c)".to_owned()));
+        assert_eq!(describe(layers.layers()), "a d");
+    }
+
+    #[test]
+    fn test_move_layer_across_depths() {
+        let mut layers = tree();
+        // into the group, which is at [0] once `a` is taken out
+        assert!(layers.move_layer(&[0], &[0, 2]));
+        assert_eq!(describe(layers.layers()), "G(b c a) d");
+        // and back out, on top of everything
+        assert!(layers.move_layer(&[0, 0], &[2]));
+        assert_eq!(describe(layers.layers()), "G(c a) d b");
+        // a group moves with its children
+        assert!(layers.move_layer(&[0], &[1]));
+        assert_eq!(describe(layers.layers()), "d G(c a) b");
+    }
+
+    #[test]
+    fn test_move_layer_invalid() {
+        let mut layers = tree();
+        assert!(!layers.move_layer(&[3], &[0]), "nothing to move");
+        assert!(!layers.move_layer(&[0], &[5]), "nowhere to put it");
+        assert!(!layers.move_layer(&[0], &[]));
+        assert!(!layers.move_layer(&[1], &[1, 0]), "a group can't go inside itself");
+        assert_eq!(describe(layers.layers()), "a G(b c) d");
     }
 }
@@ -0,0 +1,347 @@
+use amygui::prelude::*;
+use raylib::prelude::*;
+use amygui::prelude::MouseButton; // not raylib's
+use crate::{document::Document, layer::{path_after_removal, Canvas, Layer, LayerContent, LayerPath, LayerTree}, RaylibDrawBackend, RaylibTickBackend};
+
+const PANEL_WIDTH: f32 = 220.0;
+const ROW_HEIGHT: f32 = 36.0;
+const THUMBNAIL_SIZE: f32 = 32.0;
+const INDENT: f32 = 12.0;
+const TOGGLE_SIZE: f32 = 14.0;
+
+/// A row of the panel, listed topmost layer first.
+struct Row {
+    path: LayerPath,
+    depth: usize,
+}
+
+fn rows(layers: &LayerTree) -> Vec<Row> {
+    fn push_rows(rows: &mut Vec<Row>, layers: &[Layer], path: &mut LayerPath) {
+        for (i, layer) in layers.iter().enumerate().rev() {
+            path.push(i);
+            rows.push(Row { path: path.clone(), depth: path.len() - 1 });
+            if let LayerContent::Group { children, .. } = &layer.content {
+                push_rows(rows, children, path);
+            }
+            path.pop();
+        }
+    }
+    let mut rows = Vec::new();
+    push_rows(&mut rows, layers.siblings(&[]).unwrap_or_default(), &mut LayerPath::new());
+    rows
+}
+
+/// Where a dragged layer would go if it were released now.
+#[derive(Clone, PartialEq)]
+enum DropTarget {
+    /// Between rows, at this path in the tree before the dragged layer is taken out.
+    At(LayerPath),
+    /// On top of the children of the group at this path.
+    Into(LayerPath),
+}
+
+/// A row that was pressed, which may turn into a drag.
+struct Drag {
+    from: LayerPath,
+    is_dragging: bool,
+    drop: Option<DropTarget>,
+}
+
+/// The rows of the layers panel, in the panel's scroll box.
+struct LayerListNode {
+    rows: Vec<Row>,
+    drag: Option<Drag>,
+    /// Mirrors [`Document::active_layer`] for drawing.
+    active_layer: Option<LayerPath>,
+}
+
+impl LayerListNode {
+    fn row_rect(slot: Rect, index: usize) -> Rect {
+        let y_min = slot.y_min + index as f32 * ROW_HEIGHT;
+        Rect { x_min: slot.x_min, y_min, x_max: slot.x_max, y_max: y_min + ROW_HEIGHT }
+    }
+
+    fn toggle_rect(row_rect: Rect, depth: usize) -> Rect {
+        let x_min = row_rect.x_min + 4.0 + depth as f32 * INDENT;
+        let y_min = row_rect.y_min + (ROW_HEIGHT - TOGGLE_SIZE) * 0.5;
+        Rect { x_min, y_min, x_max: x_min + TOGGLE_SIZE, y_max: y_min + TOGGLE_SIZE }
+    }
+
+    fn row_at(&self, slot: Rect, point: Point) -> Option<usize> {
+        if !slot.contains(point) { return None; }
+        let index = ((point.y - slot.y_min) / ROW_HEIGHT) as usize;
+        (index < self.rows.len()).then_some(index)
+    }
+
+    fn drop_at(&self, doc: &Document, slot: Rect, point: Point) -> Option<DropTarget> {
+        let Some(index) = self.row_at(slot, point) else {
+            // past the last row means the very bottom of the root
+            return (slot.contains(point) && !self.rows.is_empty()).then(|| DropTarget::At(vec![0]));
+        };
+        let row = &self.rows[index];
+        let (&last, parent) = row.path.split_last()?;
+        let t = (point.y - Self::row_rect(slot, index).y_min) / ROW_HEIGHT;
+        let is_group = matches!(doc.layers.get(&row.path)?.content, LayerContent::Group { .. });
+        Some(if is_group && (0.25..0.75).contains(&t) {
+            DropTarget::Into(row.path.clone())
+        } else if t < 0.5 {
+            DropTarget::At([parent, &[last + 1]].concat())
+        } else {
+            DropTarget::At(row.path.clone())
+        })
+    }
+
+    /// Returns the dragged layer's new path if it moved.
+    fn apply_drop(doc: &mut Document, from: &[usize], drop: &DropTarget) -> Option<LayerPath> {
+        match drop {
+            DropTarget::At(path) => {
+                let to = path_after_removal(from, path)?;
+                (to != from && doc.move_layer(from, &to)).then_some(to)
+            }
+            DropTarget::Into(group) => doc.move_into_group(from, group),
+        }
+    }
+}
+
+impl Node for LayerListNode {
+    #[inline]
+    fn size_range(&self) -> ((f32, Option<f32>), (f32, Option<f32>)) {
+        ((0.0, None), (self.rows.len() as f32 * ROW_HEIGHT, None))
+    }
+}
+
+impl<'a> TickNode<RaylibTickBackend<'a>> for LayerListNode {
+    fn dibs_tick(&mut self, tb: &mut RaylibTickBackend<'a>, slot: Rect, events: &mut Events) {
+        let RaylibTickBackend(_, _, doc, _) = tb;
+
+        if self.drag.is_none() { return; }
+        let Some(drag_event) = events.drag.take_with_dibs_if(|drag_event| drag_event.button == MouseButton::Left) else {
+            // released without dragging
+            if events.is_mouse_released(MouseButton::Left) {
+                self.drag = None;
+            }
+            return;
+        };
+        // nothing else should react to the mouse while a layer is being dragged
+        events.mouse_event.take();
+
+        match drag_event.kind {
+            DragKind::Start | DragKind::Move => {
+                let drop = self.drop_at(doc, slot, drag_event.position);
+                let drag = self.drag.as_mut().expect("drag should still be active");
+                drag.is_dragging = true;
+                drag.drop = drop;
+            }
+            DragKind::End => {
+                let drag = self.drag.take().expect("drag should still be active");
+                if let Some(to) = drag.drop.and_then(|drop| Self::apply_drop(doc, &drag.from, &drop)) {
+                    doc.active_layer = Some(to);
+                }
+            }
+        }
+    }
+
+    fn active_tick(&mut self, tb: &mut RaylibTickBackend<'a>, slot: Rect, events: &mut Events) {
+        let RaylibTickBackend(_, _, doc, _) = tb;
+        let Some(mut mouse_event) = events.mouse_event.take_if_overlapping(slot) else { return };
+
+        if mouse_event.take_press(MouseButton::Left).is_some() {
+            if let Some(index) = self.row_at(slot, mouse_event.position) {
+                let row = &self.rows[index];
+                if Self::toggle_rect(Self::row_rect(slot, index), row.depth).contains(mouse_event.position) {
+                    doc.toggle_visible(&row.path);
+                } else {
+                    doc.active_layer = Some(row.path.clone());
+                    self.active_layer = doc.active_layer.clone();
+                    self.drag = Some(Drag {
+                        from: row.path.clone(),
+                        is_dragging: false,
+                        drop: None,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// What a row shows, copied out of the layer tree so that the tree isn't borrowed from the backend while drawing.
+struct RowView {
+    name: String,
+    visible: bool,
+    thumbnail: Option<ffi::Texture2D>,
+}
+
+impl RowView {
+    fn new(layer: &Layer) -> Self {
+        let name = match (&layer.content, layer.name.is_empty()) {
+            (_, false) => layer.name.as_str(),
+            (LayerContent::Raster { .. }, true) => "Layer",
+            (LayerContent::Group { .. }, true) => "Group",
+        };
+        Self {
+            name: name.to_owned(),
+            visible: layer.visible,
+            thumbnail: layer.rtex(|rtex| rtex.texture),
+        }
+    }
+}
+
+fn draw_thumbnail(d: &mut RaylibDrawBackend, texture: &ffi::Texture2D, canvas: &Canvas, rect: Rect) {
+    let (w, h) = (f32::from(canvas.get_w()), f32::from(canvas.get_h()));
+    let scale = (rect.width() / w).min(rect.height() / h);
+    let (thumb_w, thumb_h) = (w * scale, h * scale);
+    let x_min = rect.x_min + (rect.width() - thumb_w) * 0.5;
+    let y_min = rect.y_min + (rect.height() - thumb_h) * 0.5;
+    let dest = Rect { x_min, y_min, x_max: x_min + thumb_w, y_max: y_min + thumb_h };
+    d.draw_rect(&dest, &Color::new(64,64,64,255));
+    // render textures are stored upside down
+    let source = Rect { x_min: 0.0, y_min: 0.0, x_max: w, y_max: -h };
+    d.draw_image(texture, Some(&source), &dest, &Color::WHITE);
+}
+
+impl DrawNode<RaylibDrawBackend<'_, '_, '_>> for LayerListNode {
+    fn draw(&self, d: &mut RaylibDrawBackend, slot: Rect) {
+        let theme = d.theme();
+        let palette = *theme.palette();
+        let font_size = theme.number("layers", "font_size", theme.font_size);
+        let canvas = *d.1.canvas();
+        let views = self.rows.iter()
+            .map(|row| d.3.get(&row.path).map(RowView::new))
+            .collect::<Vec<_>>();
+
+        for (index, (row, view)) in self.rows.iter().zip(&views).enumerate() {
+            let Some(view) = view else { continue };
+            let row_rect = Self::row_rect(slot, index);
+            let is_active = self.active_layer.as_ref() == Some(&row.path);
+            let is_dragged = self.drag.as_ref().is_some_and(|drag| drag.is_dragging && drag.from == row.path);
+
+            let color = if is_active { palette.selection } else { palette.surface };
+            let color = if is_dragged { color.fade(0.5) } else { color };
+            d.draw_rect(&Rect { y_min: row_rect.y_min + 1.0, y_max: row_rect.y_max - 1.0, ..row_rect }, &color);
+
+            let toggle = Self::toggle_rect(row_rect, row.depth);
+            d.draw_rect_outline(&toggle, 1.0, &palette.text);
+            if view.visible {
+                let inner = Rect {
+                    x_min: toggle.x_min + 3.0,
+                    y_min: toggle.y_min + 3.0,
+                    x_max: toggle.x_max - 3.0,
+                    y_max: toggle.y_max - 3.0,
+                };
+                d.draw_rect(&inner, &palette.text);
+            }
+
+            let thumb_x = toggle.x_max + 4.0;
+            let thumb_y = row_rect.y_min + (ROW_HEIGHT - THUMBNAIL_SIZE) * 0.5;
+            if let Some(texture) = &view.thumbnail {
+                draw_thumbnail(d, texture, &canvas, Rect {
+                    x_min: thumb_x,
+                    y_min: thumb_y,
+                    x_max: thumb_x + THUMBNAIL_SIZE,
+                    y_max: thumb_y + THUMBNAIL_SIZE,
+                });
+            }
+
+            let text_color = if view.visible { palette.text } else { palette.text_disabled };
+            let text_pos = Point { x: thumb_x + THUMBNAIL_SIZE + 6.0, y: row_rect.y_min + (ROW_HEIGHT - font_size) * 0.5 };
+            d.draw_text(&view.name, text_pos, font_size, &text_color);
+        }
+
+        // drop indicator
+        if let Some(drop) = self.drag.as_ref().filter(|drag| drag.is_dragging).and_then(|drag| drag.drop.as_ref()) {
+            match drop {
+                DropTarget::Into(group) => {
+                    if let Some(index) = self.rows.iter().position(|row| &row.path == group) {
+                        d.draw_rect_outline(&Self::row_rect(slot, index), 2.0, &palette.accent);
+                    }
+                }
+                DropTarget::At(path) => {
+                    let (&last, parent) = path.split_last().expect("drop path should not be empty");
+                    // the line goes above the row currently at `path`, or below the one beneath it
+                    let below = [parent, &[last.wrapping_sub(1)]].concat();
+                    let (y, depth) = if let Some(index) = self.rows.iter().position(|row| &row.path == path) {
+                        (Self::row_rect(slot, index).y_max, parent.len())
+                    } else if let Some(index) = self.rows.iter().position(|row| row.path == below) {
+                        (Self::row_rect(slot, index).y_min, parent.len())
+                    } else {
+                        (Self::row_rect(slot, self.rows.len()).y_min, 0)
+                    };
+                    let x = slot.x_min + depth as f32 * INDENT;
+                    d.draw_line(Point { x, y }, Point { x: slot.x_max, y }, 2.0, &palette.accent);
+                }
+            }
+        }
+    }
+}
+
+/// Lists the layer tree with thumbnails, visibility toggles, selection and drag-to-reorder.
+/// Scrolls when there are more layers than fit.
+///
+/// Docks to the right edge of its slot.
+pub struct LayersPanelNode {
+    list: ScrollBoxNode<Color, LayerListNode>,
+}
+
+impl LayersPanelNode {
+    pub fn new() -> Self {
+        // replaced by the theme's style on the first tick
+        let style = ScrollBoxStyle {
+            bar_size: 8.0,
+            scroll_speed: ROW_HEIGHT,
+            track_color: Color::BLANK,
+            thumb_color: Color::GRAY,
+            thumb_hover_color: Color::LIGHTGRAY,
+        };
+        let list = LayerListNode {
+            rows: Vec::new(),
+            drag: None,
+            active_layer: None,
+        };
+        Self {
+            list: ScrollBoxNode::vertical(style, list).themed("layers"),
+        }
+    }
+}
+
+impl Node for LayersPanelNode {
+    #[inline]
+    fn size_range(&self) -> ((f32, Option<f32>), (f32, Option<f32>)) {
+        ((PANEL_WIDTH, Some(PANEL_WIDTH)), (0.0, None))
+    }
+
+    #[inline]
+    fn bounds(&self, slot: Rect) -> Rect {
+        Rect { x_min: (slot.x_max - PANEL_WIDTH).max(slot.x_min), ..slot }
+    }
+}
+
+impl<'a> TickNode<RaylibTickBackend<'a>> for LayersPanelNode {
+    fn dibs_tick(&mut self, tb: &mut RaylibTickBackend<'a>, slot: Rect, events: &mut Events) {
+        // refreshed before the scroll box lays out the rows
+        let list = &mut self.list.content;
+        list.rows = rows(&tb.2.layers);
+        list.active_layer = tb.2.active_layer.clone();
+        self.list.dibs_tick(tb, self.bounds(slot), events);
+    }
+
+    fn active_tick(&mut self, tb: &mut RaylibTickBackend<'a>, slot: Rect, events: &mut Events) {
+        let slot = self.bounds(slot);
+        self.list.active_tick(tb, slot, events);
+        // the panel is opaque, so clicks between the rows and the scrollbar don't fall through to the canvas
+        events.mouse_event.take_if_overlapping(slot);
+    }
+
+    fn inactive_tick(&mut self, tb: &mut RaylibTickBackend<'a>, slot: Rect, events: &Events) {
+        self.list.inactive_tick(tb, self.bounds(slot), events);
+    }
+}
+
+impl DrawNode<RaylibDrawBackend<'_, '_, '_>> for LayersPanelNode {
+    fn draw(&self, d: &mut RaylibDrawBackend, slot: Rect) {
+        let slot = self.bounds(slot);
+        let theme = d.theme();
+        let background_color = theme.color("layers", "background_color", theme.palette().background);
+        d.draw_rect(&slot, &background_color);
+        self.list.draw(d, slot);
+    }
+}
@@ -2,22 +2,77 @@
 
 use std::num::{NonZeroU16, NonZeroU32};
 use amygui::prelude::*;
-use brush::{AmyBlendModeExt, BlendEquation, BlendFactor, BlendModeA, Brush, BrushPreset, BrushPresetDraw, BrushTargetModeExt};
+use brush::{AmyBlendModeExt, BlendEquation, BlendFactor, BlendModeA, Brush, BrushPreset, BrushPresetDraw, BrushTip, BrushTargetModeExt};
+use document::Document;
+use fill::FillMode;
+use shape::Shape;
 use layer::{Canvas, EffectTable, Layer, LayerContent, LayerTree, RasterTable};
+use layers_panel::LayersPanelNode;
 #[cfg(feature = "rl-5_5")]
 use raylib::prelude::*;
 #[cfg(feature = "rl-old")]
 use raylib_old::prelude::*;
-use viewport::ViewportNode;
+use raster::RcRaster;
+use selection::{Selection, SelectionTool};
+use transform::Resample;
+use viewport::{Tool, ViewportNode};
 
 mod raster;
 mod effect;
 mod layer;
 mod brush;
+mod fill;
+mod shape;
+mod selection;
+mod transform;
 mod viewport;
+mod layers_panel;
+mod document;
+mod file;
+mod history;
+#[cfg(test)]
+mod test_support;
 
 pub struct RaylibInputBackend<'a>(pub &'a RaylibHandle);
 
+// both preludes have a `MouseButton`
+const fn raylib_mouse_button(button: amygui::prelude::MouseButton) -> raylib::consts::MouseButton {
+    use {amygui::prelude::MouseButton as Button, raylib::consts::MouseButton::*};
+    match button {
+        Button::Left => MOUSE_BUTTON_LEFT,
+        Button::Right => MOUSE_BUTTON_RIGHT,
+        Button::Middle => MOUSE_BUTTON_MIDDLE,
+        Button::Back => MOUSE_BUTTON_BACK,
+        Button::Forward => MOUSE_BUTTON_FORWARD,
+    }
+}
+
+const fn raylib_key(key: Key) -> KeyboardKey {
+    use KeyboardKey::*;
+    match key {
+        Key::A => KEY_A, Key::B => KEY_B, Key::C => KEY_C, Key::D => KEY_D, Key::E => KEY_E, Key::F => KEY_F,
+        Key::G => KEY_G, Key::H => KEY_H, Key::I => KEY_I, Key::J => KEY_J, Key::K => KEY_K, Key::L => KEY_L,
+        Key::M => KEY_M, Key::N => KEY_N, Key::O => KEY_O, Key::P => KEY_P, Key::Q => KEY_Q, Key::R => KEY_R,
+        Key::S => KEY_S, Key::T => KEY_T, Key::U => KEY_U, Key::V => KEY_V, Key::W => KEY_W, Key::X => KEY_X,
+        Key::Y => KEY_Y, Key::Z => KEY_Z,
+        Key::Digit0 => KEY_ZERO, Key::Digit1 => KEY_ONE, Key::Digit2 => KEY_TWO, Key::Digit3 => KEY_THREE, Key::Digit4 => KEY_FOUR,
+        Key::Digit5 => KEY_FIVE, Key::Digit6 => KEY_SIX, Key::Digit7 => KEY_SEVEN, Key::Digit8 => KEY_EIGHT, Key::Digit9 => KEY_NINE,
+        Key::F1 => KEY_F1, Key::F2 => KEY_F2, Key::F3 => KEY_F3, Key::F4 => KEY_F4, Key::F5 => KEY_F5, Key::F6 => KEY_F6,
+        Key::F7 => KEY_F7, Key::F8 => KEY_F8, Key::F9 => KEY_F9, Key::F10 => KEY_F10, Key::F11 => KEY_F11, Key::F12 => KEY_F12,
+        Key::Space => KEY_SPACE, Key::Enter => KEY_ENTER, Key::Escape => KEY_ESCAPE, Key::Tab => KEY_TAB,
+        Key::Backspace => KEY_BACKSPACE, Key::Delete => KEY_DELETE, Key::Insert => KEY_INSERT,
+        Key::Left => KEY_LEFT, Key::Right => KEY_RIGHT, Key::Up => KEY_UP, Key::Down => KEY_DOWN,
+        Key::Home => KEY_HOME, Key::End => KEY_END, Key::PageUp => KEY_PAGE_UP, Key::PageDown => KEY_PAGE_DOWN,
+        Key::Minus => KEY_MINUS, Key::Equal => KEY_EQUAL, Key::LeftBracket => KEY_LEFT_BRACKET, Key::RightBracket => KEY_RIGHT_BRACKET,
+        Key::Backslash => KEY_BACKSLASH, Key::Semicolon => KEY_SEMICOLON, Key::Apostrophe => KEY_APOSTROPHE,
+        Key::Comma => KEY_COMMA, Key::Period => KEY_PERIOD, Key::Slash => KEY_SLASH, Key::Grave => KEY_GRAVE,
+        Key::LeftShift => KEY_LEFT_SHIFT, Key::RightShift => KEY_RIGHT_SHIFT,
+        Key::LeftControl => KEY_LEFT_CONTROL, Key::RightControl => KEY_RIGHT_CONTROL,
+        Key::LeftAlt => KEY_LEFT_ALT, Key::RightAlt => KEY_RIGHT_ALT,
+        Key::LeftSuper => KEY_LEFT_SUPER, Key::RightSuper => KEY_RIGHT_SUPER,
+    }
+}
+
 impl InputBackend for RaylibInputBackend<'_> {
     #[inline]
     fn mouse_position(&mut self) -> Point {
@@ -26,13 +81,23 @@ impl InputBackend for RaylibInputBackend<'_> {
     }
 
     #[inline]
-    fn is_m1_pressed(&mut self) -> bool {
-        self.0.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT)
+    fn time(&mut self) -> f64 {
+        self.0.get_time()
+    }
+
+    #[inline]
+    fn is_mouse_button_down(&mut self, button: amygui::prelude::MouseButton) -> bool {
+        self.0.is_mouse_button_down(raylib_mouse_button(button))
+    }
+
+    #[inline]
+    fn is_mouse_button_pressed(&mut self, button: amygui::prelude::MouseButton) -> bool {
+        self.0.is_mouse_button_pressed(raylib_mouse_button(button))
     }
 
     #[inline]
-    fn is_m1_released(&mut self) -> bool {
-        self.0.is_mouse_button_released(MouseButton::MOUSE_BUTTON_LEFT)
+    fn is_mouse_button_released(&mut self, button: amygui::prelude::MouseButton) -> bool {
+        self.0.is_mouse_button_released(raylib_mouse_button(button))
     }
 
     #[inline]
@@ -40,43 +105,221 @@ impl InputBackend for RaylibInputBackend<'_> {
         let ffi::Vector2 { x, y } = self.0.get_mouse_wheel_move_v();
         Point { x, y }
     }
+
+    #[inline]
+    fn is_key_down(&mut self, key: Key) -> bool {
+        self.0.is_key_down(raylib_key(key))
+    }
+
+    #[inline]
+    fn is_key_pressed(&mut self, key: Key) -> bool {
+        self.0.is_key_pressed(raylib_key(key))
+    }
+
+    #[inline]
+    fn is_key_pressed_repeat(&mut self, key: Key) -> bool {
+        unsafe { ffi::IsKeyPressedRepeat(raylib_key(key) as i32) }
+    }
+
+    #[inline]
+    fn is_key_released(&mut self, key: Key) -> bool {
+        self.0.is_key_released(raylib_key(key))
+    }
+
+    #[inline]
+    fn char_pressed(&mut self) -> Option<char> {
+        // 0 when the queue is empty
+        let ch = unsafe { ffi::GetCharPressed() };
+        u32::try_from(ch).ok().filter(|&ch| ch != 0).and_then(char::from_u32)
+    }
 }
 
-pub struct RaylibTickBackend<'a>(&'a mut RaylibHandle, &'a RaylibThread, );
+pub struct RaylibTickBackend<'a>(&'a mut RaylibHandle, &'a RaylibThread, &'a mut Document, &'a Theme<Color>);
 
-impl TickBackend for RaylibTickBackend<'_> {}
+impl TickBackend for RaylibTickBackend<'_> {
+    type Color = Color;
 
-pub struct RaylibDrawBackend<'a, 'b, 'c>(&'a mut RaylibDrawHandle<'b>, &'c mut RasterTable, &'c mut EffectTable, &'c mut LayerTree);
+    #[inline]
+    fn theme(&self) -> &Theme<Color> {
+        self.3
+    }
+
+    #[inline]
+    fn clipboard_text(&mut self) -> Option<String> {
+        self.0.get_clipboard_text().ok()
+    }
+
+    #[inline]
+    fn set_clipboard_text(&mut self, text: &str) {
+        // text containing a nul can't be copied; nothing useful to do about it
+        _ = self.0.set_clipboard_text(text);
+    }
+}
+
+pub struct RaylibDrawBackend<'a, 'b, 'c>(&'a mut RaylibDrawHandle<'b>, &'c mut RasterTable, &'c mut EffectTable, &'c mut LayerTree, DrawState, &'c Theme<Color>, Option<&'c Selection>);
+
+#[inline]
+const fn raylib_rec(rect: &Rect) -> Rectangle {
+    Rectangle {
+        x: rect.x_min,
+        y: rect.y_min,
+        width: rect.width(),
+        height: rect.height(),
+    }
+}
+
+/// Raylib's roundness is a fraction of the shorter side instead of a radius.
+#[inline]
+fn raylib_roundness(rect: &Rect, radius: f32) -> f32 {
+    let shorter = rect.width().min(rect.height());
+    if shorter > 0.0 { (radius * 2.0 / shorter).clamp(0.0, 1.0) } else { 0.0 }
+}
+
+const ROUNDED_RECT_SEGMENTS: i32 = 8;
 
 impl DrawBackend for RaylibDrawBackend<'_, '_, '_> {
     type Color = Color;
+    type Image = ffi::Texture2D;
+
+    #[inline]
+    fn theme(&self) -> &Theme<Color> {
+        self.5
+    }
 
     #[inline]
     fn draw_rect(&mut self, rect: &Rect, color: &Self::Color) {
-        self.0.draw_rectangle_rec(Rectangle {
-            x: rect.x_min,
-            y: rect.y_min,
-            width: rect.width(),
-            height: rect.height(),
-        }, color);
+        self.0.draw_rectangle_rec(raylib_rec(rect), color);
+    }
+
+    #[inline]
+    fn draw_rect_outline(&mut self, rect: &Rect, thickness: f32, color: &Self::Color) {
+        self.0.draw_rectangle_lines_ex(raylib_rec(rect), thickness, color);
+    }
+
+    #[inline]
+    fn draw_rounded_rect(&mut self, rect: &Rect, radius: f32, color: &Self::Color) {
+        self.0.draw_rectangle_rounded(raylib_rec(rect), raylib_roundness(rect, radius), ROUNDED_RECT_SEGMENTS, color);
+    }
+
+    #[inline]
+    fn draw_rounded_rect_outline(&mut self, rect: &Rect, radius: f32, thickness: f32, color: &Self::Color) {
+        // raylib draws rounded outlines outside the rectangle, unlike `draw_rectangle_lines_ex`
+        let inset = Rect {
+            x_min: rect.x_min + thickness,
+            y_min: rect.y_min + thickness,
+            x_max: rect.x_max - thickness,
+            y_max: rect.y_max - thickness,
+        };
+        let radius = (radius - thickness).max(0.0);
+        unsafe {
+            ffi::DrawRectangleRoundedLinesEx(raylib_rec(&inset).into(), raylib_roundness(&inset, radius), ROUNDED_RECT_SEGMENTS, thickness, (*color).into());
+        }
+    }
+
+    #[inline]
+    fn draw_line(&mut self, start: Point, end: Point, thickness: f32, color: &Self::Color) {
+        self.0.draw_line_ex(Vector2::new(start.x, start.y), Vector2::new(end.x, end.y), thickness, color);
+    }
+
+    #[inline]
+    fn draw_image(&mut self, image: &Self::Image, source: Option<&Rect>, dest: &Rect, tint: &Self::Color) {
+        let source = source.map_or_else(
+            || Rectangle::new(0.0, 0.0, image.width as f32, image.height as f32),
+            raylib_rec,
+        );
+        unsafe {
+            ffi::DrawTexturePro(*image, source.into(), raylib_rec(dest).into(), ffi::Vector2 { x: 0.0, y: 0.0 }, 0.0, (*tint).into());
+        }
     }
 
     #[inline]
     fn draw_text(&mut self, text: &str, top_left: Point, font_size: f32, color: &Self::Color) {
         self.0.draw_text(text, top_left.x as i32, top_left.y as i32, font_size as i32, color);
     }
+
+    #[inline]
+    fn measure_text(&mut self, text: &str, font_size: f32) -> f32 {
+        measure_text(text, font_size as i32) as f32
+    }
+
+    fn push_clip(&mut self, rect: &Rect) {
+        let rect = self.4.push_clip(rect);
+        begin_scissor(&rect);
+    }
+
+    fn pop_clip(&mut self) {
+        match self.4.pop_clip() {
+            Some(outer) => begin_scissor(outer),
+            None => unsafe { ffi::EndScissorMode() },
+        }
+    }
+
+    fn push_transform(&mut self, transform: &amygui::prelude::Transform) {
+        // scissor rectangles aren't affected by the matrix stack, so `DrawState` keeps track of them separately
+        self.4.push_transform(transform);
+        unsafe {
+            ffi::rlPushMatrix();
+            ffi::rlTranslatef(transform.offset.x, transform.offset.y, 0.0);
+            ffi::rlScalef(transform.scale, transform.scale, 1.0);
+        }
+    }
+
+    fn pop_transform(&mut self) {
+        self.4.pop_transform();
+        unsafe { ffi::rlPopMatrix(); }
+    }
+}
+
+/// Scissor modes don't nest in raylib, so starting a new one replaces the current one.
+fn begin_scissor(rect: &Rect) {
+    unsafe {
+        ffi::BeginScissorMode(
+            rect.x_min.floor() as i32,
+            rect.y_min.floor() as i32,
+            rect.width().ceil() as i32,
+            rect.height().ceil() as i32,
+        );
+    }
 }
 
 impl_guinode_union!{
     pub enum(T) UINode<T> {
         AmyGUI(AmyGUINode<Color, T>),
         Viewport(ViewportNode),
+        Layers(LayersPanelNode),
     }
     impl(T: Node) Node;
     impl('a, T: TickNode<RaylibTickBackend<'a>>) Tick<(RaylibTickBackend<'a>)>;
     impl('a, 'b, 'c, T: DrawNode<RaylibDrawBackend<'a, 'b, 'c>>) Draw<(RaylibDrawBackend<'a, 'b, 'c>)>;
 }
 
+const THEME_PATH: &str = "amy.theme";
+const DEFAULT_THEME: &str = include_str!("../themes/default.theme");
+
+const fn theme_color([r, g, b, a]: [u8; 4]) -> Color {
+    Color::new(r, g, b, a)
+}
+
+/// The user's theme if they have one, otherwise the built-in one.
+fn load_theme() -> Theme<Color> {
+    match Theme::load(THEME_PATH, theme_color) {
+        Ok(theme) => theme,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("failed to load {THEME_PATH}: {e}");
+            }
+            Theme::parse(DEFAULT_THEME, theme_color).expect("built-in theme should be valid")
+        }
+    }
+}
+
+/// Switch to an opened document, dropping anything the viewport was transforming in the old one.
+fn replace_document(gui: &mut OverlayBox<UINode<Empty>>, doc: &mut Document, new_doc: Document) {
+    let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
+    viewport.cancel_transform(doc);
+    *doc = new_doc;
+}
+
 #[allow(clippy::cognitive_complexity)]
 fn main() {
     let (mut rl, thread) = init()
@@ -88,13 +331,7 @@ fn main() {
     rl.set_target_fps(60);
     rl.maximize_window();
 
-    const STYLE: ButtonStyle<Color> = ButtonStyle {
-        disabled_color: Color::GRAY,
-        normal_color: Color::new(96,96,96,255),
-        hover_color: Color::new(128,128,128,255),
-        press_color: Color::new(200,200,200,255),
-    };
-    let mut gui = OverlayBox::from_iter([
+    let mut gui: OverlayBox<UINode<Empty>> = OverlayBox::from_iter([
         UINode::Viewport(ViewportNode::new(
             Brush::new(BrushPreset::new(const { unsafe { NonZeroU16::new_unchecked(1) } }, Color::BLACK)),
             Camera2D {
@@ -104,49 +341,60 @@ fn main() {
                 zoom: 1.0,
             }
         )),
-        UINode::AmyGUI(AmyGUINode::PadBox(padding!(5.0, UniformGridNode::from_iter(
-            24.0, 24.0,  // item size
-            3.0, 3.0,    // gap
-            const { unsafe { NonZeroU32::new_unchecked(3) } }, // columns
-            [
-                Button::new(Empty, STYLE, |_| {}),
-                Button::new(Empty, STYLE, |_| {}),
-
-                Button::new(Empty, STYLE, |_| {}),
-                Button::new(Empty, STYLE, |_| {}),
-
-                Button::new(Empty, STYLE, |_| {}),
-                Button::new(Empty, STYLE, |_| {}),
-
-                Button::new(Empty, STYLE, |_| {}),
-                Button::new(Empty, STYLE, |_| {}),
-
-                Button::new(Empty, STYLE, |_| {}),
-                Button::new(Empty, STYLE, |_| {}),
-            ],
-        )))),
+        UINode::Layers(LayersPanelNode::new()),
     ]);
 
-    let mut rasters = RasterTable::new(const { unsafe { Canvas::new_unchecked(128, 128) } });
-    let mut effects = EffectTable::new();
-    let mut layer_tree = LayerTree::new();
+    let mut doc = Document::new(const { unsafe { Canvas::new_unchecked(128, 128) } });
 
     {
-        let raster0 = rasters.create_raster(&mut rl, &thread);
-        let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
-        viewport.brush.set_target(raster0.clone());
-        layer_tree.push(Layer::new(LayerContent::new_raster(raster0)));
+        let raster0 = doc.rasters.create_raster(&mut rl, &thread);
+        doc.layers.push(Layer::new(LayerContent::new_raster(raster0)));
+        doc.active_layer = Some(vec![0]);
     }
 
+    let mut theme = load_theme();
+
+    const PROJECT_PATH: &str = "untitled.amyr";
+    const EXPORT_PATH: &str = "untitled.png";
+    const EXPORT_ORA_PATH: &str = "untitled.ora";
+    const EXPORT_LAYERS_DIR: &str = "untitled_layers";
+    const BRUSH_TIP_PATH: &str = "brush.png";
+
+    let mut input_state = InputState::new();
+    // what the brush goes back to when the eraser is toggled off
+    let mut blend_before_eraser = BlendModeA::Alpha;
     while !rl.window_should_close() {
-        // brush size
-        if let Some(new_size) = rl.get_key_pressed()
-            .map(|key| key as i32 - KeyboardKey::KEY_ONE as i32 + 1)
-            .filter(|n| (1..=9).contains(n))
-            .map(|n| NonZeroU16::new(u16::try_from(n).unwrap()).unwrap())
-        {
-            let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
-            viewport.brush.preset.size = new_size;
+        // import dropped images
+        if rl.is_file_dropped() {
+            let dropped = unsafe { ffi::LoadDroppedFiles() };
+            for i in 0..dropped.count as usize {
+                let path = unsafe { std::ffi::CStr::from_ptr(*dropped.paths.add(i)) }.to_string_lossy().into_owned();
+                if path.to_lowercase().ends_with(".amyr") {
+                    match Document::load(&mut rl, &thread, &path) {
+                        Ok(new_doc) => replace_document(&mut gui, &mut doc, new_doc),
+                        Err(e) => eprintln!("failed to open {path}: {e}"),
+                    }
+                } else if path.to_lowercase().ends_with(".ora") {
+                    match file::ora::import_ora(&mut rl, &thread, &path) {
+                        Ok(new_doc) => replace_document(&mut gui, &mut doc, new_doc),
+                        Err(e) => eprintln!("failed to open {path}: {e}"),
+                    }
+                } else {
+                    // an image dropped on the startup canvas becomes the canvas, rather than being cropped to it
+                    let fit_canvas = doc.is_untouched();
+                    match file::image::import_image(&mut rl, &thread, &mut doc, &path, fit_canvas) {
+                        Ok((_, is_cropped)) => {
+                            doc.active_layer = doc.layers.topmost_raster();
+                            if is_cropped {
+                                let canvas = doc.rasters.canvas();
+                                eprintln!("{path} is larger than the canvas, so it was cropped to {}x{}", canvas.get_w(), canvas.get_h());
+                            }
+                        }
+                        Err(e) => eprintln!("failed to import {path}: {e}"),
+                    }
+                }
+            }
+            unsafe { ffi::UnloadDroppedFiles(dropped); }
         }
 
         let window_rec = Rect {
@@ -155,24 +403,200 @@ fn main() {
             x_max: rl.get_screen_width () as f32,
             y_max: rl.get_screen_height() as f32,
         };
-        let mut ui_events = Events::check(&mut RaylibInputBackend(&rl));
+        let mut ui_events = Events::check(&mut RaylibInputBackend(&rl), &mut input_state);
 
-        gui.dibs_tick(&mut RaylibTickBackend(&mut rl, &thread), window_rec, &mut ui_events);
-        gui.active_tick(&mut RaylibTickBackend(&mut rl, &thread), window_rec, &mut ui_events);
+        gui.dibs_tick(&mut RaylibTickBackend(&mut rl, &thread, &mut doc, &theme), window_rec, &mut ui_events);
+        gui.active_tick(&mut RaylibTickBackend(&mut rl, &thread, &mut doc, &theme), window_rec, &mut ui_events);
+        input_state.end_frame(&ui_events);
 
-        // update layer buffers
+        // shortcuts get whatever keys the gui left behind
+
+        // save
+        if ui_events.take_shortcut(Key::S, Modifiers::CTRL).is_some() {
+            // anything still being transformed is stamped down first, or the file would get the hole it left behind
+            let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
+            viewport.commit_transform(&mut doc);
+            if let Err(e) = doc.save(PROJECT_PATH) {
+                eprintln!("failed to save {PROJECT_PATH}: {e}");
+            }
+        }
+
+        // reopen the last save
+        if ui_events.take_shortcut(Key::O, Modifiers::CTRL).is_some() {
+            match Document::load(&mut rl, &thread, PROJECT_PATH) {
+                Ok(new_doc) => replace_document(&mut gui, &mut doc, new_doc),
+                Err(e) => eprintln!("failed to open {PROJECT_PATH}: {e}"),
+            }
+        }
+
+        // export
+        if ui_events.take_shortcut(Key::E, Modifiers::CTRL_SHIFT).is_some() {
+            let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
+            viewport.commit_transform(&mut doc);
+            if let Err(e) = file::ora::export_ora(&mut rl, &thread, &mut doc, EXPORT_ORA_PATH) {
+                eprintln!("failed to export {EXPORT_ORA_PATH}: {e}");
+            }
+        } else if ui_events.take_shortcut(Key::E, Modifiers::CTRL_ALT).is_some() {
+            let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
+            viewport.commit_transform(&mut doc);
+            if let Err(e) = file::image::export_layers_png(&doc, EXPORT_LAYERS_DIR) {
+                eprintln!("failed to export layers to {EXPORT_LAYERS_DIR}: {e}");
+            }
+        } else if ui_events.take_shortcut(Key::E, Modifiers::CTRL).is_some() {
+            let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
+            viewport.commit_transform(&mut doc);
+            if let Err(e) = file::image::export_png(&mut rl, &thread, &mut doc, EXPORT_PATH) {
+                eprintln!("failed to export {EXPORT_PATH}: {e}");
+            }
+        }
+
+        // switch between the theme's palettes
+        if ui_events.take_shortcut(Key::T, Modifiers::CTRL_SHIFT).is_some() {
+            theme.next_palette();
+        }
+
+        // undo/redo
+        if ui_events.take_shortcut(Key::Z, Modifiers::CTRL).is_some() {
+            let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
+            // undoing while something is being transformed puts it back instead
+            if !viewport.cancel_transform(&mut doc) {
+                doc.undo();
+            }
+        } else if ui_events.take_shortcut(Key::Z, Modifiers::CTRL_SHIFT).or_else(|| ui_events.take_shortcut(Key::Y, Modifiers::CTRL)).is_some() {
+            let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
+            viewport.cancel_transform(&mut doc);
+            doc.redo();
+        }
+
+        // eraser
+        if ui_events.take_shortcut(Key::E, Modifiers::NONE).is_some() {
+            let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
+            let preset = &mut viewport.brush.preset;
+            if preset.is_eraser() {
+                preset.blend = blend_before_eraser;
+            } else {
+                blend_before_eraser = std::mem::replace(&mut preset.blend, BlendModeA::ERASE);
+            }
+        }
+
+        // tools
+        {
+            let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
+            if ui_events.take_shortcut(Key::B, Modifiers::NONE).is_some() {
+                viewport.tool = Tool::Brush;
+            } else if ui_events.take_shortcut(Key::G, Modifiers::NONE).is_some() {
+                viewport.tool = Tool::Fill;
+            } else if ui_events.take_shortcut(Key::U, Modifiers::NONE).is_some() {
+                // pressing it again goes through the shapes
+                viewport.tool = match viewport.tool {
+                    Tool::Shape(shape) => Tool::Shape(shape.next()),
+                    _ => Tool::Shape(Shape::Line),
+                };
+            } else if ui_events.take_shortcut(Key::M, Modifiers::NONE).is_some() {
+                viewport.tool = match viewport.tool {
+                    Tool::Select(SelectionTool::Rectangle) => Tool::Select(SelectionTool::Ellipse),
+                    _ => Tool::Select(SelectionTool::Rectangle),
+                };
+            } else if ui_events.take_shortcut(Key::L, Modifiers::NONE).is_some() {
+                viewport.tool = match viewport.tool {
+                    Tool::Select(SelectionTool::Lasso) => Tool::Select(SelectionTool::PolygonalLasso),
+                    _ => Tool::Select(SelectionTool::Lasso),
+                };
+            } else if ui_events.take_shortcut(Key::W, Modifiers::NONE).is_some() {
+                viewport.tool = Tool::Select(SelectionTool::MagicWand);
+            } else if ui_events.take_shortcut(Key::V, Modifiers::NONE).is_some() {
+                viewport.tool = Tool::Transform;
+            } else if ui_events.take_shortcut(Key::I, Modifiers::NONE).is_some() {
+                viewport.tool = Tool::Eyedropper;
+            }
+
+            // eyedropper options
+            if ui_events.take_shortcut(Key::I, Modifiers::ALT).is_some() {
+                viewport.pick_merged = !viewport.pick_merged;
+            }
+
+            // brush options
+            if ui_events.take_shortcut(Key::B, Modifiers::CTRL).is_some() {
+                match BrushTip::load(BRUSH_TIP_PATH) {
+                    Ok(tip) => viewport.brush.preset.tip = tip,
+                    Err(e) => eprintln!("failed to load {BRUSH_TIP_PATH}: {e}"),
+                }
+            } else if ui_events.take_shortcut(Key::B, Modifiers::CTRL_SHIFT).is_some() {
+                viewport.brush.preset.tip = BrushTip::Round;
+            }
+
+            // transform options
+            if ui_events.take_shortcut(Key::V, Modifiers::SHIFT).is_some() {
+                viewport.resample = match viewport.resample {
+                    Resample::Nearest => Resample::Bilinear,
+                    Resample::Bilinear => Resample::Nearest,
+                };
+            }
+
+            // shape options
+            if ui_events.take_shortcut(Key::U, Modifiers::SHIFT).is_some() {
+                viewport.fill_shapes = !viewport.fill_shapes;
+            }
+
+            // fill options
+            if ui_events.take_shortcut(Key::G, Modifiers::SHIFT).is_some() {
+                viewport.fill.mode = match viewport.fill.mode {
+                    FillMode::Contiguous => FillMode::Global,
+                    FillMode::Global => FillMode::Contiguous,
+                };
+            }
+            if ui_events.take_shortcut(Key::G, Modifiers::ALT).is_some() {
+                viewport.fill.sample_merged = !viewport.fill.sample_merged;
+            }
+            // the magic wand picks pixels the same way as the fill
+            if matches!(viewport.tool, Tool::Fill | Tool::Select(SelectionTool::MagicWand)) {
+                const TOLERANCE_STEP: u8 = 8;
+                if ui_events.take_shortcut(Key::LeftBracket, Modifiers::NONE).is_some() {
+                    viewport.fill.tolerance = viewport.fill.tolerance.saturating_sub(TOLERANCE_STEP);
+                } else if ui_events.take_shortcut(Key::RightBracket, Modifiers::NONE).is_some() {
+                    viewport.fill.tolerance = viewport.fill.tolerance.saturating_add(TOLERANCE_STEP);
+                }
+            }
+        }
+
+        // selection
+        if ui_events.take_shortcut(Key::A, Modifiers::CTRL).is_some() {
+            doc.select_all();
+        } else if ui_events.take_shortcut(Key::D, Modifiers::CTRL).is_some() {
+            doc.deselect();
+        } else if ui_events.take_shortcut(Key::I, Modifiers::CTRL_SHIFT).is_some() {
+            doc.invert_selection();
+        }
+
+        // brush size
+        if let Some(new_size) = (1..=9)
+            .find(|&n| ui_events.take_shortcut(Key::DIGITS[n], Modifiers::NONE).is_some())
+            .map(|n| NonZeroU16::new(n as u16).unwrap())
         {
-            for layer in layer_tree.layers_mut() {
-                layer.update_buffers(&mut rl, &thread, rasters.canvas());
+            let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
+            viewport.brush.preset.size = new_size;
+        }
+
+        // keep the brush on the active layer
+        {
+            let UINode::Viewport(viewport) = &mut gui.content[0] else { panic!("you forgot to update this") };
+            match doc.active_raster() {
+                Some(raster) => if !viewport.brush.target().is_some_and(|target| RcRaster::ptr_eq(target, &raster)) {
+                    viewport.brush.set_target(raster);
+                }
+                None => _ = viewport.brush.remove_target(),
             }
         }
 
+        // update layer buffers
+        doc.layers.update_buffers(&mut rl, &thread, doc.rasters.canvas());
+
         // draw frame
         {
             let mut d = rl.begin_drawing(&thread);
             d.clear_background(Color::BLACK);
 
-            gui.draw(&mut RaylibDrawBackend(&mut d, &mut rasters, &mut effects, &mut layer_tree), window_rec);
+            gui.draw(&mut RaylibDrawBackend(&mut d, &mut doc.rasters, &mut doc.effects, &mut doc.layers, DrawState::new(), &theme, doc.selection.as_ref()), window_rec);
         }
     }
 }
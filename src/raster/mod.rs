@@ -4,3 +4,111 @@ use raylib::prelude::*;
 pub type Raster = RenderTexture2D;
 pub type RcRaster = Rc<RefCell<Raster>>;
 pub type WeakRaster = Weak<RefCell<Raster>>;
+
+/// CPU-side copy of a block of pixels, stored top row first.
+#[derive(Clone)]
+pub struct Pixels {
+    pub w: u32,
+    pub h: u32,
+    pub data: Vec<Color>,
+}
+
+impl Pixels {
+    pub fn new(w: u32, h: u32, fill: Color) -> Self {
+        Self {
+            w,
+            h,
+            data: vec![fill; w as usize * h as usize],
+        }
+    }
+
+    /// Copy pixels out of a CPU image, converting to RGBA8 if needed.
+    pub fn from_image(image: &Image) -> Self {
+        unsafe {
+            let mut raw = ffi::ImageCopy(**image);
+            ffi::ImageFormat(&mut raw, PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8A8 as i32);
+            let (w, h) = (raw.width as u32, raw.height as u32);
+            let data = std::slice::from_raw_parts(raw.data as *const Color, w as usize * h as usize).to_vec();
+            ffi::UnloadImage(raw);
+            Self { w, h, data }
+        }
+    }
+
+    /// Copy pixels into a new RGBA8 CPU image.
+    pub fn to_image(&self) -> Image {
+        let mut image = Image::gen_image_color(self.w as i32, self.h as i32, Color::BLANK);
+        unsafe {
+            std::slice::from_raw_parts_mut(image.data as *mut Color, self.data.len()).copy_from_slice(&self.data);
+        }
+        image
+    }
+
+    #[inline]
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        self.data[(y * self.w + x) as usize]
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: u32, y: u32, color: Color) {
+        self.data[(y * self.w + x) as usize] = color;
+    }
+
+    /// Copy out a sub-rectangle. The rectangle must lie within the pixels.
+    pub fn crop(&self, x: u32, y: u32, w: u32, h: u32) -> Self {
+        debug_assert!(x + w <= self.w && y + h <= self.h, "crop rectangle should be within bounds");
+        let mut data = Vec::with_capacity(w as usize * h as usize);
+        for row in y..y + h {
+            let start = (row * self.w + x) as usize;
+            data.extend_from_slice(&self.data[start..start + w as usize]);
+        }
+        Self { w, h, data }
+    }
+
+    /// Divide color by alpha, for pixels composited with color multiplied by alpha.
+    pub fn unpremultiply(&mut self) {
+        for px in &mut self.data {
+            if px.a != 0 && px.a != 255 {
+                let a = u16::from(px.a);
+                let div = |c: u8| ((u16::from(c) * 255 + a / 2) / a).min(255) as u8;
+                (px.r, px.g, px.b) = (div(px.r), div(px.g), div(px.b));
+            }
+        }
+    }
+
+    /// The same pixels stored bottom row first, the way render textures keep them.
+    fn flipped_rows(&self) -> Vec<Color> {
+        let mut data = Vec::with_capacity(self.data.len());
+        for row in self.data.chunks_exact(self.w as usize).rev() {
+            data.extend_from_slice(row);
+        }
+        data
+    }
+}
+
+/// Read the raster back from the GPU.
+///
+/// Render textures are stored upside-down, so the rows are flipped to put the top row first.
+pub fn read_pixels(raster: &Raster) -> Pixels {
+    let mut pixels = unsafe {
+        let image = Image::from_raw(ffi::LoadImageFromTexture(raster.texture));
+        Pixels::from_image(&image)
+    };
+    pixels.data = pixels.flipped_rows();
+    pixels
+}
+
+/// Overwrite the entire raster. `pixels` must be the same size as the raster.
+pub fn write_pixels(raster: &mut Raster, pixels: &Pixels) {
+    debug_assert!(pixels.w == raster.texture.width as u32 && pixels.h == raster.texture.height as u32, "pixels should match raster size");
+    let data = pixels.flipped_rows();
+    unsafe { ffi::UpdateTexture(raster.texture, data.as_ptr().cast()); }
+}
+
+/// Overwrite the part of the raster whose top left corner is at (`x`, `y`).
+pub fn write_pixels_at(raster: &mut Raster, x: u32, y: u32, pixels: &Pixels) {
+    let raster_h = raster.texture.height as u32;
+    debug_assert!(x + pixels.w <= raster.texture.width as u32 && y + pixels.h <= raster_h, "pixels should fit in raster");
+    let data = pixels.flipped_rows();
+    let rec = Rectangle::new(x as f32, (raster_h - y - pixels.h) as f32, pixels.w as f32, pixels.h as f32);
+    unsafe { ffi::UpdateTextureRec(raster.texture, rec.into(), data.as_ptr().cast()); }
+}
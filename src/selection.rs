@@ -0,0 +1,416 @@
+use amygui::prelude::Modifiers;
+use raylib::prelude::*;
+use crate::{brush::{AmyBlendModeExt, BlendModeA}, raster::{Pixels, RcRaster}, shape::{constrain_square, Shape, ShapeDraft}};
+
+/// How a new selection combines with the one already there.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SelectionOp {
+    #[default]
+    Replace,
+    Add,
+    Subtract,
+    Intersect,
+}
+
+impl SelectionOp {
+    /// Shift adds, Alt subtracts, and both together intersect. Otherwise the selection is replaced.
+    pub const fn from_modifiers(modifiers: Modifiers) -> Self {
+        match (modifiers.shift, modifiers.alt) {
+            (false, false) => Self::Replace,
+            (true, false) => Self::Add,
+            (false, true) => Self::Subtract,
+            (true, true) => Self::Intersect,
+        }
+    }
+}
+
+/// Which pixels of the canvas are selected, one per pixel, top row first.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Mask {
+    pub w: u32,
+    pub h: u32,
+    pub data: Vec<bool>,
+}
+
+impl Mask {
+    pub fn new(w: u32, h: u32, is_selected: bool) -> Self {
+        Self {
+            w,
+            h,
+            data: vec![is_selected; w as usize * h as usize],
+        }
+    }
+
+    /// Wrap a region such as one from [`crate::fill::region`].
+    pub fn from_region(w: u32, h: u32, region: Vec<bool>) -> Self {
+        debug_assert_eq!(region.len(), w as usize * h as usize, "region should be the size of the mask");
+        Self { w, h, data: region }
+    }
+
+    /// The pixels whose centers are inside the polygon, by the even-odd rule.
+    pub fn polygon(w: u32, h: u32, points: &[Vector2]) -> Self {
+        let mut mask = Self::new(w, h, false);
+        if points.len() < 3 {
+            return mask;
+        }
+        let mut crossings = Vec::new();
+        for y in 0..h {
+            let center_y = y as f32 + 0.5;
+            crossings.clear();
+            for (i, &a) in points.iter().enumerate() {
+                let b = points[(i + 1) % points.len()];
+                if (a.y <= center_y) != (b.y <= center_y) {
+                    crossings.push(a.x + (center_y - a.y) / (b.y - a.y) * (b.x - a.x));
+                }
+            }
+            crossings.sort_by(f32::total_cmp);
+            for span in crossings.chunks_exact(2) {
+                let x0 = ((span[0] - 0.5).ceil().max(0.0) as u32).min(w);
+                let x1 = ((span[1] - 0.5).ceil().max(0.0) as u32).min(w);
+                let row = (y * w) as usize;
+                mask.data[row + x0 as usize..row + x1 as usize].fill(true);
+            }
+        }
+        mask
+    }
+
+    #[inline]
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        self.data[(y * self.w + x) as usize]
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        !self.data.contains(&true)
+    }
+
+    /// The smallest rectangle containing every selected pixel, as `(x, y, w, h)`, or [`None`] if nothing is selected.
+    pub fn bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        let w = self.w as usize;
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for (i, _) in self.data.iter().enumerate().filter(|(_, is_selected)| **is_selected) {
+            let (x, y) = (i % w, i / w);
+            bounds = Some(match bounds {
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+        bounds.map(|(x0, y0, x1, y1)| (x0 as u32, y0 as u32, (x1 - x0 + 1) as u32, (y1 - y0 + 1) as u32))
+    }
+
+    /// Combine `other` into this mask. Both must be the same size.
+    pub fn combine(&mut self, other: &Self, op: SelectionOp) {
+        debug_assert!(self.w == other.w && self.h == other.h, "masks should be the same size");
+        for (a, &b) in self.data.iter_mut().zip(&other.data) {
+            *a = match op {
+                SelectionOp::Replace => b,
+                SelectionOp::Add => *a || b,
+                SelectionOp::Subtract => *a && !b,
+                SelectionOp::Intersect => *a && b,
+            };
+        }
+    }
+
+    pub fn invert(&mut self) {
+        for is_selected in &mut self.data {
+            *is_selected = !*is_selected;
+        }
+    }
+
+    /// Segments along the edges between selected and unselected pixels, with neighboring edges joined into one.
+    /// The canvas border counts as unselected.
+    pub fn outline(&self) -> Vec<[Vector2; 2]> {
+        let (w, h) = (self.w as i64, self.h as i64);
+        let at = |x: i64, y: i64| (0..w).contains(&x) && (0..h).contains(&y) && self.get(x as u32, y as u32);
+        let mut segments = Vec::new();
+
+        // edges along the top of each row, then along the left of each column
+        for y in 0..=h {
+            let mut start = None;
+            for x in 0..=w {
+                let is_edge = x < w && at(x, y - 1) != at(x, y);
+                match (start, is_edge) {
+                    (None, true) => start = Some(x),
+                    (Some(x0), false) => {
+                        segments.push([Vector2::new(x0 as f32, y as f32), Vector2::new(x as f32, y as f32)]);
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for x in 0..=w {
+            let mut start = None;
+            for y in 0..=h {
+                let is_edge = y < h && at(x - 1, y) != at(x, y);
+                match (start, is_edge) {
+                    (None, true) => start = Some(y),
+                    (Some(y0), false) => {
+                        segments.push([Vector2::new(x as f32, y0 as f32), Vector2::new(x as f32, y as f32)]);
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        segments
+    }
+
+    /// White where selected and transparent elsewhere.
+    pub fn to_pixels(&self) -> Pixels {
+        Pixels {
+            w: self.w,
+            h: self.h,
+            data: self.data.iter().map(|&is_selected| if is_selected { Color::WHITE } else { Color::BLANK }).collect(),
+        }
+    }
+}
+
+/// The selected part of a document, which painting is confined to.
+pub struct Selection {
+    mask: Mask,
+    /// Cached [`Mask::outline`], for drawing.
+    outline: Vec<[Vector2; 2]>,
+    /// The mask on the GPU for clipping, uploaded the first time it is needed.
+    texture: Option<Texture2D>,
+}
+
+impl Selection {
+    /// Returns [`None`] if nothing is selected.
+    pub fn new(mask: Mask) -> Option<Self> {
+        if mask.is_empty() {
+            return None;
+        }
+        Some(Self {
+            outline: mask.outline(),
+            mask,
+            texture: None,
+        })
+    }
+
+    #[inline]
+    pub fn mask(&self) -> &Mask {
+        &self.mask
+    }
+
+    #[inline]
+    pub fn into_mask(self) -> Mask {
+        self.mask
+    }
+
+    #[inline]
+    pub fn outline(&self) -> &[[Vector2; 2]] {
+        &self.outline
+    }
+
+    fn texture(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) -> &Texture2D {
+        self.texture.get_or_insert_with(|| rl.load_texture_from_image(thread, &self.mask.to_pixels().to_image()).unwrap())
+    }
+}
+
+/// Confines painting to the selection by putting back whatever was outside it before the painting started.
+pub struct SelectionClip {
+    before: Texture2D,
+}
+
+impl SelectionClip {
+    /// Remember the raster as it was before painting, from a copy of its pixels.
+    pub fn begin(rl: &mut RaylibHandle, thread: &RaylibThread, before: &Pixels) -> Self {
+        Self {
+            before: rl.load_texture_from_image(thread, &before.to_image()).unwrap(),
+        }
+    }
+
+    /// Undo anything painted on `raster` outside the selection since [`Self::begin`].
+    pub fn apply(&self, rl: &mut RaylibHandle, thread: &RaylibThread, raster: &RcRaster, selection: &mut Selection) {
+        let mask = **selection.texture(rl, thread);
+        let shader = outside_selection_shader();
+        let mut raster = raster.borrow_mut();
+        let mut d = rl.begin_texture_mode(thread, &mut *raster);
+        let mut d = d.begin_blend_mode_a(BlendModeA::REPLACE);
+        // the mask has to be bound after the shader starts, which flushes the batch and unbinds extra textures
+        unsafe {
+            ffi::BeginShaderMode(shader);
+            ffi::SetShaderValueTexture(shader, ffi::GetShaderLocation(shader, c"selection".as_ptr()), mask);
+        }
+        d.draw_texture(&self.before, 0, 0, Color::WHITE);
+        unsafe { ffi::EndShaderMode(); }
+    }
+}
+
+thread_local! {
+    static OUTSIDE_SELECTION_SHADER: std::cell::OnceCell<ffi::Shader> = const { std::cell::OnceCell::new() };
+}
+
+/// Shader that only draws where the `selection` texture is transparent, for [`SelectionClip`].
+///
+/// Loaded the first time it is needed and kept until the program exits.
+fn outside_selection_shader() -> ffi::Shader {
+    const FRAG: &std::ffi::CStr = c"#version 330
+in vec2 fragTexCoord;
+uniform sampler2D texture0;
+uniform sampler2D selection;
+out vec4 finalColor;
+void main() {
+    if (texture(selection, fragTexCoord).a >= 0.5) discard;
+    finalColor = texture(texture0, fragTexCoord);
+}";
+    OUTSIDE_SELECTION_SHADER.with(|shader| *shader.get_or_init(|| unsafe { ffi::LoadShaderFromMemory(std::ptr::null(), FRAG.as_ptr()) }))
+}
+
+/// Tools that change the selection.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SelectionTool {
+    Rectangle,
+    Ellipse,
+    /// Freehand, following the mouse while it is held.
+    Lasso,
+    /// Straight sides, placed one click at a time.
+    PolygonalLasso,
+    /// Pixels similar in color to the one clicked.
+    MagicWand,
+}
+
+impl SelectionTool {
+    /// Tools that are dragged out, as opposed to clicked.
+    #[inline]
+    pub const fn is_dragged(self) -> bool {
+        matches!(self, Self::Rectangle | Self::Ellipse | Self::Lasso)
+    }
+}
+
+/// A selection while it is being drawn, in canvas coordinates.
+pub struct SelectionDraft {
+    pub tool: SelectionTool,
+    pub op: SelectionOp,
+    /// Where the selection was started, then any corners or lasso points placed since. The last point follows the mouse.
+    pub points: Vec<Vector2>,
+}
+
+impl SelectionDraft {
+    pub fn begin(tool: SelectionTool, op: SelectionOp, point: Vector2) -> Self {
+        debug_assert_ne!(tool, SelectionTool::MagicWand, "the magic wand selects in one click");
+        // rectangles and ellipses go between pixels, so that they select whole pixels
+        let point = match tool {
+            SelectionTool::Rectangle | SelectionTool::Ellipse => Vector2::new(point.x.round(), point.y.round()),
+            _ => point,
+        };
+        Self { tool, op, points: vec![point, point] }
+    }
+
+    /// Follow the mouse to `point`. Rectangles and ellipses are made square and circular if `is_constrained`.
+    pub fn move_to(&mut self, point: Vector2, is_constrained: bool) {
+        match self.tool {
+            SelectionTool::Rectangle | SelectionTool::Ellipse => {
+                let point = Vector2::new(point.x.round(), point.y.round());
+                self.points[1] = if is_constrained { constrain_square(self.points[0], point) } else { point };
+            }
+            SelectionTool::Lasso => {
+                // skip tiny movements so a long lasso doesn't collect thousands of points
+                let [.., placed, last] = self.points.as_mut_slice() else { unreachable!("drafts always have a start and an end") };
+                *last = point;
+                if (*last - *placed).length() >= 1.0 {
+                    self.points.push(point);
+                }
+            }
+            SelectionTool::PolygonalLasso | SelectionTool::MagicWand => {
+                *self.points.last_mut().expect("drafts always have a start and an end") = point;
+            }
+        }
+    }
+
+    /// Place a polygonal lasso corner at the last point and start a new side from it.
+    pub fn add_corner(&mut self) {
+        let last = *self.points.last().expect("drafts always have a start and an end");
+        self.points.push(last);
+    }
+
+    /// Points around the edge of the selection, ending where they started.
+    pub fn outline(&self) -> Vec<Vector2> {
+        let shape = match self.tool {
+            SelectionTool::Rectangle => Shape::Rectangle,
+            SelectionTool::Ellipse => Shape::Ellipse,
+            _ => Shape::Polygon,
+        };
+        ShapeDraft { shape, points: self.points.clone() }.outline()
+    }
+
+    pub fn to_mask(&self, w: u32, h: u32) -> Mask {
+        Mask::polygon(w, h, &self.outline())
+    }
+}
+
+#[cfg(test)]
+mod selection_tests {
+    use super::*;
+
+    fn count(mask: &Mask) -> usize {
+        mask.data.iter().filter(|&&is_selected| is_selected).count()
+    }
+
+    #[test]
+    fn test_rectangle() {
+        let mut draft = SelectionDraft::begin(SelectionTool::Rectangle, SelectionOp::Replace, Vector2::new(1.2, 0.8));
+        draft.move_to(Vector2::new(3.9, 3.1), false);
+        let mask = draft.to_mask(5, 5);
+        assert_eq!(count(&mask), 6);
+        assert!(mask.get(1, 1) && mask.get(3, 2) && !mask.get(0, 1) && !mask.get(4, 1) && !mask.get(1, 3));
+    }
+
+    #[test]
+    fn test_polygon_clips_to_canvas() {
+        let points = [Vector2::new(-5.0, -5.0), Vector2::new(10.0, -5.0), Vector2::new(10.0, 10.0), Vector2::new(-5.0, 10.0)];
+        assert_eq!(Mask::polygon(4, 3, &points), Mask::new(4, 3, true));
+    }
+
+    #[test]
+    fn test_combine() {
+        let left = Mask::from_region(2, 1, vec![true, false]);
+        let both = Mask::new(2, 1, true);
+
+        let mut mask = left.clone();
+        mask.combine(&Mask::from_region(2, 1, vec![false, true]), SelectionOp::Add);
+        assert_eq!(mask, both);
+        mask.combine(&left, SelectionOp::Subtract);
+        assert_eq!(mask.data, [false, true]);
+        mask.combine(&left, SelectionOp::Intersect);
+        assert!(mask.is_empty());
+        mask.combine(&left, SelectionOp::Replace);
+        assert_eq!(mask, left);
+        mask.invert();
+        assert_eq!(mask.data, [false, true]);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut mask = Mask::new(5, 4, false);
+        assert_eq!(mask.bounds(), None);
+        mask.data[6] = true;
+        mask.data[13] = true;
+        assert_eq!(mask.bounds(), Some((1, 1, 3, 2)));
+    }
+
+    #[test]
+    fn test_outline() {
+        // a 2x1 block in the middle of a 4x3 canvas has one segment per side
+        let mut mask = Mask::new(4, 3, false);
+        mask.data[5] = true;
+        mask.data[6] = true;
+        let outline = mask.outline();
+        assert_eq!(outline.len(), 4);
+        assert!(outline.contains(&[Vector2::new(1.0, 1.0), Vector2::new(3.0, 1.0)]));
+        assert!(outline.contains(&[Vector2::new(3.0, 1.0), Vector2::new(3.0, 2.0)]));
+
+        // the canvas border counts as an edge
+        assert_eq!(Mask::new(2, 2, true).outline().len(), 4);
+        assert!(Mask::new(2, 2, false).outline().is_empty());
+    }
+
+    #[test]
+    fn test_op_from_modifiers() {
+        assert_eq!(SelectionOp::from_modifiers(Modifiers::NONE), SelectionOp::Replace);
+        assert_eq!(SelectionOp::from_modifiers(Modifiers::SHIFT), SelectionOp::Add);
+        assert_eq!(SelectionOp::from_modifiers(Modifiers::ALT), SelectionOp::Subtract);
+        assert_eq!(SelectionOp::from_modifiers(Modifiers { shift: true, alt: true, ..Modifiers::NONE }), SelectionOp::Intersect);
+    }
+}
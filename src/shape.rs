@@ -0,0 +1,272 @@
+use std::f32::consts::{FRAC_PI_4, TAU};
+use raylib::prelude::*;
+
+/// Kinds of shape the shape tool draws.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Shape {
+    Line,
+    Rectangle,
+    Ellipse,
+    /// Any number of straight sides, placed one click at a time.
+    Polygon,
+}
+
+impl Shape {
+    pub const ALL: [Self; 4] = [Self::Line, Self::Rectangle, Self::Ellipse, Self::Polygon];
+
+    /// Shapes other than polygons are dragged out from one corner to the other.
+    #[inline]
+    pub const fn is_dragged(self) -> bool {
+        !matches!(self, Self::Polygon)
+    }
+
+    /// Whether the shape has an inside to fill.
+    #[inline]
+    pub const fn has_area(self) -> bool {
+        !matches!(self, Self::Line)
+    }
+
+    /// The next shape in [`Self::ALL`], wrapping around.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&shape| shape == self).expect("every shape should be in ALL");
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// Move `to` onto the nearest line through `from` at a multiple of 45°.
+///
+/// Diagonals come out with x and y exactly the same distance from `from`, so they stay on the pixel grid.
+pub fn constrain_angle(from: Vector2, to: Vector2) -> Vector2 {
+    let delta = to - from;
+    if delta.x == 0.0 && delta.y == 0.0 {
+        return to;
+    }
+    let angle = (delta.y.atan2(delta.x) / FRAC_PI_4).round() * FRAC_PI_4;
+    let (dx, dy) = (angle.cos().round(), angle.sin().round());
+    // projection onto the direction (dx, dy), which is only unit length when it isn't diagonal
+    let t = (delta.x * dx + delta.y * dy) / (dx * dx + dy * dy);
+    from + Vector2::new(dx, dy) * t
+}
+
+/// Move `to` so that the box from `from` to `to` is square, keeping the longer side.
+pub fn constrain_square(from: Vector2, to: Vector2) -> Vector2 {
+    let delta = to - from;
+    let side = delta.x.abs().max(delta.y.abs());
+    from + Vector2::new(side.copysign(delta.x), side.copysign(delta.y))
+}
+
+/// Move a point onto the center of the pixel under it, so that hard brushes stay crisp.
+#[inline]
+pub fn snap_to_pixel(point: Vector2) -> Vector2 {
+    Vector2::new(point.x.floor() + 0.5, point.y.floor() + 0.5)
+}
+
+/// A shape while it is being drawn, in canvas coordinates.
+pub struct ShapeDraft {
+    pub shape: Shape,
+    /// Where the shape was started, then any polygon corners placed since. The last point follows the mouse.
+    pub points: Vec<Vector2>,
+}
+
+impl ShapeDraft {
+    pub fn begin(shape: Shape, point: Vector2) -> Self {
+        let point = snap_to_pixel(point);
+        Self { shape, points: vec![point, point] }
+    }
+
+    /// Move the last point to `point`, constrained to 45° angles or squares and circles if `is_constrained`.
+    pub fn move_to(&mut self, point: Vector2, is_constrained: bool) {
+        let [.., anchor, last] = self.points.as_mut_slice() else { unreachable!("drafts always have a start and an end") };
+        *last = snap_to_pixel(match self.shape {
+            _ if !is_constrained => point,
+            Shape::Line | Shape::Polygon => constrain_angle(*anchor, point),
+            Shape::Rectangle | Shape::Ellipse => constrain_square(*anchor, point),
+        });
+    }
+
+    /// Place a polygon corner at the last point and start a new side from it.
+    pub fn add_corner(&mut self) {
+        debug_assert_eq!(self.shape, Shape::Polygon, "only polygons have corners to place");
+        let last = *self.points.last().expect("drafts always have a start and an end");
+        self.points.push(last);
+    }
+
+    /// Points around the edge of the shape, in order.
+    /// Shapes with an inside end where they started, so the outline can be drawn as one path.
+    pub fn outline(&self) -> Vec<Vector2> {
+        let mut points = match self.shape {
+            Shape::Line => return self.points.clone(),
+            Shape::Polygon => self.points.clone(),
+            Shape::Rectangle => {
+                let (a, b) = (self.points[0], self.points[1]);
+                vec![a, Vector2::new(b.x, a.y), b, Vector2::new(a.x, b.y)]
+            }
+            Shape::Ellipse => {
+                let (a, b) = (self.points[0], self.points[1]);
+                let center = (a + b) * 0.5;
+                let (rx, ry) = ((b.x - a.x).abs() * 0.5, (b.y - a.y).abs() * 0.5);
+                // about one segment for every 2 pixels around the edge
+                let segments = (TAU * rx.max(ry) * 0.5).clamp(16.0, 256.0) as usize;
+                (0..segments)
+                    .map(|i| {
+                        let angle = i as f32 / segments as f32 * TAU;
+                        center + Vector2::new(angle.cos() * rx, angle.sin() * ry)
+                    })
+                    .collect()
+            }
+        };
+        points.dedup();
+        if let Some(&first) = points.first() {
+            points.push(first);
+        }
+        points
+    }
+}
+
+/// Twice the signed area of the triangle. Positive when `a`, `b`, `c` turn clockwise on screen (with y pointing down).
+#[inline]
+fn cross(a: Vector2, b: Vector2, c: Vector2) -> f32 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+/// Split a simple polygon into triangles by ear clipping, each wound counter-clockwise on screen the way raylib draws them.
+///
+/// The polygon may be wound either way and may repeat its first point at the end.
+/// Self-intersecting polygons still give triangles, though they may not cover the inside exactly.
+pub fn triangulate(points: &[Vector2]) -> Vec<[Vector2; 3]> {
+    let points = match points {
+        [first, rest @ .., last] if first == last && !rest.is_empty() => &points[..points.len() - 1],
+        _ => points,
+    };
+    if points.len() < 3 {
+        return Vec::new();
+    }
+
+    // walk the polygon clockwise on screen, so ears are the corners that turn clockwise
+    let area: f32 = (0..points.len()).map(|i| cross(Vector2::zero(), points[i], points[(i + 1) % points.len()])).sum();
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if area < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let corner = |i: usize| (points[remaining[(i + n - 1) % n]], points[remaining[i]], points[remaining[(i + 1) % n]]);
+        let is_ear = |i: usize| {
+            let (a, b, c) = corner(i);
+            cross(a, b, c) > 0.0 && remaining.iter().all(|&j| {
+                let p = points[j];
+                // points on the edge count as outside, so that repeated points don't block every ear
+                p == a || p == b || p == c || !(cross(a, b, p) > 0.0 && cross(b, c, p) > 0.0 && cross(c, a, p) > 0.0)
+            })
+        };
+        // a self-intersecting polygon may run out of ears; clip something anyway so this always finishes
+        let i = (0..n).find(|&i| is_ear(i)).unwrap_or(0);
+        let (a, b, c) = corner(i);
+        triangles.push([a, b, c]);
+        remaining.remove(i);
+    }
+    triangles.push([0, 1, 2].map(|i| points[remaining[i]]));
+
+    // drop triangles with no area, which raylib would skip anyway
+    triangles.retain(|&[a, b, c]| cross(a, b, c) != 0.0);
+    for triangle in &mut triangles {
+        // ears are clockwise, as are fallback clips unless the polygon crosses itself
+        if cross(triangle[0], triangle[1], triangle[2]) > 0.0 {
+            triangle.swap(1, 2);
+        }
+    }
+    triangles
+}
+
+pub trait ShapeDraw: RaylibDraw {
+    /// Fill the inside of the polygon with `color`.
+    fn draw_polygon_fill(&mut self, points: &[Vector2], color: Color) {
+        for [a, b, c] in triangulate(points) {
+            self.draw_triangle(a, b, c, color);
+        }
+    }
+
+    /// Thin lines along a path, for showing where a shape will go when it can't be previewed properly.
+    fn draw_path_lines(&mut self, points: &[Vector2], thickness: f32, color: Color) {
+        for pair in points.windows(2) {
+            self.draw_line_ex(pair[0], pair[1], thickness, color);
+        }
+    }
+}
+impl<T: RaylibDraw> ShapeDraw for T {}
+
+#[cfg(test)]
+mod shape_tests {
+    use super::*;
+
+    fn area(triangles: &[[Vector2; 3]]) -> f32 {
+        triangles.iter().map(|&[a, b, c]| cross(a, b, c).abs() * 0.5).sum()
+    }
+
+    fn is_close(a: Vector2, b: Vector2) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    #[test]
+    fn test_constrain_angle() {
+        let from = Vector2::new(0.0, 0.0);
+        assert!(is_close(constrain_angle(from, Vector2::new(10.0, 1.0)), Vector2::new(10.0, 0.0)));
+        assert!(is_close(constrain_angle(from, Vector2::new(1.0, -10.0)), Vector2::new(0.0, -10.0)));
+        assert!(is_close(constrain_angle(from, Vector2::new(-5.0, 4.0)), Vector2::new(-4.5, 4.5)));
+    }
+
+    #[test]
+    fn test_constrain_square() {
+        let from = Vector2::new(2.0, 2.0);
+        assert!(is_close(constrain_square(from, Vector2::new(5.0, -6.0)), Vector2::new(10.0, -6.0)));
+        assert!(is_close(constrain_square(from, Vector2::new(-1.0, 3.0)), Vector2::new(-1.0, 5.0)));
+    }
+
+    #[test]
+    fn test_rectangle_outline() {
+        let mut draft = ShapeDraft::begin(Shape::Rectangle, Vector2::new(0.2, 0.7));
+        draft.move_to(Vector2::new(4.9, 2.1), false);
+        let outline = draft.outline();
+        assert_eq!(outline.len(), 5);
+        assert_eq!(outline.first(), outline.last());
+        assert!(is_close(outline[2], Vector2::new(4.5, 2.5)));
+    }
+
+    #[test]
+    fn test_triangulate_either_winding() {
+        let square = [Vector2::new(0.0, 0.0), Vector2::new(2.0, 0.0), Vector2::new(2.0, 2.0), Vector2::new(0.0, 2.0)];
+        for points in [square.to_vec(), square.iter().rev().copied().collect()] {
+            let triangles = triangulate(&points);
+            assert_eq!(triangles.len(), 2);
+            assert!((area(&triangles) - 4.0).abs() < 1e-4);
+            assert!(triangles.iter().all(|&[a, b, c]| cross(a, b, c) < 0.0), "raylib wants counter-clockwise triangles");
+        }
+    }
+
+    #[test]
+    fn test_triangulate_concave() {
+        // an L shape: 3x3 with the top right 2x2 missing, so area 5
+        let points = [
+            Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), Vector2::new(1.0, 2.0),
+            Vector2::new(3.0, 2.0), Vector2::new(3.0, 3.0), Vector2::new(0.0, 3.0), Vector2::new(0.0, 0.0),
+        ];
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+        assert!((area(&triangles) - 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_polygon_draft() {
+        let mut draft = ShapeDraft::begin(Shape::Polygon, Vector2::new(0.0, 0.0));
+        draft.move_to(Vector2::new(10.0, 1.0), true);
+        draft.add_corner();
+        draft.move_to(Vector2::new(10.0, 10.0), false);
+        assert_eq!(draft.points.len(), 3);
+        assert!(is_close(draft.points[1], Vector2::new(10.5, 0.5)));
+        let outline = draft.outline();
+        assert_eq!(outline.len(), 4);
+        assert_eq!(triangulate(&outline).len(), 1);
+    }
+}
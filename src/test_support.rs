@@ -0,0 +1,50 @@
+//! Fixtures shared by the tests of several modules.
+
+use std::sync::{Mutex, PoisonError};
+use raylib::prelude::*;
+use crate::layer::{Layer, LayerContent, LayerTree};
+
+static WINDOW: Mutex<()> = Mutex::new(());
+
+/// Run `f` with a hidden window open, for tests that draw on the GPU.
+///
+/// Only one window can be open at a time, so these tests take turns. Anything loaded on the GPU should be dropped
+/// inside `f`, before the window closes.
+pub fn with_window<T>(f: impl FnOnce(&mut RaylibHandle, &RaylibThread) -> T) -> T {
+    let _window = WINDOW.lock().unwrap_or_else(PoisonError::into_inner);
+    unsafe { ffi::SetConfigFlags(ffi::ConfigFlags::FLAG_WINDOW_HIDDEN as u32); }
+    let (mut rl, thread) = init().size(16, 16).title("test").build();
+    f(&mut rl, &thread)
+}
+
+/// A raster layer with no raster behind it, which is all that edits to the tree look at.
+pub fn raster(name: &str) -> Layer {
+    Layer::new(LayerContent::empty_raster()).named(name)
+}
+
+/// A group whose buffer is a placeholder that was never loaded on the GPU.
+/// That is fine as long as nothing draws it, and unloading it does nothing.
+pub fn group(name: &str, children: impl IntoIterator<Item = Layer>) -> Layer {
+    let buffer = unsafe { RenderTexture2D::from_raw(std::mem::zeroed()) };
+    Layer::new(LayerContent::with_children(buffer, children)).named(name)
+}
+
+/// Names bottom to top, with the children of groups in parentheses.
+pub fn describe<'a>(layers: impl IntoIterator<Item = &'a Layer>) -> String {
+    layers.into_iter()
+        .map(|layer| match &layer.content {
+            LayerContent::Raster { .. } => layer.name.clone(),
+            LayerContent::Group { children, .. } => format!("{}({})", layer.name, describe(children)),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `a G(b c) d`
+pub fn tree() -> LayerTree {
+    let mut layers = LayerTree::new();
+    layers.push(raster("a"));
+    layers.push(group("G", [raster("b"), raster("c")]));
+    layers.push(raster("d"));
+    layers
+}
@@ -0,0 +1,436 @@
+use std::f32::consts::PI;
+use raylib::prelude::*;
+use crate::{document::Document, fill::blend_over, history::Edit, raster::{self, Pixels, RcRaster}, selection::{Mask, Selection}};
+
+/// How pixels are picked when the floating buffer is stamped at a new size or angle.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Resample {
+    /// Keeps hard pixel edges, for pixel art.
+    #[default]
+    Nearest,
+    /// Blends the four nearest pixels, for smoother results.
+    Bilinear,
+}
+
+/// How close, in screen pixels, a click has to be to a handle to grab it.
+pub const HANDLE_SIZE: f32 = 8.0;
+
+/// How far, in screen pixels, the rotate handle sits above the top edge.
+pub const ROTATE_HANDLE_DISTANCE: f32 = 20.0;
+
+/// Rotation snaps to multiples of this while constrained.
+const ROTATION_STEP: f32 = PI / 12.0;
+
+/// Parts of the transform box that can be dragged.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Handle {
+    /// A corner or the middle of a side, as which way it is from the center along each axis of the buffer.
+    Scale(i8, i8),
+    Rotate,
+}
+
+impl Handle {
+    pub const SCALE: [Self; 8] = [
+        Self::Scale(-1, -1), Self::Scale(0, -1), Self::Scale(1, -1),
+        Self::Scale(-1,  0),                     Self::Scale(1,  0),
+        Self::Scale(-1,  1), Self::Scale(0,  1), Self::Scale(1,  1),
+    ];
+}
+
+/// What dragging the mouse does to the floating buffer.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransformDrag {
+    /// `grab` is where the mouse took hold, relative to the center.
+    Move { grab: Vector2 },
+    /// The opposite handle stays where it is.
+    Scale { sx: i8, sy: i8 },
+    /// `grab` is the angle from the center to where the mouse took hold.
+    Rotate { grab: f32, start_rotation: f32 },
+}
+
+#[inline]
+fn rotate(v: Vector2, angle: f32) -> Vector2 {
+    let (sin, cos) = angle.sin_cos();
+    Vector2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+#[inline]
+fn angle_of(v: Vector2) -> f32 {
+    v.y.atan2(v.x)
+}
+
+/// Where a buffer of pixels goes on the canvas.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Placement {
+    /// Size of the buffer in pixels, before scaling.
+    pub size: Vector2,
+    /// Where the middle of the buffer goes on the canvas.
+    pub center: Vector2,
+    /// Negative scales flip the buffer.
+    pub scale: Vector2,
+    /// Clockwise on screen, in radians.
+    pub rotation: f32,
+}
+
+impl Placement {
+    /// Put the buffer back where it came from, with its top left corner at (`x`, `y`).
+    pub fn new(x: u32, y: u32, w: u32, h: u32) -> Self {
+        let size = Vector2::new(w as f32, h as f32);
+        Self {
+            size,
+            center: Vector2::new(x as f32, y as f32) + size * 0.5,
+            scale: Vector2::new(1.0, 1.0),
+            rotation: 0.0,
+        }
+    }
+
+    /// Half the size of the buffer once it's scaled, before it is rotated.
+    #[inline]
+    fn half_extent(&self) -> Vector2 {
+        Vector2::new(self.size.x * self.scale.x * 0.5, self.size.y * self.scale.y * 0.5)
+    }
+
+    /// A point on the buffer, in pixels from its top left corner, to the canvas.
+    pub fn canvas_pos(&self, local: Vector2) -> Vector2 {
+        let offset = local - self.size * 0.5;
+        self.center + rotate(Vector2::new(offset.x * self.scale.x, offset.y * self.scale.y), self.rotation)
+    }
+
+    /// A point on the canvas to the buffer. [`None`] if the buffer has been squashed flat.
+    pub fn local_pos(&self, canvas: Vector2) -> Option<Vector2> {
+        if self.scale.x == 0.0 || self.scale.y == 0.0 {
+            return None;
+        }
+        let offset = rotate(canvas - self.center, -self.rotation);
+        Some(Vector2::new(offset.x / self.scale.x, offset.y / self.scale.y) + self.size * 0.5)
+    }
+
+    /// Whether `canvas` is over the buffer.
+    pub fn contains(&self, canvas: Vector2) -> bool {
+        self.local_pos(canvas).is_some_and(|local| (0.0..=self.size.x).contains(&local.x) && (0.0..=self.size.y).contains(&local.y))
+    }
+
+    /// The corners of the buffer on the canvas, going around it.
+    pub fn corners(&self) -> [Vector2; 4] {
+        [(0.0, 0.0), (self.size.x, 0.0), (self.size.x, self.size.y), (0.0, self.size.y)]
+            .map(|(x, y)| self.canvas_pos(Vector2::new(x, y)))
+    }
+
+    /// Where a handle is on the canvas. The rotate handle keeps the same distance on screen at any `zoom`.
+    pub fn handle_pos(&self, handle: Handle, zoom: f32) -> Vector2 {
+        match handle {
+            Handle::Scale(sx, sy) => self.canvas_pos(Vector2::new(
+                (f32::from(sx) + 1.0) * 0.5 * self.size.x,
+                (f32::from(sy) + 1.0) * 0.5 * self.size.y,
+            )),
+            Handle::Rotate => {
+                let up = rotate(Vector2::new(0.0, -1.0), self.rotation);
+                self.handle_pos(Handle::Scale(0, -1), zoom) + up * (ROTATE_HANDLE_DISTANCE / zoom)
+            }
+        }
+    }
+
+    /// Decide what a press at `pos` grabs: a handle if one is close enough, the buffer if it's under the mouse,
+    /// and otherwise the rotation, like grabbing the edge of a turntable.
+    pub fn begin_drag(&self, pos: Vector2, zoom: f32) -> TransformDrag {
+        let grab_distance = HANDLE_SIZE / zoom;
+        let handle = std::iter::once(Handle::Rotate)
+            .chain(Handle::SCALE)
+            .find(|&handle| (self.handle_pos(handle, zoom) - pos).length() <= grab_distance);
+        match handle {
+            Some(Handle::Scale(sx, sy)) => TransformDrag::Scale { sx, sy },
+            Some(Handle::Rotate) => TransformDrag::Rotate { grab: angle_of(pos - self.center), start_rotation: self.rotation },
+            None if self.contains(pos) => TransformDrag::Move { grab: pos - self.center },
+            None => TransformDrag::Rotate { grab: angle_of(pos - self.center), start_rotation: self.rotation },
+        }
+    }
+
+    /// Follow the mouse to `pos`. If `is_constrained`, corners keep the buffer's proportions and rotation snaps to 15°.
+    pub fn drag(&mut self, drag: TransformDrag, pos: Vector2, is_constrained: bool) {
+        match drag {
+            TransformDrag::Move { grab } => self.center = pos - grab,
+            TransformDrag::Scale { sx, sy } => self.scale_to(sx, sy, pos, is_constrained),
+            TransformDrag::Rotate { grab, start_rotation } => {
+                let rotation = start_rotation + angle_of(pos - self.center) - grab;
+                self.rotation = if is_constrained { (rotation / ROTATION_STEP).round() * ROTATION_STEP } else { rotation };
+            }
+        }
+    }
+
+    /// Move the handle at (`sx`, `sy`) to `pos`, keeping the opposite handle in place.
+    fn scale_to(&mut self, sx: i8, sy: i8, pos: Vector2, keep_proportions: bool) {
+        let side = Vector2::new(f32::from(sx), f32::from(sy));
+        let half = self.half_extent();
+        let anchor = self.center - rotate(Vector2::new(side.x * half.x, side.y * half.y), self.rotation);
+        let reach = rotate(pos - anchor, -self.rotation);
+        if sx != 0 && self.size.x > 0.0 {
+            self.scale.x = reach.x / (side.x * self.size.x);
+        }
+        if sy != 0 && self.size.y > 0.0 {
+            self.scale.y = reach.y / (side.y * self.size.y);
+        }
+        if keep_proportions && sx != 0 && sy != 0 {
+            // follow whichever side was pulled further, keeping any flips
+            let amount = self.scale.x.abs().max(self.scale.y.abs());
+            self.scale = Vector2::new(amount.copysign(self.scale.x), amount.copysign(self.scale.y));
+        }
+        let half = self.half_extent();
+        self.center = anchor + rotate(Vector2::new(side.x * half.x, side.y * half.y), self.rotation);
+    }
+}
+
+/// The color of `source` at `local`, in pixels from its top left corner. Outside the buffer is transparent.
+pub fn sample(source: &Pixels, local: Vector2, resample: Resample) -> Color {
+    let texel = |x: f32, y: f32| {
+        if x >= 0.0 && y >= 0.0 && x < source.w as f32 && y < source.h as f32 {
+            source.get(x as u32, y as u32)
+        } else {
+            Color::BLANK
+        }
+    };
+    match resample {
+        Resample::Nearest => texel(local.x.floor(), local.y.floor()),
+        Resample::Bilinear => {
+            // pixel centers are at the halves
+            let (u, v) = (local.x - 0.5, local.y - 0.5);
+            let (x0, y0) = (u.floor(), v.floor());
+            let (fx, fy) = (u - x0, v - y0);
+            // weigh color by alpha so transparent pixels don't darken the edges
+            let mut sum = [0.0f32; 4];
+            for (x, y, weight) in [
+                (x0, y0, (1.0 - fx) * (1.0 - fy)),
+                (x0 + 1.0, y0, fx * (1.0 - fy)),
+                (x0, y0 + 1.0, (1.0 - fx) * fy),
+                (x0 + 1.0, y0 + 1.0, fx * fy),
+            ] {
+                let color = texel(x, y);
+                let a = f32::from(color.a) * weight;
+                sum[0] += f32::from(color.r) * a;
+                sum[1] += f32::from(color.g) * a;
+                sum[2] += f32::from(color.b) * a;
+                sum[3] += a;
+            }
+            if sum[3] <= 0.0 {
+                return Color::BLANK;
+            }
+            let channel = |c: f32| (c / sum[3]).round().clamp(0.0, 255.0) as u8;
+            Color::new(channel(sum[0]), channel(sum[1]), channel(sum[2]), sum[3].round().min(255.0) as u8)
+        }
+    }
+}
+
+/// Paint `source` over `target` where `placement` puts it.
+///
+/// Returns the rectangle that changed as `(x, y, w, h)`, or [`None`] if nothing landed on the target.
+pub fn stamp(target: &mut Pixels, source: &Pixels, placement: &Placement, resample: Resample) -> Option<(u32, u32, u32, u32)> {
+    let corners = placement.corners();
+    let (mut min, mut max) = (corners[0], corners[0]);
+    for corner in &corners[1..] {
+        (min.x, min.y) = (min.x.min(corner.x), min.y.min(corner.y));
+        (max.x, max.y) = (max.x.max(corner.x), max.y.max(corner.y));
+    }
+    // bilinear sampling reaches half a pixel past the edge
+    let x0 = ((min.x.floor() - 1.0).max(0.0) as u32).min(target.w);
+    let y0 = ((min.y.floor() - 1.0).max(0.0) as u32).min(target.h);
+    let x1 = ((max.x.ceil() + 1.0).max(0.0) as u32).min(target.w);
+    let y1 = ((max.y.ceil() + 1.0).max(0.0) as u32).min(target.h);
+
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let local = placement.local_pos(Vector2::new(x as f32 + 0.5, y as f32 + 0.5))?;
+            let color = sample(source, local, resample);
+            if color.a == 0 {
+                continue;
+            }
+            target.set(x, y, blend_over(target.get(x, y), color));
+            bounds = Some(match bounds {
+                Some((bx0, by0, bx1, by1)) => (bx0.min(x), by0.min(y), bx1.max(x), by1.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+    }
+    bounds.map(|(bx0, by0, bx1, by1)| (bx0, by0, bx1 - bx0 + 1, by1 - by0 + 1))
+}
+
+/// Cut the pixels inside `mask` out of `pixels`, or all of them if there's no mask.
+///
+/// Returns the cut out pixels and where they were as `(x, y, w, h)`, or [`None`] if nothing is selected.
+pub fn lift(pixels: &mut Pixels, mask: Option<&Mask>) -> Option<(Pixels, (u32, u32, u32, u32))> {
+    let (x, y, w, h) = match mask {
+        Some(mask) => mask.bounds()?,
+        None => (0, 0, pixels.w, pixels.h),
+    };
+    let mut lifted = pixels.crop(x, y, w, h);
+    for ly in 0..h {
+        for lx in 0..w {
+            if mask.is_none_or(|mask| mask.get(x + lx, y + ly)) {
+                pixels.set(x + lx, y + ly, Color::BLANK);
+            } else {
+                lifted.set(lx, ly, Color::BLANK);
+            }
+        }
+    }
+    Some((lifted, (x, y, w, h)))
+}
+
+/// Pixels lifted off a raster to be moved, scaled and rotated before being stamped back down.
+pub struct FloatingBuffer {
+    raster: RcRaster,
+    /// The whole raster from before the pixels were lifted, for undo.
+    before: Pixels,
+    pixels: Pixels,
+    texture: Texture2D,
+    /// Where the pixels were lifted from, as `(x, y, w, h)`.
+    source: (u32, u32, u32, u32),
+    /// The selection the pixels were lifted with, which moves with them.
+    mask: Option<Mask>,
+    pub placement: Placement,
+}
+
+impl FloatingBuffer {
+    /// Lift the selected pixels off `raster`, or the whole raster if nothing is selected.
+    /// The selection is taken out of `doc` until the buffer is committed or canceled.
+    ///
+    /// Returns [`None`] if there is nothing to lift, or the lifted pixels couldn't be uploaded to the GPU.
+    pub fn lift(rl: &mut RaylibHandle, thread: &RaylibThread, raster: &RcRaster, doc: &mut Document) -> Option<Self> {
+        let before = raster::read_pixels(&raster.borrow());
+        let mut cleared = before.clone();
+        let (pixels, source) = lift(&mut cleared, doc.selection.as_ref().map(Selection::mask))?;
+        // load the texture before touching the raster, so a failure leaves nothing half lifted
+        let texture = rl.load_texture_from_image(thread, &pixels.to_image()).ok()?;
+        raster::write_pixels(&mut raster.borrow_mut(), &cleared);
+        let (x, y, w, h) = source;
+        Some(Self {
+            raster: raster.clone(),
+            before,
+            texture,
+            pixels,
+            source,
+            mask: doc.selection.take().map(Selection::into_mask),
+            placement: Placement::new(x, y, w, h),
+        })
+    }
+
+    /// Stamp the pixels down where they've been placed, as one undoable step with the lift.
+    pub fn commit(self, doc: &mut Document, resample: Resample) {
+        let mut after = raster::read_pixels(&self.raster.borrow());
+        let (sx, sy, sw, sh) = self.source;
+        let (x0, y0, x1, y1) = match stamp(&mut after, &self.pixels, &self.placement, resample) {
+            Some((x, y, w, h)) => (sx.min(x), sy.min(y), (sx + sw).max(x + w), (sy + sh).max(y + h)),
+            None => (sx, sy, sx + sw, sy + sh),
+        };
+        let after = after.crop(x0, y0, x1 - x0, y1 - y0);
+        raster::write_pixels_at(&mut self.raster.borrow_mut(), x0, y0, &after);
+        doc.history.push(Edit::Pixels {
+            raster: RcRaster::downgrade(&self.raster),
+            x: x0,
+            y: y0,
+            before: self.before.crop(x0, y0, x1 - x0, y1 - y0),
+            after,
+        });
+
+        // the selection lands where the pixels did
+        if let Some(mask) = self.mask {
+            let mut moved = Pixels::new(mask.w, mask.h, Color::BLANK);
+            stamp(&mut moved, &mask.to_pixels().crop(sx, sy, sw, sh), &self.placement, resample);
+            doc.selection = Selection::new(Mask::from_region(mask.w, mask.h, moved.data.iter().map(|px| px.a >= 128).collect()));
+        }
+    }
+
+    /// Put everything back the way it was before the lift.
+    pub fn cancel(self, doc: &mut Document) {
+        raster::write_pixels(&mut self.raster.borrow_mut(), &self.before);
+        doc.selection = self.mask.and_then(Selection::new);
+    }
+
+    /// Show the buffer where it's been placed, on top of everything else.
+    pub fn draw<D: RaylibDraw>(&self, d: &mut D, resample: Resample) {
+        let filter = match resample {
+            Resample::Nearest => TextureFilter::TEXTURE_FILTER_POINT,
+            Resample::Bilinear => TextureFilter::TEXTURE_FILTER_BILINEAR,
+        };
+        unsafe { ffi::SetTextureFilter(*self.texture, filter as i32); }
+
+        let Placement { size, center, scale, rotation } = self.placement;
+        // raylib flips the texture when the source rectangle is negative
+        let source = Rectangle::new(0.0, 0.0, size.x.copysign(scale.x), size.y.copysign(scale.y));
+        let (w, h) = ((size.x * scale.x).abs(), (size.y * scale.y).abs());
+        let dest = Rectangle::new(center.x, center.y, w, h);
+        d.draw_texture_pro(&self.texture, source, dest, Vector2::new(w * 0.5, h * 0.5), rotation.to_degrees(), Color::WHITE);
+    }
+}
+
+#[cfg(test)]
+mod transform_tests {
+    use super::*;
+
+    const RED: Color = Color::new(255, 0, 0, 255);
+
+    fn is_close(a: Vector2, b: Vector2) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut placement = Placement::new(2, 3, 4, 6);
+        placement.scale = Vector2::new(-2.0, 0.5);
+        placement.rotation = 0.7;
+        let local = Vector2::new(1.0, 5.0);
+        assert!(is_close(placement.local_pos(placement.canvas_pos(local)).unwrap(), local));
+        placement.scale.x = 0.0;
+        assert_eq!(placement.local_pos(local), None);
+    }
+
+    #[test]
+    fn test_scale_keeps_opposite_corner() {
+        let mut placement = Placement::new(0, 0, 4, 4);
+        placement.rotation = 0.3;
+        let anchor = placement.handle_pos(Handle::Scale(-1, -1), 1.0);
+        let target = placement.handle_pos(Handle::Scale(1, 1), 1.0) * 2.0 - anchor;
+        placement.drag(TransformDrag::Scale { sx: 1, sy: 1 }, target, false);
+        assert!(is_close(placement.scale, Vector2::new(2.0, 2.0)));
+        assert!(is_close(placement.handle_pos(Handle::Scale(-1, -1), 1.0), anchor));
+        assert!(is_close(placement.handle_pos(Handle::Scale(1, 1), 1.0), target));
+    }
+
+    #[test]
+    fn test_begin_drag() {
+        let placement = Placement::new(0, 0, 40, 40);
+        assert_eq!(placement.begin_drag(Vector2::new(40.0, 20.0), 1.0), TransformDrag::Scale { sx: 1, sy: 0 });
+        assert_eq!(placement.begin_drag(Vector2::new(15.0, 20.0), 1.0), TransformDrag::Move { grab: Vector2::new(-5.0, 0.0) });
+        assert!(matches!(placement.begin_drag(Vector2::new(20.0, -20.0), 1.0), TransformDrag::Rotate { .. }));
+        assert!(matches!(placement.begin_drag(Vector2::new(80.0, 80.0), 1.0), TransformDrag::Rotate { .. }));
+        // handles are easier to grab when zoomed out
+        assert_eq!(placement.begin_drag(Vector2::new(30.0, 20.0), 0.5), TransformDrag::Scale { sx: 1, sy: 0 });
+    }
+
+    #[test]
+    fn test_lift_and_stamp() {
+        let mut pixels = Pixels::new(4, 4, RED);
+        let mut mask = Mask::new(4, 4, false);
+        mask.data[5] = true;
+        mask.data[6] = true;
+        let (lifted, source) = lift(&mut pixels, Some(&mask)).unwrap();
+        assert_eq!(source, (1, 1, 2, 1));
+        assert_eq!(pixels.get(1, 1), Color::BLANK);
+        assert_eq!(pixels.get(0, 1), RED);
+
+        // moved down by 2 and stretched to twice the width, around its center
+        let mut placement = Placement::new(1, 1, 2, 1);
+        placement.center.y += 2.0;
+        placement.scale.x = 2.0;
+        assert_eq!(stamp(&mut pixels, &lifted, &placement, Resample::Nearest), Some((0, 3, 4, 1)));
+        assert!((0..4).all(|x| pixels.get(x, 3) == RED));
+    }
+
+    #[test]
+    fn test_bilinear() {
+        let mut source = Pixels::new(2, 1, Color::BLANK);
+        source.set(0, 0, RED);
+        // halfway between red and transparent keeps the red and halves the alpha
+        assert_eq!(sample(&source, Vector2::new(1.0, 0.5), Resample::Bilinear), Color::new(255, 0, 0, 128));
+        assert_eq!(sample(&source, Vector2::new(0.5, 0.5), Resample::Bilinear), RED);
+        assert_eq!(sample(&source, Vector2::new(1.5, 0.5), Resample::Nearest), Color::BLANK);
+    }
+}
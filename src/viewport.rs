@@ -1,16 +1,75 @@
 use amygui::prelude::*;
 use raylib::prelude::*;
-use crate::{brush::{AmyBlendModeExt, BlendEquation, BlendFactor, BlendModeA, Brush, BrushPresetDraw, BrushTargetModeExt}, RaylibDrawBackend, RaylibTickBackend};
+use amygui::prelude::MouseButton; // not raylib's
+use crate::{brush::{AmyBlendModeExt, BlendEquation, BlendFactor, BlendModeA, Brush, BrushPresetDraw, BrushTargetModeExt, StrokeBuffer}, document::Document, fill::FillOptions, history::StrokeRecorder, layer::LayerBlend, raster::Pixels, selection::{SelectionClip, SelectionDraft, SelectionOp, SelectionTool}, shape::{Shape, ShapeDraft, ShapeDraw}, transform::{FloatingBuffer, Handle, Resample, TransformDrag, HANDLE_SIZE}, RaylibDrawBackend, RaylibTickBackend};
+
+/// What clicking on the canvas does.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tool {
+    Brush,
+    /// Flood fill with the brush color.
+    Fill,
+    Shape(Shape),
+    Select(SelectionTool),
+    /// Move, scale and rotate the selected pixels, or the whole layer if nothing is selected.
+    Transform,
+    /// Pick up the brush color from the canvas.
+    Eyedropper,
+}
+
+impl Tool {
+    /// Tools that put color on the canvas, which Alt swaps for the eyedropper.
+    #[inline]
+    pub const fn is_painting(self) -> bool {
+        matches!(self, Self::Brush | Self::Fill | Self::Shape(_))
+    }
+}
+
+/// How close, in screen pixels, a click has to be to the first corner of a polygon to close it.
+const CLOSE_POLYGON_DISTANCE: f32 = 6.0;
+
+const CROSSHAIR_COLOR: Color = Color::new(200,200,200,255);
+
+/// How many canvas pixels across the eyedropper's loupe shows.
+const LOUPE_PIXELS: f32 = 11.0;
+/// How many screen pixels each canvas pixel takes up in the loupe.
+const LOUPE_ZOOM: f32 = 8.0;
+
+/// Whether a click at `pos` finishes the polygon with corners `points`, by double-clicking or clicking on the first corner.
+fn is_closing_click(points: &[Vector2], pos: Vector2, click_count: u32, zoom: f32) -> bool {
+    click_count >= 2 || (points.len() > 3 && (points[0] - pos).length() * zoom <= CLOSE_POLYGON_DISTANCE)
+}
 
 pub struct ViewportNode {
     is_m1_space_panning: bool,
     is_m3_panning: bool,
     is_drawing: bool,
     is_cursor_shown: bool,
+    is_alt_down: bool,
     brush_pos: Option<Vector2>,
     brush_pos_prev: Option<Vector2>,
     camera: Camera2D,
+    stroke: Option<StrokeRecorder>,
+    /// Where the dabs of the stroke collect before they are blended onto the brush target.
+    stroke_buffer: Option<StrokeBuffer>,
+    /// Keeps the stroke inside the selection, if there was one when the stroke began.
+    stroke_clip: Option<SelectionClip>,
+    shape_draft: Option<ShapeDraft>,
+    selection_draft: Option<SelectionDraft>,
+    /// Pixels lifted by the transform tool, until they are stamped back down.
+    floating: Option<FloatingBuffer>,
+    transform_drag: Option<TransformDrag>,
+    /// What the eyedropper picks from while the mouse is held, read once when it is pressed.
+    pick_source: Option<Pixels>,
+    pub tool: Tool,
     pub brush: Brush,
+    /// Also used by the magic wand, which selects what a fill would fill.
+    pub fill: FillOptions,
+    /// Whether shapes with an inside are filled with the brush color, rather than outlined with the brush.
+    pub fill_shapes: bool,
+    pub resample: Resample,
+    /// Whether the eyedropper picks from the composite of every layer, rather than only the active one.
+    pub pick_merged: bool,
 }
 
 impl ViewportNode {
@@ -20,25 +79,135 @@ impl ViewportNode {
             is_m3_panning: false,
             is_drawing: false,
             is_cursor_shown: false,
+            is_alt_down: false,
             brush_pos: None,
             brush_pos_prev: None,
             camera,
+            stroke: None,
+            stroke_buffer: None,
+            stroke_clip: None,
+            shape_draft: None,
+            selection_draft: None,
+            floating: None,
+            transform_drag: None,
+            pick_source: None,
+            tool: Tool::Brush,
             brush,
+            fill: FillOptions::new(),
+            fill_shapes: false,
+            resample: Resample::Nearest,
+            pick_merged: false,
+        }
+    }
+
+    /// The tool that clicking uses right now, which is the eyedropper while Alt is held over a painting tool.
+    pub fn current_tool(&self) -> Tool {
+        if self.is_alt_down && self.tool.is_painting() && !self.is_drawing && self.shape_draft.is_none() {
+            Tool::Eyedropper
+        } else {
+            self.tool
+        }
+    }
+
+    fn begin_stroke(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, doc: &Document) {
+        self.is_drawing = true;
+        self.brush.begin_stroke();
+        self.stroke = self.brush.target().map(StrokeRecorder::begin);
+        self.stroke_buffer = self.stroke.as_ref()
+            .and_then(|stroke| StrokeBuffer::begin(rl, thread, stroke.before()));
+        self.stroke_clip = self.stroke.as_ref()
+            .filter(|_| doc.selection.is_some())
+            .map(|stroke| SelectionClip::begin(rl, thread, stroke.before()));
+    }
+
+    fn end_stroke(&mut self, doc: &mut Document) {
+        self.is_drawing = false;
+        self.stroke_buffer = None;
+        self.stroke_clip = None;
+        if let Some(edit) = self.stroke.take().and_then(StrokeRecorder::finish) {
+            doc.history.push(edit);
+        }
+    }
+
+    /// Draw the shape being drafted onto the brush target, as one undoable step.
+    fn commit_shape(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, doc: &mut Document) {
+        let Some(draft) = self.shape_draft.take() else { return };
+        let Some(mut recorder) = self.brush.target().map(StrokeRecorder::begin) else { return };
+        let outline = draft.outline();
+        let brush_radius = self.brush.preset.size.get() as f32 * 0.5;
+        for &point in &outline {
+            recorder.include(point, brush_radius);
+        }
+
+        self.brush.begin_stroke();
+        if self.fill_shapes && draft.shape.has_area() {
+            if let Some((mut d, stroke)) = rl.begin_brush_target_mode(thread, &mut self.brush) {
+                let mut d = d.begin_blend_mode_a(stroke.preset.blend);
+                d.draw_polygon_fill(&outline, stroke.preset.color);
+            }
+        } else if let Some(mut buffer) = StrokeBuffer::begin(rl, thread, recorder.before()) {
+            buffer.paint_dab_path(rl, thread, &mut self.brush, &outline);
+        }
+        if let Some(selection) = &mut doc.selection && let Some(target) = self.brush.target() {
+            SelectionClip::begin(rl, thread, recorder.before()).apply(rl, thread, target, selection);
+        }
+        if let Some(edit) = recorder.finish() {
+            doc.history.push(edit);
+        }
+    }
+
+    /// Combine the selection being drafted into the document's selection.
+    fn commit_selection(&mut self, doc: &mut Document) {
+        let Some(draft) = self.selection_draft.take() else { return };
+        let canvas = doc.rasters.canvas();
+        let mask = draft.to_mask(canvas.get_w().into(), canvas.get_h().into());
+        doc.select(mask, draft.op);
+    }
+
+    /// Set the brush color to the pixel under `pos` in [`Self::pick_source`].
+    /// Fully transparent pixels leave the color alone, since they have no color to speak of.
+    fn pick_color(&mut self, doc: &Document, pos: Vector2) {
+        if let Some(pixels) = &self.pick_source && let Some(color) = doc.pick_color(pixels, pos).filter(|color| color.a != 0) {
+            self.brush.preset.color = color;
+        }
+    }
+
+    /// Stamp the floating buffer back down, if there is one.
+    pub fn commit_transform(&mut self, doc: &mut Document) {
+        self.transform_drag = None;
+        if let Some(floating) = self.floating.take() {
+            floating.commit(doc, self.resample);
         }
     }
+
+    /// Put the floating buffer back where it was lifted from. Returns false if nothing was floating.
+    pub fn cancel_transform(&mut self, doc: &mut Document) -> bool {
+        self.transform_drag = None;
+        let Some(floating) = self.floating.take() else { return false };
+        floating.cancel(doc);
+        true
+    }
 }
 
 impl Node for ViewportNode {}
 
 impl<'a> TickNode<RaylibTickBackend<'a>> for ViewportNode {
     fn dibs_tick(&mut self, tb: &mut RaylibTickBackend<'a>, slot: Rect, events: &mut Events) {
-        // todo
+        let RaylibTickBackend(_, _, doc, _) = tb;
+
+        // switching tools drops a shape or selection in progress, and stamps down anything being transformed
+        self.shape_draft.take_if(|draft| self.tool != Tool::Shape(draft.shape));
+        self.selection_draft.take_if(|draft| self.tool != Tool::Select(draft.tool));
+        if self.tool != Tool::Transform {
+            self.commit_transform(doc);
+        }
     }
 
     fn active_tick(&mut self, tb: &mut RaylibTickBackend<'a>, slot: Rect, events: &mut Events) {
-        let RaylibTickBackend(rl, thread) = tb;
+        let RaylibTickBackend(rl, thread, doc, _) = tb;
 
         self.brush_pos_prev = self.brush_pos;
+        self.is_alt_down = events.modifiers.alt;
         if let Some(mut mouse_event) = events.mouse_event.take() {
             let mouse_pos = Vector2::new(
                 mouse_event.position.x,
@@ -47,21 +216,29 @@ impl<'a> TickNode<RaylibTickBackend<'a>> for ViewportNode {
 
             // zoom/pan
             {
-                let is_zoom_scrolling = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL);
+                let is_zoom_scrolling = events.modifiers.ctrl;
+                // a focused widget may be using space for itself
+                let is_space_down = events.focus().is_none() && events.is_key_down(Key::Space);
 
                 if self.is_m1_space_panning {
-                    if events.left_mouse_release || rl.is_key_released(KeyboardKey::KEY_SPACE) {
+                    if events.is_mouse_released(MouseButton::Left) || !is_space_down {
                         self.is_m1_space_panning = false
                     }
                 } else {
-                    if rl.is_key_down(KeyboardKey::KEY_SPACE) {
-                        if mouse_event.left_mouse_press.take().is_some() {
+                    if is_space_down {
+                        if mouse_event.take_press(MouseButton::Left).is_some() {
                             self.is_m1_space_panning = true;
                         }
                     }
                 }
 
-                self.is_m3_panning = rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_MIDDLE);
+                if self.is_m3_panning {
+                    if !events.is_mouse_down(MouseButton::Middle) {
+                        self.is_m3_panning = false;
+                    }
+                } else if mouse_event.take_press(MouseButton::Middle).is_some() {
+                    self.is_m3_panning = true;
+                }
 
                 let is_pan_scrolling = !is_zoom_scrolling;
 
@@ -96,21 +273,133 @@ impl<'a> TickNode<RaylibTickBackend<'a>> for ViewportNode {
 
             let mouse_world_pos = rl.get_screen_to_world2D(mouse_pos, self.camera);
             self.brush_pos = Some(mouse_world_pos);
+            self.brush.update_tip(rl, thread);
 
             if self.is_drawing {
-                if events.left_mouse_release {
-                    self.is_drawing = false
+                if events.is_mouse_released(MouseButton::Left) {
+                    self.end_stroke(doc);
                 }
             } else {
-                if mouse_event.left_mouse_press.take().is_some() {
-                    self.is_drawing = true;
+                match self.current_tool() {
+                    Tool::Brush => if mouse_event.take_press(MouseButton::Left).is_some() {
+                        self.begin_stroke(rl, thread, doc);
+                    }
+
+                    Tool::Fill => if mouse_event.take_press(MouseButton::Left).is_some() && let Some(target) = self.brush.target() {
+                        doc.fill(rl, thread, target, mouse_world_pos, &self.brush.preset, &self.fill);
+                    }
+
+                    Tool::Shape(shape) => {
+                        if let Some(draft) = &mut self.shape_draft {
+                            draft.move_to(mouse_world_pos, events.modifiers.shift);
+                        }
+
+                        if let Some(click) = mouse_event.take_press(MouseButton::Left) {
+                            match &mut self.shape_draft {
+                                None => self.shape_draft = Some(ShapeDraft::begin(shape, mouse_world_pos)),
+                                Some(draft) => {
+                                    if is_closing_click(&draft.points, mouse_world_pos, click.count, self.camera.zoom) {
+                                        // the corner following the mouse is on top of the last one placed, or of the first
+                                        draft.points.pop();
+                                        self.commit_shape(rl, thread, doc);
+                                    } else {
+                                        draft.add_corner();
+                                    }
+                                }
+                            }
+                        }
+
+                        if shape.is_dragged() && self.shape_draft.is_some() && events.is_mouse_released(MouseButton::Left) {
+                            self.commit_shape(rl, thread, doc);
+                        } else if self.shape_draft.is_some() && events.take_shortcut(Key::Enter, Modifiers::NONE).is_some() {
+                            self.commit_shape(rl, thread, doc);
+                        } else if self.shape_draft.is_some() && events.take_shortcut(Key::Escape, Modifiers::NONE).is_some() {
+                            self.shape_draft = None;
+                        }
+                    }
+
+                    Tool::Select(SelectionTool::MagicWand) => if mouse_event.take_press(MouseButton::Left).is_some() {
+                        doc.select_similar(rl, thread, mouse_world_pos, &self.fill, SelectionOp::from_modifiers(events.modifiers));
+                    }
+
+                    Tool::Select(tool) => {
+                        if let Some(draft) = &mut self.selection_draft {
+                            draft.move_to(mouse_world_pos, events.modifiers.shift);
+                        }
+
+                        if let Some(click) = mouse_event.take_press(MouseButton::Left) {
+                            match &mut self.selection_draft {
+                                // the modifiers held when starting decide how the new selection combines with the old
+                                None => self.selection_draft = Some(SelectionDraft::begin(tool, SelectionOp::from_modifiers(events.modifiers), mouse_world_pos)),
+                                Some(draft) => {
+                                    if is_closing_click(&draft.points, mouse_world_pos, click.count, self.camera.zoom) {
+                                        draft.points.pop();
+                                        self.commit_selection(doc);
+                                    } else {
+                                        draft.add_corner();
+                                    }
+                                }
+                            }
+                        }
+
+                        if tool.is_dragged() && self.selection_draft.is_some() && events.is_mouse_released(MouseButton::Left) {
+                            self.commit_selection(doc);
+                        } else if self.selection_draft.is_some() && events.take_shortcut(Key::Enter, Modifiers::NONE).is_some() {
+                            self.commit_selection(doc);
+                        } else if self.selection_draft.is_some() && events.take_shortcut(Key::Escape, Modifiers::NONE).is_some() {
+                            self.selection_draft = None;
+                        }
+                    }
+
+                    Tool::Transform => {
+                        if mouse_event.take_press(MouseButton::Left).is_some() {
+                            if self.floating.is_none() && let Some(target) = self.brush.target() {
+                                self.floating = FloatingBuffer::lift(rl, thread, target, doc);
+                            }
+                            self.transform_drag = self.floating.as_ref().map(|floating| floating.placement.begin_drag(mouse_world_pos, self.camera.zoom));
+                        }
+
+                        if let Some(drag) = self.transform_drag && let Some(floating) = &mut self.floating {
+                            floating.placement.drag(drag, mouse_world_pos, events.modifiers.shift);
+                        }
+                        if events.is_mouse_released(MouseButton::Left) {
+                            self.transform_drag = None;
+                        }
+
+                        if self.floating.is_some() && events.take_shortcut(Key::Enter, Modifiers::NONE).is_some() {
+                            self.commit_transform(doc);
+                        } else if self.floating.is_some() && events.take_shortcut(Key::Escape, Modifiers::NONE).is_some() {
+                            self.cancel_transform(doc);
+                        }
+                    }
+
+                    Tool::Eyedropper => {
+                        if mouse_event.take_press(MouseButton::Left).is_some() {
+                            self.pick_source = doc.sample_pixels(rl, thread, self.pick_merged);
+                            self.pick_color(doc, mouse_world_pos);
+                        } else if self.pick_source.is_some() && events.is_mouse_down(MouseButton::Left) {
+                            // keep picking while dragging
+                            self.pick_color(doc, mouse_world_pos);
+                        } else {
+                            self.pick_source = None;
+                        }
+                    }
                 }
             }
 
             // edit artwork
             if self.is_drawing {
-                if let Some((mut d, preset)) = rl.begin_brush_target_mode(tb.1, &mut self.brush) {
-                    d.draw_line_brush(preset, self.brush_pos_prev.unwrap_or(mouse_world_pos), mouse_world_pos);
+                let brush_pos_prev = self.brush_pos_prev.unwrap_or(mouse_world_pos);
+                if let Some(stroke) = &mut self.stroke {
+                    let brush_radius = self.brush.preset.size.get() as f32 * 0.5;
+                    stroke.include(brush_pos_prev, brush_radius);
+                    stroke.include(mouse_world_pos, brush_radius);
+                }
+                if let Some(buffer) = &mut self.stroke_buffer {
+                    buffer.paint_dabs(rl, thread, &mut self.brush, brush_pos_prev, mouse_world_pos);
+                }
+                if let Some(clip) = &self.stroke_clip && let Some(selection) = &mut doc.selection && let Some(target) = self.brush.target() {
+                    clip.apply(rl, thread, target, selection);
                 }
             }
         } else {
@@ -119,7 +408,28 @@ impl<'a> TickNode<RaylibTickBackend<'a>> for ViewportNode {
     }
 
     fn inactive_tick(&mut self, tb: &mut RaylibTickBackend<'a>, slot: Rect, events: &Events) {
-        let RaylibTickBackend(rl, thread) = tb;
+        let RaylibTickBackend(rl, thread, doc, _) = tb;
+
+        if self.is_drawing && events.is_mouse_released(MouseButton::Left) {
+            self.end_stroke(doc);
+        }
+
+        if self.shape_draft.as_ref().is_some_and(|draft| draft.shape.is_dragged()) && events.is_mouse_released(MouseButton::Left) {
+            self.commit_shape(rl, thread, doc);
+        }
+
+        if self.selection_draft.as_ref().is_some_and(|draft| draft.tool.is_dragged()) && events.is_mouse_released(MouseButton::Left) {
+            self.commit_selection(doc);
+        }
+
+        if events.is_mouse_released(MouseButton::Left) {
+            self.transform_drag = None;
+            self.pick_source = None;
+        }
+
+        if self.is_m3_panning && !events.is_mouse_down(MouseButton::Middle) {
+            self.is_m3_panning = false;
+        }
 
         self.camera.target += rl.get_mouse_delta() / self.camera.zoom;
         self.camera.offset = rl.get_mouse_position();
@@ -128,9 +438,59 @@ impl<'a> TickNode<RaylibTickBackend<'a>> for ViewportNode {
     }
 }
 
+impl ViewportNode {
+    /// A magnified view of the pixels around `brush_pos`, beside the mouse, for the eyedropper.
+    /// It shows the same layers the eyedropper picks from, with the current brush color underneath.
+    fn draw_loupe(&self, backend: &mut RaylibDrawBackend, brush_pos: Vector2) {
+        const MOUSE_GAP: f32 = 16.0;
+        const SWATCH_HEIGHT: f32 = 12.0;
+        let size = LOUPE_PIXELS * LOUPE_ZOOM;
+        let mouse_pos = backend.0.get_world_to_screen2D(brush_pos, self.camera);
+        let rect = Rect {
+            x_min: mouse_pos.x + MOUSE_GAP,
+            y_min: mouse_pos.y - MOUSE_GAP - size - SWATCH_HEIGHT,
+            x_max: mouse_pos.x + MOUSE_GAP + size,
+            y_max: mouse_pos.y - MOUSE_GAP - SWATCH_HEIGHT,
+        };
+        let rec = Rectangle::new(rect.x_min, rect.y_min, size, size);
+        let pixel = Vector2::new(brush_pos.x.floor() + 0.5, brush_pos.y.floor() + 0.5);
+        let camera = Camera2D {
+            offset: Vector2::new(rect.x_min + size * 0.5, rect.y_min + size * 0.5),
+            target: pixel,
+            rotation: 0.0,
+            zoom: LOUPE_ZOOM,
+        };
+
+        backend.0.draw_rectangle_rec(rec, Color::BLACK);
+        backend.push_clip(&rect);
+        {
+            let RaylibDrawBackend(d, rasters, _, layer_tree, _, _, _) = &mut *backend;
+            let canvas = rasters.canvas();
+            let mut d = d.begin_mode2D(camera);
+            d.draw_rectangle_rec(canvas.rec, Color::new(64,64,64,255));
+            if self.pick_merged {
+                if let Some(buffer) = layer_tree.buffer() {
+                    let mut d = d.begin_blend_mode_a(LayerBlend::Normal.blend_mode());
+                    d.draw_texture_pro(buffer, canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, Color::WHITE);
+                }
+            } else if let Some(raster) = self.brush.target() {
+                d.draw_texture_pro(&*raster.borrow(), canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, Color::WHITE);
+            }
+            // the pixel that would be picked
+            d.draw_rectangle_lines_ex(Rectangle::new(pixel.x - 0.5, pixel.y - 0.5, 1.0, 1.0), LOUPE_ZOOM.recip(), CROSSHAIR_COLOR);
+        }
+        backend.pop_clip();
+
+        let d = &mut *backend.0;
+        d.draw_rectangle_rec(Rectangle::new(rect.x_min, rect.y_max, size, SWATCH_HEIGHT), self.brush.preset.color);
+        d.draw_rectangle_lines_ex(Rectangle::new(rect.x_min, rect.y_min, size, size + SWATCH_HEIGHT), 1.0, CROSSHAIR_COLOR);
+    }
+}
+
 impl DrawNode<RaylibDrawBackend<'_, '_, '_>> for ViewportNode {
-    fn draw(&self, d: &mut RaylibDrawBackend, slot: Rect) {
-        let RaylibDrawBackend(d, rasters, effects, layer_tree) = d;
+    fn draw(&self, backend: &mut RaylibDrawBackend, slot: Rect) {
+        let RaylibDrawBackend(d, rasters, effects, layer_tree, _, _, selection) = backend;
+        let time = d.get_time() as f32;
 
         // world
         {
@@ -140,18 +500,82 @@ impl DrawNode<RaylibDrawBackend<'_, '_, '_>> for ViewportNode {
             d.draw_rectangle_rec(rasters.canvas().rec, Color::new(64,64,64,255));
 
             // draw artwork
-            for layer in layer_tree.layers() {
-                layer.draw(&mut d, rasters.canvas());
+            if let Some(buffer) = layer_tree.buffer() {
+                let canvas = rasters.canvas();
+                let mut d = d.begin_blend_mode_a(LayerBlend::Normal.blend_mode());
+                d.draw_texture_pro(buffer, canvas.flipped_rec, canvas.rec, Vector2::zero(), 0.0, Color::WHITE);
+            }
+
+            // marching ants
+            if let Some(selection) = selection {
+                // dashes are a fixed size on screen and crawl along by one dash every quarter second
+                let dash = 4.0 * px_size;
+                let offset = time * 4.0 * dash;
+                for &[a, b] in selection.outline() {
+                    d.draw_line_ex(a, b, px_size, Color::WHITE);
+                    let length = (b - a).length();
+                    let direction = (b - a) / length;
+                    // measuring from x + y keeps the pattern going across corners, since segments only run right or down
+                    let mut t = -(a.x + a.y + offset).rem_euclid(2.0 * dash);
+                    while t < length {
+                        let (t0, t1) = (t.max(0.0), (t + dash).min(length));
+                        if t0 < t1 {
+                            d.draw_line_ex(a + direction * t0, a + direction * t1, px_size, Color::BLACK);
+                        }
+                        t += 2.0 * dash;
+                    }
+                }
+            }
+
+            // selection preview
+            if let Some(draft) = self.selection_draft.as_ref().filter(|draft| self.tool == Tool::Select(draft.tool)) {
+                d.draw_path_lines(&draft.outline(), px_size, CROSSHAIR_COLOR);
+            }
+
+            // floating buffer and its handles
+            if let Some(floating) = &self.floating {
+                floating.draw(&mut d, self.resample);
+                let placement = &floating.placement;
+                let corners = placement.corners();
+                d.draw_path_lines(&[corners[0], corners[1], corners[2], corners[3], corners[0]], px_size, CROSSHAIR_COLOR);
+
+                let zoom = self.camera.zoom;
+                let half = HANDLE_SIZE * 0.5 * px_size;
+                let rotate_handle = placement.handle_pos(Handle::Rotate, zoom);
+                d.draw_line_ex(placement.handle_pos(Handle::Scale(0, -1), zoom), rotate_handle, px_size, CROSSHAIR_COLOR);
+                d.draw_circle_v(rotate_handle, half, CROSSHAIR_COLOR);
+                for handle in Handle::SCALE {
+                    let pos = placement.handle_pos(handle, zoom);
+                    d.draw_rectangle_v(pos - Vector2::new(half, half), Vector2::new(half * 2.0, half * 2.0), CROSSHAIR_COLOR);
+                }
+            }
+
+            // shape preview
+            if let Some(draft) = self.shape_draft.as_ref().filter(|draft| self.tool == Tool::Shape(draft.shape)) {
+                let outline = draft.outline();
+                let preset = &self.brush.preset;
+                if preset.is_eraser() {
+                    // like the brush, erasing can't be previewed, so show where the shape goes instead
+                    d.draw_path_lines(&outline, px_size, CROSSHAIR_COLOR);
+                } else if self.fill_shapes && draft.shape.has_area() {
+                    d.draw_polygon_fill(&outline, preset.color);
+                } else {
+                    let mut since_last_dab = None;
+                    d.draw_dab_path(&mut self.brush.preview_stroke(&mut since_last_dab), &outline);
+                }
             }
 
             if let Some(brush_pos) = self.brush_pos {
+                let tool = self.current_tool();
+
                 // brush preview
-                d.draw_line_brush(&self.brush.preset, self.brush_pos_prev.unwrap_or(brush_pos), brush_pos);
+                // erasing can't be previewed without the canvas underneath, so leave it to the crosshair
+                if tool == Tool::Brush && let Some(tip) = self.brush.tip().filter(|_| !self.brush.preset.is_eraser()) {
+                    d.draw_dab(&self.brush.preset, tip, brush_pos);
+                }
 
                 // crosshair
                 {
-                    const CROSSHAIR_COLOR: Color = Color::new(200,200,200,255);
-                    let brush_radius = self.brush.preset.size.get() as f32 * 0.5;
                     let mut d = d.begin_blend_mode_a(BlendModeA::CustomSeparate {
                         src_rgb: BlendFactor::OneMinusDstColor,
                         dst_rgb: BlendFactor::OneMinusSrcColor,
@@ -160,9 +584,30 @@ impl DrawNode<RaylibDrawBackend<'_, '_, '_>> for ViewportNode {
                         eq_rgb: BlendEquation::FuncAdd,
                         eq_alpha: BlendEquation::FuncAdd,
                     });
-                    d.draw_ring(brush_pos, brush_radius, brush_radius + px_size, 0.0, 360.0, 20, CROSSHAIR_COLOR);
+                    match tool {
+                        Tool::Brush | Tool::Shape(_) => {
+                            let brush_radius = self.brush.preset.size.get() as f32 * 0.5;
+                            d.draw_ring(brush_pos, brush_radius, brush_radius + px_size, 0.0, 360.0, 20, CROSSHAIR_COLOR);
+                        }
+                        Tool::Fill | Tool::Select(_) | Tool::Transform | Tool::Eyedropper => {
+                            // a plus over the pixel that the fill, magic wand or eyedropper would use, or right on the mouse otherwise
+                            let arm = 6.0 * px_size;
+                            let center = if matches!(tool, Tool::Fill | Tool::Select(SelectionTool::MagicWand) | Tool::Eyedropper) {
+                                Vector2::new(brush_pos.x.floor() + 0.5, brush_pos.y.floor() + 0.5)
+                            } else {
+                                brush_pos
+                            };
+                            d.draw_line_ex(center - Vector2::new(arm, 0.0), center + Vector2::new(arm, 0.0), px_size, CROSSHAIR_COLOR);
+                            d.draw_line_ex(center - Vector2::new(0.0, arm), center + Vector2::new(0.0, arm), px_size, CROSSHAIR_COLOR);
+                        }
+                    }
                 }
             }
         }
+
+        // loupe
+        if let Some(brush_pos) = self.brush_pos && self.current_tool() == Tool::Eyedropper {
+            self.draw_loupe(backend, brush_pos);
+        }
     }
 }